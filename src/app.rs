@@ -1,16 +1,37 @@
 use crate::span::cursor::CursorSpan;
+use crate::span::alerts::AlertsSpan;
+use crate::span::picker::PickerSpan;
+use crate::span::worklog::WorklogSpan;
+use crate::span::chart::ChartSpan;
+use crate::span::pointer::PointerEvent;
 use crate::span::godswood;
+use crate::span::godswood::worklog::WorklogFilter;
+use crate::utils::timestamp_ms;
 
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 pub struct Application {
     app: wand::core::Application,
+    godswood: godswood::GodswoodHandle,
 }
 
 #[wasm_bindgen]
 impl Application {
     pub fn new() -> Self {
+        Application::new_with_config("{}")
+    }
+
+    // JSON configuration, like
+    // `{"source": {"kind": "websocket", "url": "wss://..."}, "rules": ["status == down on .shop.**"]}`
+    pub fn new_with_config(config: &str) -> Self {
+        let config: serde_json::Value = match serde_json::from_str(config) {
+            Ok(config) => config,
+            Err(err) => {
+                log!("Ignored bad configuration: {}", err);
+                serde_json::Value::Null
+            },
+        };
         let mut app = wand::Application::new_with_canvas_id("canvas");
         let state = app.get_state();
 
@@ -20,11 +41,35 @@ impl Application {
         let section_0_2 = app.new_section("alerts", 1., 1., 0.);
         let section_1 = app.new_section("worklog", 1., 1., 0.);
         let cursor_span = CursorSpan::new(state.clone(), app.counter.clone(), "cursor", "Cursor:(N/A)", 0.2, 0.2);
-        let world_span = godswood::create_godswood(&app);
+        let (world_span, godswood) = godswood::create_godswood(&app, &config);
         {
+            // Registered after the world to draw its tooltips above it
+            let picker_span = PickerSpan::new(
+                godswood.selection.clone(), godswood.control.clone(), godswood.node_tooltip(), "picker", 1., 1.);
             let mut section = section_0_1.borrow_mut();
             section.register_span(cursor_span);
             section.register_span(world_span);
+            section.register_span(picker_span);
+        }
+        {
+            // Alerts on top, charts of the selected node below
+            let alerts_span = AlertsSpan::new(godswood.alerts.clone(), godswood.selection.clone(), "alerts", 1., 0.4);
+            let requests = godswood.metric_chart("Requests", vec![
+                ("request_rate".to_string(), "#42c8f5".to_string()),
+            ], 5 * 60_000);
+            let requests_span = ChartSpan::new(requests, 5 * 60_000, "request_chart", 1., 0.3, 0.4);
+            let errors = godswood.metric_chart("Errors", vec![
+                ("error_rate".to_string(), "#eb4034".to_string()),
+            ], 5 * 60_000);
+            let errors_span = ChartSpan::new(errors, 5 * 60_000, "error_chart", 1., 0.3, 0.7);
+            let mut section = section_0_2.borrow_mut();
+            section.register_span(alerts_span);
+            section.register_span(requests_span);
+            section.register_span(errors_span);
+        }
+        {
+            let worklog_span = WorklogSpan::new(godswood.worklog.clone(), "worklog", 1., 1.);
+            section_1.borrow_mut().register_span(worklog_span);
         }
         {
             let mut section = section_0.borrow_mut();
@@ -38,6 +83,7 @@ impl Application {
 
         Self {
             app,
+            godswood,
         }
     }
 
@@ -74,6 +120,210 @@ impl Application {
                 Box::new(format!("Cursor: x: {}, y: {}", x, y))
             );
         }
+        for name in ["picker", "request_chart", "error_chart"].iter() {
+            self.dispatch_span(name, Box::new(PointerEvent::Move { x, y }));
+        }
         // self.app.draw();
     }
+
+    pub fn on_mouse_down(&mut self, x: f64, y: f64) {
+        self.dispatch_span("picker", Box::new(PointerEvent::Down { x, y }));
+    }
+
+    pub fn on_mouse_up(&mut self, x: f64, y: f64) {
+        self.dispatch_span("picker", Box::new(PointerEvent::Up { x, y }));
+    }
+
+    // Pointer at `x`, `y` moved by `dx`, `dy` with a button held, the world camera orbits, or pans
+    // when `pan` like for the right button or shift
+    pub fn on_drag(&mut self, x: f64, y: f64, dx: f64, dy: f64, pan: bool) {
+        self.dispatch_span("picker", Box::new(PointerEvent::Drag { x, y, dx, dy, pan }));
+    }
+
+    pub fn on_click(&mut self, x: f64, y: f64) {
+        self.dispatch_span("alerts", Box::new(PointerEvent::Click { x, y }));
+        self.dispatch_span("picker", Box::new(PointerEvent::Click { x, y }));
+    }
+
+    // Select the node at `path`, like a search result, and fly the camera to it
+    pub fn select_node(&mut self, path: &str) -> bool {
+        self.godswood.focus_node(path)
+    }
+
+    // Path of the selected node, empty when none
+    pub fn selected_node(&self) -> String {
+        self.godswood.selection.borrow().get_selected().cloned().unwrap_or_default()
+    }
+
+    // Positive `delta` scrolls down, in pixels
+    pub fn on_wheel(&mut self, x: f64, y: f64, delta: f64) {
+        // Scrolls the worklog or zooms the world, whichever is under the pointer
+        for name in ["worklog", "picker"].iter() {
+            self.dispatch_span(name, Box::new(PointerEvent::Wheel { x, y, delta }));
+        }
+    }
+
+    // Show worklog entries at least as severe as `severity` on nodes matching the `path` pattern,
+    // like `.shop.**`, empty shows all
+    pub fn worklog_filter(&mut self, severity: &str, path: &str) {
+        self.dispatch_span("worklog", Box::new(WorklogFilter::parse(severity, path)));
+    }
+
+    // Operator note in the worklog, on the node at `path` unless empty
+    pub fn add_note(&mut self, path: &str, message: &str) {
+        let path = if path.is_empty() { None } else { Some(path) };
+        self.godswood.worklog.borrow_mut().add_note(timestamp_ms(), path, message);
+    }
+
+    fn dispatch_span(&self, name: &str, data: Box<dyn std::any::Any>) {
+        let state = self.app.get_state();
+        let state = state.borrow_mut();
+        if let Some(span) = state.fetch_span(name) {
+            let mut span = span.borrow_mut();
+            span.as_mut().dispath(data);
+        }
+    }
+
+    // Keyboard camera turn and move speeds, kept across reloads, false unless both are above zero
+    pub fn set_preferences(&mut self, turn_speed: f32, move_speed: f32) -> bool {
+        self.godswood.set_preferences(turn_speed, move_speed)
+    }
+
+    // Wood in `format`, json, yaml or toml, or a file name to take the format from like `shop.yaml`
+    pub fn load_wood(&mut self, raw: &str, format: &str) -> bool {
+        match self.godswood.load_wood(raw, format) {
+            Ok(()) => true,
+            Err(err) => {
+                log!("Failed to load wood: {}", err);
+                false
+            },
+        }
+    }
+
+    // JSON list of the paths of nodes matching the pattern, like `.shop.**.db-*`
+    pub fn query_nodes(&self, pattern: &str) -> String {
+        match self.godswood.query_nodes(pattern) {
+            Some(paths) => serde_json::Value::from(paths).to_string(),
+            None => {
+                log!("Bad node pattern {}", pattern);
+                "[]".to_string()
+            },
+        }
+    }
+
+    // Canonical JSON of the wood named `name`, loads back with `load_wood`, empty for an unknown wood
+    pub fn export_json(&self, name: &str) -> String {
+        self.godswood.export_wood(name, "json").unwrap_or_default()
+    }
+
+    // Graphviz DOT of the wood named `name`, empty for an unknown wood
+    pub fn export_dot(&self, name: &str) -> String {
+        self.godswood.export_wood(name, "dot").unwrap_or_default()
+    }
+
+    // Mermaid flowchart of the wood named `name`, empty for an unknown wood
+    pub fn export_mermaid(&self, name: &str) -> String {
+        self.godswood.export_wood(name, "mermaid").unwrap_or_default()
+    }
+
+    // Like `query_nodes`, keeping the nodes accepted by the filter, space separated terms like
+    // `#critical owner=team-* payments` for a tag, an attribute glob and a text to find
+    pub fn search_nodes(&self, pattern: &str, filter: &str) -> String {
+        match self.godswood.search_nodes(pattern, filter) {
+            Some(paths) => serde_json::Value::from(paths).to_string(),
+            None => {
+                log!("Bad node pattern {}", pattern);
+                "[]".to_string()
+            },
+        }
+    }
+
+    // Add, remove, rename or move a node of a live wood, like
+    // `{"op": "move", "path": ".shop.cache", "parent": ".shop.api"}`
+    pub fn patch_wood(&mut self, patch: &str) -> bool {
+        let result = serde_json::from_str(patch).map_err(|err| err.to_string())
+            .and_then(|patch| self.godswood.patch_wood(&patch).map_err(|err| err.to_string()));
+        match result {
+            Ok(()) => true,
+            Err(err) => {
+                log!("Dropped bad wood patch: {}", err);
+                false
+            },
+        }
+    }
+
+    // Health of the node at `path`, like `down`, `at` is the time of the change in ms like `Date.now()`
+    pub fn set_status(&mut self, path: &str, health: &str, reason: &str, at: f64) -> bool {
+        if !self.godswood.set_status(path, health, reason, at as u64) {
+            log!("Dropped status {} of node {}", health, path);
+            return false;
+        }
+        true
+    }
+
+    // Drop a wood and despawn the nodes only it holds
+    pub fn remove_wood(&mut self, name: &str) -> bool {
+        self.godswood.remove_wood(name)
+    }
+
+    // Rule text or JSON like in the configuration, replacing the rule with the same name
+    pub fn add_alert_rule(&mut self, rule: &str) -> bool {
+        let raw = serde_json::from_str(rule).unwrap_or_else(|_| serde_json::Value::from(rule));
+        match godswood::rule::AlertRule::from_json(&raw) {
+            Ok(rule) => {
+                self.godswood.rules.borrow_mut().add_rule(rule);
+                true
+            },
+            Err(err) => {
+                log!("Dropped bad alert rule: {}", err);
+                false
+            },
+        }
+    }
+
+    pub fn remove_alert_rule(&mut self, name: &str) -> bool {
+        self.godswood.rules.borrow_mut().remove_rule(name)
+    }
+
+    // Replay what was recorded from its start, at `speed` times real time
+    pub fn replay_start(&mut self, speed: f64) {
+        self.godswood.recorder.borrow_mut().start_replay(speed);
+    }
+
+    pub fn replay_stop(&mut self) {
+        self.godswood.recorder.borrow_mut().stop_replay();
+    }
+
+    pub fn replay_pause(&mut self, paused: bool) {
+        self.godswood.recorder.borrow_mut().set_paused(paused);
+    }
+
+    pub fn replay_speed(&mut self, speed: f64) {
+        self.godswood.recorder.borrow_mut().set_speed(speed);
+    }
+
+    // `at` is a timestamp in ms within the recording
+    pub fn replay_seek(&mut self, at: f64) {
+        self.godswood.recorder.borrow_mut().seek(at as u64);
+    }
+
+    // JSON with the recording range and the replay position, for scrubbing controls
+    pub fn replay_state(&self) -> String {
+        self.godswood.recorder.borrow().state_json().to_string()
+    }
+
+    // Recording file content
+    pub fn export_recording(&self) -> String {
+        self.godswood.recorder.borrow().to_json().to_string()
+    }
+
+    pub fn import_recording(&mut self, raw: &str) -> bool {
+        match self.godswood.recorder.borrow_mut().import(raw) {
+            Ok(()) => true,
+            Err(err) => {
+                log!("Failed to import recording: {}", err);
+                false
+            },
+        }
+    }
 }
@@ -0,0 +1,162 @@
+use wasm_bindgen::prelude::*;
+use std::any::Any;
+use crate::utils::{timestamp_ms, format_duration};
+use crate::span::pointer::PointerEvent;
+use crate::span::godswood::alert::{Alert, Alerts};
+use crate::span::godswood::select::Selection;
+
+const HEADER_HEIGHT: f64 = 24.;
+const ROW_HEIGHT: f64 = 20.;
+const PADDING: f64 = 6.;
+
+// Active alerts, most severe and oldest first, clicking an alert selects its node and focuses the
+// camera on it
+pub struct AlertsSpan {
+    pub name: String,
+
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+
+    pub width: f32,
+    pub height: f32,
+    pub order: u8,
+
+    alerts: Alerts,
+    selection: Selection,
+}
+
+impl AlertsSpan {
+    pub fn new(
+        alerts: Alerts,
+        selection: Selection,
+        name: &str,
+        width: f32, height: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            x: 0.,
+            y: 0.,
+            w: 0.,
+            h: 0.,
+
+            width,
+            height,
+            order: 1,
+            alerts,
+            selection,
+        }
+    }
+
+    // Rows fitting in the span, one is kept for the overflow count when needed
+    fn visible_rows(&self, total: usize) -> usize {
+        let rows = ((self.h - HEADER_HEIGHT) / ROW_HEIGHT).floor().max(0.) as usize;
+        if total > rows { rows.saturating_sub(1) } else { total }
+    }
+
+    // Alert at the canvas point
+    fn alert_at(&self, x: f64, y: f64) -> Option<Alert> {
+        if x < self.x || x > self.x + self.w || y < self.y + HEADER_HEIGHT || y > self.y + self.h {
+            return None;
+        }
+        let row = ((y - self.y - HEADER_HEIGHT) / ROW_HEIGHT).floor() as usize;
+        let alerts = self.alerts.borrow();
+        let active = alerts.get_active();
+        if row >= self.visible_rows(active.len()) {
+            return None;
+        }
+        active.get(row).map(|alert| (*alert).clone())
+    }
+
+    fn draw_outline(&self, ctx: &web_sys::CanvasRenderingContext2d) {
+        ctx.set_stroke_style(&JsValue::from_str("white"));
+        ctx.stroke_rect(self.x, self.y, self.w, self.h);
+    }
+
+    fn draw_row(&self, ctx: &web_sys::CanvasRenderingContext2d, alert: &Alert, top: f64, now: u64, selected: bool) {
+        if selected {
+            ctx.set_fill_style(&JsValue::from_str("rgba(255, 255, 255, 0.1)"));
+            ctx.fill_rect(self.x + 1., top, self.w - 2., ROW_HEIGHT);
+        }
+        ctx.set_fill_style(&JsValue::from_str(alert.severity.color()));
+        ctx.fill_rect(self.x + PADDING, top + 3., 4., ROW_HEIGHT - 6.);
+
+        // Path, age and message columns
+        let middle = top + ROW_HEIGHT / 2.;
+        let left = self.x + PADDING + 10.;
+        let inner = (self.w - PADDING - 10. - PADDING).max(0.);
+        let _ = ctx.fill_text_with_max_width(&alert.path, left, middle, inner * 0.4);
+        ctx.set_fill_style(&JsValue::from_str("gray"));
+        let _ = ctx.fill_text_with_max_width(
+            &format_duration(now.saturating_sub(alert.since)), left + inner * 0.42, middle, inner * 0.12
+        );
+        ctx.set_fill_style(&JsValue::from_str("lightgray"));
+        let _ = ctx.fill_text_with_max_width(&alert.message, left + inner * 0.56, middle, inner * 0.44);
+    }
+}
+
+impl wand::SpanTrait for AlertsSpan {
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn dispatch_event(&mut self, _ev: &mut wand::component::Event) {
+    }
+
+    fn dispath(&mut self, data: Box<dyn Any>) {
+        if let Ok(event) = data.downcast::<PointerEvent>() {
+            if let PointerEvent::Click { x, y } = *event {
+                if let Some(alert) = self.alert_at(x, y) {
+                    self.selection.borrow_mut().select(&alert.path, true);
+                }
+            }
+        }
+    }
+
+    fn render_tick(&self, ctx: &web_sys::CanvasRenderingContext2d) {
+        self.draw_outline(ctx);
+        let alerts = self.alerts.borrow();
+        let active = alerts.get_active();
+        let now = timestamp_ms();
+
+        ctx.set_text_align("left");
+        ctx.set_text_baseline("middle");
+        ctx.set_font("14px Arial");
+        ctx.set_fill_style(&JsValue::from_str("white"));
+        let _ = ctx.fill_text(&format!("Alerts ({})", active.len()), self.x + PADDING, self.y + HEADER_HEIGHT / 2.);
+
+        ctx.set_font("12px Arial");
+        if active.is_empty() {
+            ctx.set_fill_style(&JsValue::from_str("gray"));
+            let _ = ctx.fill_text("No active alerts", self.x + PADDING, self.y + HEADER_HEIGHT + ROW_HEIGHT / 2.);
+            return;
+        }
+
+        let selection = self.selection.borrow();
+        let selected = selection.get_selected();
+        let rows = self.visible_rows(active.len());
+        for (i, alert) in active.iter().take(rows).enumerate() {
+            let top = self.y + HEADER_HEIGHT + i as f64 * ROW_HEIGHT;
+            self.draw_row(ctx, alert, top, now, selected == Some(&alert.path));
+        }
+        if rows < active.len() {
+            ctx.set_fill_style(&JsValue::from_str("gray"));
+            let top = self.y + HEADER_HEIGHT + rows as f64 * ROW_HEIGHT;
+            let _ = ctx.fill_text(&format!("+{} more", active.len() - rows), self.x + PADDING, top + ROW_HEIGHT / 2.);
+        }
+    }
+
+    fn on_resize(&mut self, left: f64, top: f64, right: f64, bottom: f64) -> (f64, f64, bool) {
+        self.x = left;
+        self.y = top;
+        self.w = self.width as f64 * (right - left);
+        self.h = self.height as f64 * (bottom - top);
+        (0., 0., true)
+    }
+
+    fn get_order(&self) -> u8 {
+        self.order
+    }
+
+}
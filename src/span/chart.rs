@@ -0,0 +1,376 @@
+use wasm_bindgen::prelude::*;
+use std::any::Any;
+use crate::utils::{timestamp_ms, format_clock};
+use crate::span::pointer::PointerEvent;
+
+// Room around the plot for the title, legend and tick labels
+const MARGIN_LEFT: f64 = 48.;
+const MARGIN_RIGHT: f64 = 12.;
+const MARGIN_TOP: f64 = 26.;
+const MARGIN_BOTTOM: f64 = 20.;
+const TICKS: f64 = 5.;
+// Time ticks step by one of these, in ms
+const TIME_STEPS: [u64; 12] = [
+    1_000, 2_000, 5_000, 10_000, 15_000, 30_000,
+    60_000, 120_000, 300_000, 600_000, 1_800_000, 3_600_000,
+];
+
+// Points of a line in time order, as timestamps in ms and values
+pub struct ChartSeries {
+    pub label: String,
+    pub color: String,
+    pub points: Vec<(u64, f64)>,
+}
+
+// Horizontal line at `value`
+pub struct ChartThreshold {
+    pub label: String,
+    pub value: f64,
+    pub color: String,
+}
+
+pub struct ChartData {
+    pub title: String,
+    pub series: Vec<ChartSeries>,
+    pub thresholds: Vec<ChartThreshold>,
+}
+
+// Data of the chart for the current time in ms, called on every render
+pub type ChartProvider = Box<dyn Fn(u64) -> ChartData>;
+
+// Round step near `raw`, 1, 2 or 5 times a power of ten
+fn nice_step(raw: f64) -> f64 {
+    if raw <= 0. || !raw.is_finite() {
+        return 1.;
+    }
+    let power = 10f64.powf(raw.log10().floor());
+    let fraction = raw / power;
+    let nice = if fraction <= 1. { 1. } else if fraction <= 2. { 2. } else if fraction <= 5. { 5. } else { 10. };
+    nice * power
+}
+
+fn format_value(value: f64, step: f64) -> String {
+    let decimals = if step >= 1. { 0 } else { (-step.log10().floor()) as usize };
+    format!("{:.*}", decimals, value)
+}
+
+// Time series chart over the last `window` ms, with a crosshair reading the values under the pointer
+pub struct ChartSpan {
+    pub name: String,
+
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+
+    pub width: f32,
+    pub height: f32,
+    // Distance from the top of the section, as a fraction of its height
+    pub offset: f32,
+    pub order: u8,
+
+    window: u64,
+    provider: ChartProvider,
+    hover: Option<(f64, f64)>,
+}
+
+impl ChartSpan {
+    pub fn new(
+        provider: ChartProvider,
+        window: u64,
+        name: &str,
+        width: f32, height: f32, offset: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            x: 0.,
+            y: 0.,
+            w: 0.,
+            h: 0.,
+
+            width,
+            height,
+            offset,
+            order: 1,
+            window,
+            provider,
+            hover: None,
+        }
+    }
+
+    // Plot area as left, top, width and height
+    fn plot(&self) -> (f64, f64, f64, f64) {
+        (
+            self.x + MARGIN_LEFT,
+            self.y + MARGIN_TOP,
+            (self.w - MARGIN_LEFT - MARGIN_RIGHT).max(1.),
+            (self.h - MARGIN_TOP - MARGIN_BOTTOM).max(1.),
+        )
+    }
+
+    // Value range rounded out to whole ticks, with its tick step, values out of range are left out
+    fn value_range(data: &ChartData) -> (f64, f64, f64) {
+        let values = data.series.iter().flat_map(|series| series.points.iter().map(|(_, value)| *value))
+            .chain(data.thresholds.iter().map(|threshold| threshold.value))
+            .filter(|value| value.is_finite());
+        let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| (min.min(value), max.max(value)));
+        if !min.is_finite() {
+            return (0., 1., 0.2);
+        }
+        // Rates and counts read better from zero
+        let min = if min > 0. { 0. } else { min };
+        let max = if max > min { max } else { min + 1. };
+        let step = nice_step((max - min) / TICKS);
+        let (min, max) = ((min / step).floor() * step, (max / step).ceil() * step);
+        if !(max - min).is_finite() {
+            return (0., 1., 0.2);
+        }
+        (min, max, step)
+    }
+
+    fn draw_outline(&self, ctx: &web_sys::CanvasRenderingContext2d) {
+        ctx.set_stroke_style(&JsValue::from_str("white"));
+        ctx.stroke_rect(self.x, self.y, self.w, self.h);
+    }
+
+    fn draw_axes(&self, ctx: &web_sys::CanvasRenderingContext2d, start: u64, end: u64, (min, max, step): (f64, f64, f64)) {
+        let (left, top, width, height) = self.plot();
+        ctx.set_line_width(1.);
+        ctx.set_stroke_style(&JsValue::from_str("gray"));
+        ctx.set_fill_style(&JsValue::from_str("gray"));
+        ctx.begin_path();
+        ctx.move_to(left, top);
+        ctx.line_to(left, top + height);
+        ctx.line_to(left + width, top + height);
+        ctx.stroke();
+
+        ctx.set_font("10px Arial");
+        ctx.set_text_align("right");
+        ctx.set_text_baseline("middle");
+        let ticks = ((max - min) / step).round() as usize;
+        for value in (0..=ticks.min(TICKS as usize + 1)).map(|tick| min + tick as f64 * step) {
+            let y = top + height * (1. - (value - min) / (max - min));
+            ctx.fill_rect(left - 3., y, 3., 1.);
+            let _ = ctx.fill_text(&format_value(value, step), left - 5., y);
+        }
+
+        let span = end - start;
+        let time_step = TIME_STEPS.iter().cloned().find(|step| span / step <= TICKS as u64).unwrap_or(span.max(1));
+        ctx.set_text_align("center");
+        ctx.set_text_baseline("top");
+        let mut at = start - start % time_step + time_step;
+        while at <= end {
+            let x = left + width * (at - start) as f64 / span as f64;
+            ctx.fill_rect(x, top + height, 1., 3.);
+            let _ = ctx.fill_text(&format_clock(at), x, top + height + 5.);
+            at += time_step;
+        }
+    }
+
+    fn draw_title(&self, ctx: &web_sys::CanvasRenderingContext2d, data: &ChartData) {
+        ctx.set_font("12px Arial");
+        ctx.set_text_baseline("middle");
+        ctx.set_text_align("left");
+        ctx.set_fill_style(&JsValue::from_str("white"));
+        let _ = ctx.fill_text(&data.title, self.x + 6., self.y + MARGIN_TOP / 2.);
+
+        // Legend from the right edge, with the latest value of each series
+        ctx.set_font("10px Arial");
+        ctx.set_text_align("right");
+        let mut right = self.x + self.w - MARGIN_RIGHT;
+        for series in data.series.iter().rev() {
+            let text = match series.points.last() {
+                Some((_, value)) => format!("{} {:.2}", series.label, value),
+                None => series.label.clone(),
+            };
+            ctx.set_fill_style(&JsValue::from_str("lightgray"));
+            let _ = ctx.fill_text(&text, right, self.y + MARGIN_TOP / 2.);
+            let text_width = ctx.measure_text(&text).map_or(0., |metrics| metrics.width());
+            ctx.set_fill_style(&JsValue::from_str(&series.color));
+            ctx.fill_rect(right - text_width - 12., self.y + MARGIN_TOP / 2. - 4., 8., 8.);
+            right -= text_width + 24.;
+        }
+    }
+
+    fn draw_thresholds(&self, ctx: &web_sys::CanvasRenderingContext2d, data: &ChartData, (min, max, _): (f64, f64, f64)) {
+        let (left, top, width, height) = self.plot();
+        let dash = js_sys::Array::of2(&JsValue::from_f64(4.), &JsValue::from_f64(4.));
+        let _ = ctx.set_line_dash(&dash);
+        ctx.set_font("10px Arial");
+        ctx.set_text_align("right");
+        ctx.set_text_baseline("bottom");
+        for threshold in data.thresholds.iter() {
+            let y = top + height * (1. - (threshold.value - min) / (max - min));
+            ctx.set_stroke_style(&JsValue::from_str(&threshold.color));
+            ctx.begin_path();
+            ctx.move_to(left, y);
+            ctx.line_to(left + width, y);
+            ctx.stroke();
+            ctx.set_fill_style(&JsValue::from_str(&threshold.color));
+            let _ = ctx.fill_text(&threshold.label, left + width - 2., y - 2.);
+        }
+        let _ = ctx.set_line_dash(&js_sys::Array::new());
+    }
+
+    fn draw_series(&self, ctx: &web_sys::CanvasRenderingContext2d, data: &ChartData, start: u64, end: u64, (min, max, _): (f64, f64, f64)) {
+        let (left, top, width, height) = self.plot();
+        let span = (end - start) as f64;
+        ctx.set_line_width(1.5);
+        for series in data.series.iter() {
+            ctx.set_stroke_style(&JsValue::from_str(&series.color));
+            ctx.begin_path();
+            for (i, (at, value)) in series.points.iter().filter(|(at, _)| *at >= start).enumerate() {
+                let x = left + width * (at - start) as f64 / span;
+                let y = top + height * (1. - (value - min) / (max - min));
+                if i == 0 {
+                    ctx.move_to(x, y);
+                } else {
+                    ctx.line_to(x, y);
+                }
+            }
+            ctx.stroke();
+        }
+        ctx.set_line_width(1.);
+    }
+
+    // Vertical line under the pointer with the closest value of each series
+    fn draw_crosshair(&self, ctx: &web_sys::CanvasRenderingContext2d, data: &ChartData, start: u64, end: u64, (min, max, _): (f64, f64, f64)) {
+        let (left, top, width, height) = self.plot();
+        let (x, _) = match self.hover {
+            Some((x, y)) if x >= left && x <= left + width && y >= top && y <= top + height => (x, y),
+            _ => return,
+        };
+        let at = start + ((x - left) / width * (end - start) as f64) as u64;
+        ctx.set_stroke_style(&JsValue::from_str("rgba(255, 255, 255, 0.5)"));
+        ctx.begin_path();
+        ctx.move_to(x, top);
+        ctx.line_to(x, top + height);
+        ctx.stroke();
+
+        let mut lines = vec![(format_clock(at), "white".to_string())];
+        for series in data.series.iter() {
+            let closest = series.points.iter().filter(|(at, _)| *at >= start)
+                .min_by_key(|(point_at, _)| (*point_at as i64 - at as i64).abs());
+            if let Some((point_at, value)) = closest {
+                let point_x = left + width * (point_at - start) as f64 / (end - start) as f64;
+                let point_y = top + height * (1. - (value - min) / (max - min));
+                ctx.set_fill_style(&JsValue::from_str(&series.color));
+                ctx.fill_rect(point_x - 2., point_y - 2., 4., 4.);
+                lines.push((format!("{} {:.2}", series.label, value), series.color.clone()));
+            }
+        }
+
+        // Readout on the side of the crosshair with more room
+        ctx.set_font("10px Arial");
+        ctx.set_text_align("left");
+        ctx.set_text_baseline("top");
+        let box_width = lines.iter().map(|(text, _)| ctx.measure_text(text).map_or(0., |metrics| metrics.width())).fold(0., f64::max) + 8.;
+        let box_height = lines.len() as f64 * 12. + 4.;
+        let box_x = if x + box_width + 6. > left + width { x - box_width - 6. } else { x + 6. };
+        ctx.set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.7)"));
+        ctx.fill_rect(box_x, top + 2., box_width, box_height);
+        for (i, (text, color)) in lines.iter().enumerate() {
+            ctx.set_fill_style(&JsValue::from_str(color));
+            let _ = ctx.fill_text(text, box_x + 4., top + 4. + i as f64 * 12.);
+        }
+    }
+}
+
+impl wand::SpanTrait for ChartSpan {
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn dispatch_event(&mut self, _ev: &mut wand::component::Event) {
+    }
+
+    fn dispath(&mut self, data: Box<dyn Any>) {
+        if let Ok(event) = data.downcast::<PointerEvent>() {
+            if let PointerEvent::Move { x, y } = *event {
+                self.hover = Some((x, y));
+            }
+        }
+    }
+
+    fn render_tick(&self, ctx: &web_sys::CanvasRenderingContext2d) {
+        self.draw_outline(ctx);
+        let end = timestamp_ms();
+        let start = end.saturating_sub(self.window);
+        let data = (self.provider)(end);
+        let range = ChartSpan::value_range(&data);
+
+        self.draw_title(ctx, &data);
+        self.draw_axes(ctx, start, end, range);
+        if data.series.iter().all(|series| series.points.is_empty()) {
+            let (left, top, width, height) = self.plot();
+            ctx.set_font("12px Arial");
+            ctx.set_text_align("center");
+            ctx.set_text_baseline("middle");
+            ctx.set_fill_style(&JsValue::from_str("gray"));
+            let _ = ctx.fill_text("No data", left + width / 2., top + height / 2.);
+            return;
+        }
+        self.draw_thresholds(ctx, &data, range);
+        self.draw_series(ctx, &data, start, end, range);
+        self.draw_crosshair(ctx, &data, start, end, range);
+    }
+
+    fn on_resize(&mut self, left: f64, top: f64, right: f64, bottom: f64) -> (f64, f64, bool) {
+        self.x = left;
+        self.y = top + self.offset as f64 * (bottom - top);
+        self.w = self.width as f64 * (right - left);
+        self.h = self.height as f64 * (bottom - top);
+        (0., 0., true)
+    }
+
+    fn get_order(&self) -> u8 {
+        self.order
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(values: &[f64], thresholds: &[f64]) -> ChartData {
+        ChartData {
+            title: String::new(),
+            series: vec![ChartSeries {
+                label: String::new(),
+                color: String::new(),
+                points: values.iter().enumerate().map(|(i, value)| (i as u64, *value)).collect(),
+            }],
+            thresholds: thresholds.iter().map(|value| ChartThreshold {
+                label: String::new(),
+                value: *value,
+                color: String::new(),
+            }).collect(),
+        }
+    }
+
+    #[test]
+    fn rounds_steps() {
+        assert_eq!(nice_step(0.), 1.);
+        assert_eq!(nice_step(f64::NAN), 1.);
+        assert_eq!(nice_step(1.), 1.);
+        assert_eq!(nice_step(1.5), 2.);
+        assert_eq!(nice_step(3.), 5.);
+        assert_eq!(nice_step(7.), 10.);
+        assert_eq!(nice_step(240.), 500.);
+        assert_eq!(format_value(12.4, 5.), "12");
+        assert_eq!(format_value(0.25, 0.05), "0.25");
+    }
+
+    #[test]
+    fn ranges_values() {
+        assert_eq!(ChartSpan::value_range(&data(&[], &[])), (0., 1., 0.2));
+        assert_eq!(ChartSpan::value_range(&data(&[12., 37.], &[])), (0., 40., 10.));
+        assert_eq!(ChartSpan::value_range(&data(&[12., 37.], &[90.])), (0., 100., 20.));
+        assert_eq!(ChartSpan::value_range(&data(&[-3., 4.], &[])), (-4., 4., 2.));
+        assert_eq!(ChartSpan::value_range(&data(&[5., 5.], &[])), (0., 5., 1.));
+        assert_eq!(ChartSpan::value_range(&data(&[12., f64::INFINITY, 37., f64::NAN], &[])), (0., 40., 10.));
+        assert_eq!(ChartSpan::value_range(&data(&[f64::NEG_INFINITY], &[])), (0., 1., 0.2));
+        assert_eq!(ChartSpan::value_range(&data(&[f64::MAX], &[])), (0., 1., 0.2));
+    }
+}
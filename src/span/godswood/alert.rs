@@ -0,0 +1,185 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// Ordered from the least to the most urgent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl AlertSeverity {
+    pub fn parse(name: &str) -> Option<AlertSeverity> {
+        match name.to_lowercase().as_str() {
+            "info" => Some(AlertSeverity::Info),
+            "warning" => Some(AlertSeverity::Warning),
+            "critical" => Some(AlertSeverity::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertSeverity::Info => "info",
+            AlertSeverity::Warning => "warning",
+            AlertSeverity::Critical => "critical",
+        }
+    }
+
+    pub fn color(&self) -> &'static str {
+        match self {
+            AlertSeverity::Info => "#42c8f5",
+            AlertSeverity::Warning => "#f5a442",
+            AlertSeverity::Critical => "red",
+        }
+    }
+}
+
+// Alert raised on a node, `key` identifies it across updates
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alert {
+    pub key: String,
+    pub severity: AlertSeverity,
+    pub path: String,
+    pub message: String,
+    // Milliseconds since epoch the alert became active
+    pub since: u64,
+}
+
+// Alert becoming active or going away
+#[derive(Clone, Debug, PartialEq)]
+pub enum AlertEvent {
+    Fired(Alert),
+    Resolved(Alert),
+}
+
+// Active alerts, shared between the data source and the alert list
+pub struct AlertsProto {
+    active: HashMap<String, Alert>,
+    events: Vec<AlertEvent>,
+    revision: u64,
+}
+
+pub type Alerts = Rc<RefCell<AlertsProto>>;
+
+impl AlertsProto {
+    pub fn new() -> Alerts {
+        Rc::new(RefCell::new(Self {
+            active: HashMap::new(),
+            events: Vec::new(),
+            revision: 0,
+        }))
+    }
+
+    // Raising an alert already active updates it, keeping the time it started
+    pub fn raise(&mut self, mut alert: Alert) {
+        if let Some(active) = self.active.get(&alert.key) {
+            alert.since = active.since;
+            if *active == alert {
+                return;
+            }
+        } else {
+            self.events.push(AlertEvent::Fired(alert.clone()));
+        }
+        self.active.insert(alert.key.clone(), alert);
+        self.revision += 1;
+    }
+
+    pub fn resolve(&mut self, key: &str) -> Option<Alert> {
+        let alert = self.active.remove(key)?;
+        self.events.push(AlertEvent::Resolved(alert.clone()));
+        self.revision += 1;
+        Some(alert)
+    }
+
+    // Dropped without resolving, like when the woods are reset
+    pub fn clear(&mut self) {
+        if !self.active.is_empty() {
+            self.active.clear();
+            self.revision += 1;
+        }
+    }
+
+    // Alerts fired and resolved since the last call, in order
+    pub fn take_events(&mut self) -> Vec<AlertEvent> {
+        self.events.drain(..).collect()
+    }
+
+    // Bumped on every change of the active alerts
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // Most severe first, the oldest first within a severity
+    pub fn get_active(&self) -> Vec<&Alert> {
+        let mut alerts: Vec<&Alert> = self.active.values().collect();
+        alerts.sort_by(|a, b| {
+            b.severity.cmp(&a.severity)
+                .then(a.since.cmp(&b.since))
+                .then(a.key.cmp(&b.key))
+        });
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(key: &str, severity: AlertSeverity, since: u64) -> Alert {
+        Alert {
+            key: key.to_string(),
+            severity,
+            path: ".app.db".to_string(),
+            message: String::new(),
+            since,
+        }
+    }
+
+    #[test]
+    fn orders_alerts() {
+        let alerts = AlertsProto::new();
+        let mut alerts = alerts.borrow_mut();
+        alerts.raise(alert("slow", AlertSeverity::Warning, 10));
+        alerts.raise(alert("down", AlertSeverity::Critical, 30));
+        alerts.raise(alert("full", AlertSeverity::Critical, 20));
+        alerts.raise(alert("deploy", AlertSeverity::Info, 0));
+        let keys: Vec<&str> = alerts.get_active().iter().map(|alert| alert.key.as_str()).collect();
+        assert_eq!(keys, vec!["full", "down", "slow", "deploy"]);
+    }
+
+    #[test]
+    fn raises_and_resolves() {
+        let alerts = AlertsProto::new();
+        let mut alerts = alerts.borrow_mut();
+        alerts.raise(alert("down", AlertSeverity::Warning, 10));
+        let revision = alerts.revision();
+        alerts.raise(alert("down", AlertSeverity::Warning, 20));
+        assert_eq!(alerts.revision(), revision);
+
+        // Updates keep when the alert started
+        alerts.raise(alert("down", AlertSeverity::Critical, 30));
+        assert_eq!(alerts.get_active()[0].since, 10);
+        assert_eq!(alerts.get_active()[0].severity, AlertSeverity::Critical);
+        assert!(alerts.resolve("down").is_some());
+        assert!(alerts.resolve("down").is_none());
+        assert_eq!(alerts.take_events(), vec![
+            AlertEvent::Fired(alert("down", AlertSeverity::Warning, 10)),
+            AlertEvent::Resolved(alert("down", AlertSeverity::Critical, 10)),
+        ]);
+
+        alerts.raise(alert("down", AlertSeverity::Warning, 40));
+        alerts.clear();
+        assert!(alerts.get_active().is_empty());
+        assert_eq!(alerts.take_events().len(), 1);
+    }
+
+    #[test]
+    fn parses_severities() {
+        assert_eq!(AlertSeverity::parse("Critical"), Some(AlertSeverity::Critical));
+        assert_eq!(AlertSeverity::parse("fatal"), None);
+        assert!(AlertSeverity::Info < AlertSeverity::Warning && AlertSeverity::Warning < AlertSeverity::Critical);
+    }
+}
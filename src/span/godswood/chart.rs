@@ -0,0 +1,58 @@
+use crate::span::chart::{ChartData, ChartProvider, ChartSeries, ChartThreshold};
+use crate::span::godswood::GodswoodHandle;
+use crate::span::godswood::node::StoreOps;
+use crate::span::godswood::rule::RuleCondition;
+
+// Points drawn per series
+const CHART_POINTS: usize = 120;
+
+impl GodswoodHandle {
+    // Chart of `(metric, color)` series of the selected node over the last `window` ms, with the
+    // thresholds of the alert rules watching them on the node
+    pub fn metric_chart(&self, title: &str, metrics: Vec<(String, String)>, window: u64) -> ChartProvider {
+        let handle = self.clone();
+        let title = title.to_string();
+        Box::new(move |now| {
+            let path = handle.selection.borrow().get_selected().cloned();
+            let node = path.as_ref().and_then(|path| handle.woods.store.get_node(path));
+            let (path, node) = match (path, node) {
+                (Some(path), Some(node)) => (path, node),
+                _ => return ChartData {
+                    title: format!("{} (no node selected)", title),
+                    series: Vec::new(),
+                    thresholds: Vec::new(),
+                },
+            };
+
+            let node = node.borrow();
+            let since = now.saturating_sub(window);
+            let series = metrics.iter().map(|(metric, color)| ChartSeries {
+                label: metric.clone(),
+                color: color.clone(),
+                points: node.series.get(metric).map_or(Vec::new(), |series| {
+                    series.get_samples(since, CHART_POINTS).iter().map(|sample| (sample.at, sample.avg())).collect()
+                }),
+            }).collect();
+
+            let rules = handle.rules.borrow();
+            let thresholds = rules.get_rules().iter().filter(|rule| rule.pattern.matches(&path)).filter_map(|rule| {
+                match &rule.condition {
+                    RuleCondition::Metric { metric, op, threshold } if metrics.iter().any(|(name, _)| name == metric) => {
+                        Some(ChartThreshold {
+                            label: format!("{} {} {}", metric, op.as_str(), threshold),
+                            value: *threshold,
+                            color: rule.severity.color().to_string(),
+                        })
+                    },
+                    _ => None,
+                }
+            }).collect();
+
+            ChartData {
+                title: format!("{} {}", title, path),
+                series,
+                thresholds,
+            }
+        })
+    }
+}
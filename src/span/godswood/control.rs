@@ -0,0 +1,85 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use dragon::core::Vector3;
+
+// Pointer input for the camera gathered between ticks, positions and moves in normalized device
+// coordinates of the world
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CameraInput {
+    pub orbit: (f32, f32),
+    pub pan: (f32, f32),
+    // Wheel pixels, positive zooming out, toward the last pointer position
+    pub zoom: f32,
+    pub pointer: (f32, f32),
+    pub aspect: f32,
+}
+
+// Camera controls shared by the world pointer span and the world
+pub struct CameraControlProto {
+    input: CameraInput,
+    // Point to orbit around from now on, set when the camera flies to a node
+    target: Option<Vector3<f32>>,
+}
+
+pub type CameraControl = Rc<RefCell<CameraControlProto>>;
+
+impl CameraControlProto {
+    pub fn new() -> CameraControl {
+        Rc::new(RefCell::new(Self {
+            input: CameraInput::default(),
+            target: None,
+        }))
+    }
+
+    pub fn orbit(&mut self, dx: f32, dy: f32) {
+        self.input.orbit.0 += dx;
+        self.input.orbit.1 += dy;
+    }
+
+    pub fn pan(&mut self, dx: f32, dy: f32, aspect: f32) {
+        self.input.pan.0 += dx;
+        self.input.pan.1 += dy;
+        self.input.aspect = aspect;
+    }
+
+    pub fn zoom(&mut self, delta: f32, x: f32, y: f32, aspect: f32) {
+        self.input.zoom += delta;
+        self.input.pointer = (x, y);
+        self.input.aspect = aspect;
+    }
+
+    pub fn take_input(&mut self) -> CameraInput {
+        std::mem::take(&mut self.input)
+    }
+
+    pub fn set_target(&mut self, target: Vector3<f32>) {
+        self.target = Some(target);
+    }
+
+    pub fn take_target(&mut self) -> Option<Vector3<f32>> {
+        self.target.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gathers_input() {
+        let control = CameraControlProto::new();
+        let mut control = control.borrow_mut();
+        control.orbit(0.1, 0.2);
+        control.orbit(0.1, -0.1);
+        control.pan(0.5, 0.5, 1.5);
+        control.zoom(120., 0.25, -0.5, 2.);
+        let input = control.take_input();
+        assert!((input.orbit.0 - 0.2).abs() < 1e-6 && (input.orbit.1 - 0.1).abs() < 1e-6);
+        assert_eq!((input.pan, input.zoom, input.pointer, input.aspect), ((0.5, 0.5), 120., (0.25, -0.5), 2.));
+        assert_eq!(control.take_input(), CameraInput::default());
+
+        control.set_target(Vector3::new(1., 2., 3.));
+        assert_eq!(control.take_target(), Some(Vector3::new(1., 2., 3.)));
+        assert_eq!(control.take_target(), None);
+    }
+}
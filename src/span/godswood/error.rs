@@ -0,0 +1,96 @@
+use std::error::Error;
+use std::fmt;
+use crate::utils::CodecError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WoodErrorKind {
+    Syntax(String),
+    MissingRoot,
+    EmptyName,
+    IllegalName(String),
+    DuplicateName(String),
+    InvalidField(String),
+    UnknownDependency(String),
+    UnknownNode(String),
+    RootNode,
+    Cycle(String),
+    SharedNode(String),
+}
+
+// Error raised while loading a wood, `path` points at the offending JSON value, like
+// `$.children.service2.depends_on[0]`, or at the node path for a patch
+// `location` is the 1-based line and column in the source text when known
+#[derive(Debug, Clone, PartialEq)]
+pub struct WoodError {
+    pub kind: WoodErrorKind,
+    pub path: String,
+    pub location: Option<(usize, usize)>,
+}
+
+impl WoodError {
+    pub fn new(kind: WoodErrorKind, path: &str) -> Self {
+        Self {
+            kind,
+            path: path.to_string(),
+            location: None,
+        }
+    }
+
+    pub fn with_location(mut self, location: Option<(usize, usize)>) -> Self {
+        self.location = location;
+        self
+    }
+}
+
+// Undecodable wood data, the codec does not tell where it failed so the error points at the root
+impl From<CodecError> for WoodError {
+    fn from(err: CodecError) -> Self {
+        WoodError::new(WoodErrorKind::Syntax(err.to_string()), "$")
+    }
+}
+
+impl fmt::Display for WoodErrorKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            WoodErrorKind::Syntax(msg) => write!(fmt, "Bad wood source: {}", msg),
+            WoodErrorKind::MissingRoot => fmt.write_str("Missing wood root"),
+            WoodErrorKind::EmptyName => fmt.write_str("Empty node name"),
+            WoodErrorKind::IllegalName(name) => write!(fmt, "Illegal node name {}", name),
+            WoodErrorKind::DuplicateName(name) => write!(fmt, "Duplicate node name {}", name),
+            WoodErrorKind::InvalidField(field) => write!(fmt, "Invalid field {}", field),
+            WoodErrorKind::UnknownDependency(path) => write!(fmt, "Unknown dependency {}", path),
+            WoodErrorKind::UnknownNode(path) => write!(fmt, "Unknown node {}", path),
+            WoodErrorKind::RootNode => fmt.write_str("Not allowed on wood root"),
+            WoodErrorKind::Cycle(path) => write!(fmt, "Dependency cycle through {}", path),
+            WoodErrorKind::SharedNode(id) => write!(fmt, "Conflicting declaration of shared node {}", id),
+        }
+    }
+}
+
+impl fmt::Display for WoodError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.location {
+            Some((line, column)) => write!(fmt, "{} at {} (line {}, column {})", self.kind, self.path, line, column),
+            None => write!(fmt, "{} at {}", self.kind, self.path),
+        }
+    }
+}
+
+impl Error for WoodError {
+    fn description(&self) -> &str {
+        "Bad wood"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_codec_errors() {
+        let err = WoodError::from(CodecError);
+        assert_eq!(err.kind, WoodErrorKind::Syntax("Bad data".to_string()));
+        assert_eq!(err.to_string(), "Bad wood source: Bad data at $");
+        assert_eq!(err.with_location(Some((2, 5))).to_string(), "Bad wood source: Bad data at $ (line 2, column 5)");
+    }
+}
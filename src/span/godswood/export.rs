@@ -0,0 +1,268 @@
+use std::rc::Rc;
+use std::collections::HashSet;
+use serde_json::{json, Value};
+use crate::span::godswood::node::*;
+use crate::span::godswood::tree::*;
+use crate::span::godswood::status::GodsnodeRollup;
+use crate::utils::JsonMap;
+
+impl GodswoodProto {
+    // Canonical wood JSON, shared nodes are declared under their first parent and linked from the
+    // others through `depends_on`, so the output loads back into the same wood
+    // Nodes shared across woods keep their `id` and full subtree, which other woods may repeat
+    pub fn to_json(&self) -> Value {
+        let app = self.read_name();
+        match self.get_root().upgrade() {
+            Some(root) => {
+                let mut value = export_node(&root, &app);
+                value["name"] = json!(app);
+                value
+            },
+            None => Value::Null,
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let app = self.read_name();
+        let nodes = self.export_nodes();
+        let mut dot = format!("digraph {} {{\n", dot_quote(&app));
+        for node in nodes.iter() {
+            let node = node.borrow();
+            let path = node.read_path(&app).unwrap_or_default();
+            dot.push_str(&format!(
+                "  {} [label={}, class={}, tooltip={}];\n",
+                dot_quote(&path), dot_quote(&node.display_name), dot_quote(node.service_type.as_str()),
+                dot_quote(&describe_node(&node).join("\n"))
+            ));
+        }
+        for (parent, kid) in export_edges(&nodes, &app).iter() {
+            dot.push_str(&format!("  {} -> {};\n", dot_quote(parent), dot_quote(kid)));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let app = self.read_name();
+        let nodes = self.export_nodes();
+        let paths: Vec<String> = nodes.iter().map(|node| node.borrow().read_path(&app).unwrap_or_default()).collect();
+        // Mermaid ids only allow plain characters, nodes are numbered in export order instead
+        let id = |path: &String| format!("n{}", paths.iter().position(|p| p == path).unwrap_or(0));
+
+        let mut mermaid = String::from("flowchart TD\n");
+        for (node, path) in nodes.iter().zip(paths.iter()) {
+            mermaid.push_str(&format!("  {}[\"{}\"]\n", id(path), mermaid_escape(&node.borrow().display_name)));
+        }
+        for (parent, kid) in export_edges(&nodes, &app).iter() {
+            mermaid.push_str(&format!("  {} --> {}\n", id(parent), id(kid)));
+        }
+        let mut classes: Vec<GodsnodeClass> = Vec::new();
+        for (node, path) in nodes.iter().zip(paths.iter()) {
+            let class = node.borrow().service_type;
+            if !classes.contains(&class) {
+                classes.push(class);
+            }
+            mermaid.push_str(&format!("  class {} {}\n", id(path), class.as_str()));
+        }
+        for class in classes.iter() {
+            mermaid.push_str(&format!("  classDef {} {}\n", class.as_str(), mermaid_style(*class)));
+        }
+        // Mermaid has no place for attributes, keep them as comments
+        for (node, path) in nodes.iter().zip(paths.iter()) {
+            for line in describe_node(&node.borrow()).iter() {
+                mermaid.push_str(&format!("  %% {} {}\n", path, line));
+            }
+        }
+        mermaid
+    }
+
+    // Nodes of the wood in declaration order, children sorted by name
+    fn export_nodes(&self) -> Vec<Rc<Godsnode>> {
+        let app = self.read_name();
+        let mut nodes = Vec::new();
+        let mut tasks = Vec::new();
+        if let Some(root) = self.get_root().upgrade() {
+            tasks.push(root);
+        }
+        while let Some(node) = tasks.pop() {
+            let mut kids = declared_children(&node, &app);
+            kids.reverse();
+            tasks.extend(kids);
+            nodes.push(node);
+        }
+        nodes
+    }
+}
+
+// Children declared under the node, sorted by name
+pub fn declared_children(node: &Rc<Godsnode>, app: &String) -> Vec<Rc<Godsnode>> {
+    let mut kids: Vec<Rc<Godsnode>> = node.borrow().get_children().iter().filter_map(|kid| kid.upgrade()).filter(|kid| {
+        kid.borrow().primary_parent(app).is_some_and(|parent| Rc::ptr_eq(&parent, node))
+    }).collect();
+    kids.sort_by(|a, b| a.borrow().name.cmp(&b.borrow().name));
+    kids
+}
+
+fn export_node(node: &Rc<Godsnode>, app: &String) -> Value {
+    let declared = declared_children(node, app);
+    let state = node.borrow();
+    let mut value = json!({
+        "display_name": state.display_name,
+        "class": state.service_type.as_str(),
+    });
+    if let Some(global_id) = &state.global_id {
+        value["id"] = json!(global_id);
+    }
+    if state.rollup != GodsnodeRollup::Worst {
+        value["rollup"] = json!(state.rollup.to_string());
+    }
+    if !state.attributes.is_empty() {
+        value["attributes"] = json!(state.attributes);
+    }
+    if !state.tags.is_empty() {
+        let mut tags: Vec<&String> = state.tags.iter().collect();
+        tags.sort();
+        value["tags"] = json!(tags);
+    }
+
+    let declared_ids: HashSet<u64> = declared.iter().map(|kid| kid.borrow().id).collect();
+    let mut depends_on: Vec<String> = state.get_children().iter().filter_map(|kid| kid.upgrade()).filter(|kid| {
+        !declared_ids.contains(&kid.borrow().id)
+    }).filter_map(|kid| kid.borrow().read_path(app)).collect();
+    if !depends_on.is_empty() {
+        depends_on.sort();
+        value["depends_on"] = json!(depends_on);
+    }
+
+    if !declared.is_empty() {
+        let mut children = JsonMap::new();
+        for kid in declared.iter() {
+            children.insert(kid.borrow().name.clone(), export_node(kid, app));
+        }
+        value["children"] = Value::Object(children);
+    }
+    value
+}
+
+// Every parent to child edge between the nodes, as declared paths
+fn export_edges(nodes: &[Rc<Godsnode>], app: &String) -> Vec<(String, String)> {
+    let mut edges = Vec::new();
+    for node in nodes.iter() {
+        let node = node.borrow();
+        let path = match node.read_path(app) {
+            Some(path) => path,
+            None => continue,
+        };
+        let mut kids: Vec<String> = node.get_children().iter().filter_map(|kid| kid.upgrade()).filter_map(|kid| {
+            kid.borrow().read_path(app)
+        }).collect();
+        kids.sort();
+        for kid in kids.into_iter() {
+            edges.push((path.clone(), kid));
+        }
+    }
+    edges
+}
+
+// Sorted `key=value` lines of the attributes, then the tags
+fn describe_node(node: &GodsnodeProto) -> Vec<String> {
+    let mut lines: Vec<String> = node.attributes.iter().map(|(key, value)| {
+        format!("{}={}", key, value.replace('\n', " "))
+    }).collect();
+    lines.sort();
+    let mut tags: Vec<&String> = node.tags.iter().collect();
+    tags.sort();
+    if !tags.is_empty() {
+        lines.push(format!("tags={}", tags.iter().map(|tag| tag.as_str()).collect::<Vec<&str>>().join(",")));
+    }
+    lines
+}
+
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+// Fill per class, the stage tells classes apart by mesh instead
+fn mermaid_style(class: GodsnodeClass) -> &'static str {
+    match class {
+        GodsnodeClass::General => "fill:#e8e8e8,stroke:#333",
+        GodsnodeClass::Database => "fill:#cfe2ff,stroke:#333",
+        GodsnodeClass::Cache => "fill:#ffe5b4,stroke:#333",
+        GodsnodeClass::Queue => "fill:#e2d4f0,stroke:#333",
+        GodsnodeClass::Gateway => "fill:#d4f0e2,stroke:#333",
+        GodsnodeClass::Worker => "fill:#f0e2d4,stroke:#333",
+        GodsnodeClass::External => "fill:#f0d4d4,stroke:#333",
+    }
+}
+
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_dot() {
+        assert_eq!(dot_quote("node1"), r#""node1""#);
+        assert_eq!(dot_quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(dot_quote(r"C:\dir"), r#""C:\\dir""#);
+        assert_eq!(dot_quote("owner=a\nversion=2"), r#""owner=a\nversion=2""#);
+        assert_eq!(dot_quote(""), r#""""#);
+    }
+
+    #[test]
+    fn escapes_mermaid() {
+        assert_eq!(mermaid_escape("node1"), "node1");
+        assert_eq!(mermaid_escape(r#"say "hi""#), "say #quot;hi#quot;");
+    }
+
+    #[test]
+    fn exports_woods() {
+        let mut woods = Godswoods::new();
+        woods.add_wood(&json!({
+            "name": "app",
+            "display_name": "App",
+            "children": {
+                "node2": { "display_name": "Say \"hi\"", "class": "cache", "depends_on": [".app.node1"] },
+                "node1": { "display_name": "Node", "attributes": { "owner": "team-a" }, "tags": ["edge"] },
+            },
+        })).unwrap();
+        let woods = woods.woods.borrow();
+        let wood = &woods.get("app").unwrap().borrow().wood;
+        assert_eq!(wood.to_dot(), concat!(
+            "digraph \"app\" {\n",
+            "  \".app\" [label=\"App\", class=\"general\", tooltip=\"\"];\n",
+            "  \".app.node1\" [label=\"Node\", class=\"general\", tooltip=\"owner=team-a\\ntags=edge\"];\n",
+            "  \".app.node2\" [label=\"Say \\\"hi\\\"\", class=\"cache\", tooltip=\"\"];\n",
+            "  \".app\" -> \".app.node1\";\n",
+            "  \".app\" -> \".app.node2\";\n",
+            "  \".app.node2\" -> \".app.node1\";\n",
+            "}\n",
+        ));
+        assert_eq!(wood.to_mermaid(), concat!(
+            "flowchart TD\n",
+            "  n0[\"App\"]\n",
+            "  n1[\"Node\"]\n",
+            "  n2[\"Say #quot;hi#quot;\"]\n",
+            "  n0 --> n1\n",
+            "  n0 --> n2\n",
+            "  n2 --> n1\n",
+            "  class n0 general\n",
+            "  class n1 general\n",
+            "  class n2 cache\n",
+            "  classDef general fill:#e8e8e8,stroke:#333\n",
+            "  classDef cache fill:#ffe5b4,stroke:#333\n",
+            "  %% .app.node1 owner=team-a\n",
+            "  %% .app.node1 tags=edge\n",
+        ));
+
+        // Loads back into the same wood
+        let exported = wood.to_json();
+        assert_eq!(exported["children"]["node2"]["depends_on"], json!([".app.node1"]));
+        let mut copies = Godswoods::new();
+        copies.add_wood(&exported).unwrap();
+        assert_eq!(copies.woods.borrow().get("app").unwrap().borrow().wood.to_json(), exported);
+    }
+}
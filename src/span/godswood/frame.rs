@@ -0,0 +1,207 @@
+use crate::utils::{get_u16_le, get_u32_le, get_u64_le, CodecError};
+use crate::span::godswood::status::GodsnodeHealth;
+
+// Binary frames for high rate updates, all numbers are little endian
+//
+// header, 14 bytes:
+//   u8 version | u8 kind | u32 node id | u64 timestamp in ms
+// status payload, kind 1:
+//   u8 health
+// metric payload, kind 2:
+//   u16 metric id | f32 value
+//
+// Frames are simply concatenated in a buffer, the kind tells the size of each.
+// The dashboard only decodes, encoding is kept for sources written in Rust and for tests.
+pub const FRAME_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 14;
+const KIND_STATUS: u8 = 1;
+const KIND_METRIC: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Frame {
+    Status { node: u32, health: GodsnodeHealth, at: u64 },
+    Metric { node: u32, metric: u16, value: f32, at: u64 },
+}
+
+#[allow(dead_code)]
+fn health_code(health: GodsnodeHealth) -> u8 {
+    match health {
+        GodsnodeHealth::Healthy => 0,
+        GodsnodeHealth::Degraded => 1,
+        GodsnodeHealth::Down => 2,
+        GodsnodeHealth::Unknown => 3,
+        GodsnodeHealth::Maintenance => 4,
+    }
+}
+
+fn health_from_code(code: u8) -> Result<GodsnodeHealth, CodecError> {
+    match code {
+        0 => Ok(GodsnodeHealth::Healthy),
+        1 => Ok(GodsnodeHealth::Degraded),
+        2 => Ok(GodsnodeHealth::Down),
+        3 => Ok(GodsnodeHealth::Unknown),
+        4 => Ok(GodsnodeHealth::Maintenance),
+        _ => Err(CodecError),
+    }
+}
+
+impl Frame {
+    pub fn node(&self) -> u32 {
+        match self {
+            Frame::Status { node, .. } => *node,
+            Frame::Metric { node, .. } => *node,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn at(&self) -> u64 {
+        match self {
+            Frame::Status { at, .. } => *at,
+            Frame::Metric { at, .. } => *at,
+        }
+    }
+
+    // Append the frame to the buffer
+    #[allow(dead_code)]
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(FRAME_VERSION);
+        buf.push(match self {
+            Frame::Status { .. } => KIND_STATUS,
+            Frame::Metric { .. } => KIND_METRIC,
+        });
+        buf.extend_from_slice(&self.node().to_le_bytes());
+        buf.extend_from_slice(&self.at().to_le_bytes());
+        match self {
+            Frame::Status { health, .. } => buf.push(health_code(*health)),
+            Frame::Metric { metric, value, .. } => {
+                buf.extend_from_slice(&metric.to_le_bytes());
+                buf.extend_from_slice(&value.to_bits().to_le_bytes());
+            },
+        }
+    }
+
+    // Decode the frame at the start of the buffer, with the number of bytes it took
+    pub fn decode(buf: &[u8]) -> Result<(Frame, usize), CodecError> {
+        if buf.len() < HEADER_SIZE || buf[0] != FRAME_VERSION {
+            return Err(CodecError);
+        }
+        let node = get_u32_le(&buf[2..]);
+        let at = get_u64_le(&buf[6..]);
+        let payload = &buf[HEADER_SIZE..];
+        match buf[1] {
+            KIND_STATUS if !payload.is_empty() => {
+                let health = health_from_code(payload[0])?;
+                Ok((Frame::Status { node, health, at }, HEADER_SIZE + 1))
+            },
+            KIND_METRIC if payload.len() >= 6 => {
+                let metric = get_u16_le(payload);
+                let value = f32::from_bits(get_u32_le(&payload[2..]));
+                Ok((Frame::Metric { node, metric, value, at }, HEADER_SIZE + 6))
+            },
+            _ => Err(CodecError),
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn encode_frames(frames: &[Frame]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(frames.len() * (HEADER_SIZE + 6));
+    for frame in frames.iter() {
+        frame.encode(&mut buf);
+    }
+    buf
+}
+
+// Decode every frame of the buffer, any bad or truncated frame fails the whole buffer
+pub fn decode_frames(buf: &[u8]) -> Result<Vec<Frame>, CodecError> {
+    let mut frames = Vec::new();
+    let mut offset = 0;
+    while offset < buf.len() {
+        let (frame, size) = Frame::decode(&buf[offset..])?;
+        frames.push(frame);
+        offset += size;
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Frame> {
+        vec![
+            Frame::Status { node: 7, health: GodsnodeHealth::Down, at: 1_571_000_000_123 },
+            Frame::Metric { node: u32::MAX, metric: 3, value: -12.5, at: u64::MAX },
+            Frame::Status { node: 0, health: GodsnodeHealth::Maintenance, at: 0 },
+            Frame::Metric { node: 42, metric: u16::MAX, value: 0.001, at: 1 },
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let frames = sample();
+        let buf = encode_frames(&frames);
+        assert_eq!(buf.len(), 2 * 15 + 2 * 20);
+        assert_eq!(decode_frames(&buf).unwrap(), frames);
+    }
+
+    #[test]
+    fn rejects_truncated_frames() {
+        let buf = encode_frames(&sample());
+        let mut ends = Vec::new();
+        let mut offset = 0;
+        while offset < buf.len() {
+            offset += Frame::decode(&buf[offset..]).unwrap().1;
+            ends.push(offset);
+        }
+        for len in 1..buf.len() {
+            if !ends.contains(&len) {
+                assert!(decode_frames(&buf[..len]).is_err(), "accepted {} bytes", len);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_frames() {
+        let mut buf = encode_frames(&sample()[..1]);
+        buf[0] = FRAME_VERSION + 1;
+        assert!(decode_frames(&buf).is_err());
+
+        let mut buf = encode_frames(&sample()[..1]);
+        buf[1] = 9;
+        assert!(decode_frames(&buf).is_err());
+
+        let mut buf = encode_frames(&sample()[..1]);
+        buf[HEADER_SIZE] = 5;
+        assert!(decode_frames(&buf).is_err());
+    }
+
+    // Random buffers and random corruptions of valid ones must never panic, and whatever decodes
+    // must encode back to the same bytes
+    #[test]
+    fn fuzz_decoder() {
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        let valid = encode_frames(&sample());
+        for round in 0..10_000 {
+            let buf: Vec<u8> = if round % 2 == 0 {
+                let len = (next() % 64) as usize;
+                (0..len).map(|_| next() as u8).collect()
+            } else {
+                let mut buf = valid.clone();
+                let at = (next() % buf.len() as u64) as usize;
+                buf[at] = next() as u8;
+                buf.truncate((next() % (buf.len() as u64 + 1)) as usize);
+                buf
+            };
+            if let Ok(frames) = decode_frames(&buf) {
+                assert_eq!(encode_frames(&frames), buf);
+            }
+        }
+    }
+}
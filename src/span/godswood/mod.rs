@@ -3,22 +3,193 @@ mod system;
 mod component;
 mod node;
 mod tree;
+mod error;
+mod parser;
+mod patch;
+mod query;
+mod export;
+mod status;
+mod frame;
+mod source;
+mod record;
+mod series;
+mod shapes;
+mod chart;
+mod pick;
+mod tooltip;
+pub mod alert;
+pub mod rule;
+pub mod select;
+pub mod control;
+pub mod worklog;
+mod persist;
 mod stage;
 use system::movement::MovementSystem;
+use system::orbit::OrbitSystem;
+use system::topology::TopologySystem;
+use system::health::HealthSystem;
+use system::persist::PersistSystem;
+use system::source::SourceSystem;
+use system::focus::FocusSystem;
+use system::pick::PickSystem;
+use system::alert::AlertSystem;
+use system::worklog::WorklogSystem;
+use system::sparkline::SparklineSystem;
 use stage::Godsstage;
+use tree::Godswoods;
+use node::StoreOps;
+use query::StoreQuery;
+use persist::{Preferences, SharedPreferences};
+use record::{Recorder, RecorderProto};
+use alert::{Alerts, AlertsProto};
+use rule::{RuleEngine, RuleEngineProto};
+use select::{Selection, SelectionProto};
+use control::{CameraControl, CameraControlProto};
+use worklog::{Worklog, WorklogProto};
+use shapes::ShapeLayersProto;
+use std::{rc::Rc, cell::RefCell};
 
 
-pub fn create_godswood(app: &wand::Application) -> wand::WorldSpan {
+// Shared state for the application to drive the godswood from outside the world
+#[derive(Clone)]
+pub struct GodswoodHandle {
+    pub woods: Godswoods,
+    pub recorder: Recorder,
+    pub alerts: Alerts,
+    pub rules: RuleEngine,
+    pub selection: Selection,
+    pub control: CameraControl,
+    pub worklog: Worklog,
+    pub preferences: SharedPreferences,
+}
+
+impl GodswoodHandle {
+    // Selects the node at the path and flies the camera to it, false for unknown nodes
+    pub fn focus_node(&self, path: &str) -> bool {
+        if self.woods.store.get_node(path).is_none() {
+            return false;
+        }
+        self.selection.borrow_mut().select(path, true);
+        true
+    }
+
+    // Paths of the nodes matching the pattern, like `.shop.**.db-*`, each node listed once by its
+    // own path, none for a bad pattern
+    pub fn query_nodes(&self, pattern: &str) -> Option<Vec<String>> {
+        self.search_nodes(pattern, "")
+    }
+
+    // Like `query_nodes`, keeping the nodes accepted by the filter, like `#critical owner=team-*`,
+    // see `GodsnodeFilter::parse`
+    pub fn search_nodes(&self, pattern: &str, filter: &str) -> Option<Vec<String>> {
+        let query = query::GodsnodeQuery::parse(pattern)?;
+        let filter = query::GodsnodeFilter::parse(filter);
+        Some(self.woods.store.search(&query, &filter).iter().filter_map(|node| node.upgrade())
+            .filter_map(|node| node.borrow().get_path())
+            .collect())
+    }
+
+    // Add a wood from JSON, YAML or TOML text, `format` is the format or a file name like `shop.yaml`
+    pub fn load_wood(&mut self, raw: &str, format: &str) -> Result<(), error::WoodError> {
+        match parser::WoodFormat::from_file_name(format) {
+            Some(format) => self.woods.add_wood_from_str(raw, format),
+            None => Err(error::WoodError::new(error::WoodErrorKind::Syntax(format!("unknown format {}", format)), "$")),
+        }
+    }
+
+    // Wood named `name` in canonical JSON, Graphviz DOT or Mermaid, `format` is `json`, `dot` or
+    // `mermaid`, none for an unknown wood or format
+    pub fn export_wood(&self, name: &str, format: &str) -> Option<String> {
+        let woods = self.woods.woods.borrow();
+        let wood = &woods.get(name)?.borrow().wood;
+        match format {
+            "json" => serde_json::to_string_pretty(&wood.to_json()).ok(),
+            "dot" => Some(wood.to_dot()),
+            "mermaid" => Some(wood.to_mermaid()),
+            _ => None,
+        }
+    }
+
+    // Camera turn and move speeds, saved with the next snapshot, false for speeds not above zero
+    pub fn set_preferences(&self, turn_speed: f32, move_speed: f32) -> bool {
+        match Preferences::new(turn_speed, move_speed) {
+            Some(preferences) => {
+                *self.preferences.borrow_mut() = preferences;
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Apply a patch in JSON to a live wood, see `GodswoodPatch::from_json`
+    pub fn patch_wood(&mut self, raw: &serde_json::Value) -> Result<(), error::WoodError> {
+        let patch = patch::GodswoodPatch::from_json(raw)?;
+        self.woods.patch(&patch)
+    }
+
+    // Set the health of the node at the path, like `down`, changed at `at` ms, false for an unknown
+    // node or health
+    pub fn set_status(&self, path: &str, health: &str, reason: &str, at: u64) -> bool {
+        match (self.woods.store.get_node(path), status::GodsnodeHealth::parse(health)) {
+            (Some(node), Some(health)) => {
+                self.woods.store.update_status(&node, health, reason, at);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    // Drop the wood named `name` with the nodes only it holds, false for an unknown wood
+    pub fn remove_wood(&mut self, name: &str) -> bool {
+        self.woods.remove_wood(&name.to_string())
+    }
+}
+
+// `config["source"]` picks the data source, see `source::create_source`, and `config["rules"]` lists
+// the alert rules, see `rule::AlertRule::from_json`
+pub fn create_godswood(app: &wand::Application, config: &serde_json::Value) -> (wand::WorldSpan, GodswoodHandle) {
     let state = app.get_state();
     let world_span = wand::WorldSpan::new(state.clone(), app.context.clone(), "world", "World", 1., 1.);
     let w = &world_span.world.state;
-    // Register movement system and enter godsstage
-    {
-        let movement_system = MovementSystem::new(w.clone(), app.input.clone());
+    // Register the systems and enter godsstage, restoring the last saved snapshot if any
+    let handle = {
+        let woods = Godswoods::new();
+        let recorder = RecorderProto::new();
+        let alerts = AlertsProto::new();
+        let rules = RuleEngineProto::from_json(&config["rules"]);
+        let selection = SelectionProto::new();
+        let control = CameraControlProto::new();
+        let worklog = WorklogProto::new();
+        let shapes = ShapeLayersProto::new();
+        let snapshot = persist::load();
+        let preferences = snapshot.as_ref().map_or(Preferences::default(), |snapshot| snapshot.preferences);
+        let preferences = Rc::new(RefCell::new(preferences));
+        let source_system = SourceSystem::new(woods.clone(), source::create_source(&config["source"]), recorder.clone(), alerts.clone());
+        w.register_system("source", source_system);
+        let alert_system = AlertSystem::new(woods.clone(), alerts.clone(), rules.clone());
+        w.register_system("alert", alert_system);
+        let worklog_system = WorklogSystem::new(woods.clone(), alerts.clone(), worklog.clone());
+        w.register_system("worklog", worklog_system);
+        let movement_system = MovementSystem::new(w.clone(), app.input.clone(), preferences.clone());
         w.register_system("movement", movement_system);
+        let orbit_system = OrbitSystem::new(w.clone(), control.clone());
+        w.register_system("orbit", orbit_system);
+        let topology_system = TopologySystem::new(w.clone(), woods.clone(), shapes.clone());
+        w.register_system("topology", topology_system);
+        let pick_system = PickSystem::new(w.clone(), woods.clone(), selection.clone());
+        w.register_system("pick", pick_system);
+        let focus_system = FocusSystem::new(w.clone(), woods.clone(), selection.clone(), control.clone());
+        w.register_system("focus", focus_system);
+        let health_system = HealthSystem::new(w.clone(), woods.clone());
+        w.register_system("health", health_system);
+        let sparkline_system = SparklineSystem::new(w.clone(), shapes);
+        w.register_system("sparkline", sparkline_system);
+        let persist_system = PersistSystem::new(w.clone(), woods.clone(), preferences.clone());
+        w.register_system("persist", persist_system);
 
-        let stage = Godsstage::new(w.clone());
+        let stage = Godsstage::new(w.clone(), woods.clone(), snapshot);
         w.enter("godswood", stage);
-    }
-    world_span
+        GodswoodHandle { woods, recorder, alerts, rules, selection, control, worklog, preferences }
+    };
+    (world_span, handle)
 }
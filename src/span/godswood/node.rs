@@ -1,8 +1,11 @@
 use std::rc::{Rc, Weak};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use serde_json::Value;
 use crate::utils::*;
+use crate::span::godswood::status::*;
+use crate::span::godswood::alert::AlertSeverity;
+use crate::span::godswood::series::MetricSeries;
 
 pub enum GodsnodeType {
     Root,
@@ -10,16 +13,51 @@ pub enum GodsnodeType {
     Leaf,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum GodsnodeClass {
     General,
+    Database,
+    Cache,
+    Queue,
+    Gateway,
+    Worker,
+    External,
+}
+
+impl GodsnodeClass {
+    // Unknown classes fall back to general
+    pub fn parse(name: &str) -> GodsnodeClass {
+        match name.to_lowercase().as_str() {
+            "database" => GodsnodeClass::Database,
+            "cache" => GodsnodeClass::Cache,
+            "queue" => GodsnodeClass::Queue,
+            "gateway" => GodsnodeClass::Gateway,
+            "worker" => GodsnodeClass::Worker,
+            "external" => GodsnodeClass::External,
+            _ => GodsnodeClass::General,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GodsnodeClass::General => "general",
+            GodsnodeClass::Database => "database",
+            GodsnodeClass::Cache => "cache",
+            GodsnodeClass::Queue => "queue",
+            GodsnodeClass::Gateway => "gateway",
+            GodsnodeClass::Worker => "worker",
+            GodsnodeClass::External => "external",
+        }
+    }
 }
 
 pub type Godsnodes = Rc<RefCell<HashMap<usize, Vec<Weak<Godsnode>>>>>;
 pub type GodsnodeQ = Vec<Weak<Godsnode>>;
 
-pub struct InitGodsnodeQ {
-    pub app_meta: GodswoodMeta,
-    pub nodes: GodsnodeQ,
+// Pending `depends_on` link from a node to the node at path
+pub struct GodsnodeLink {
+    pub node: Weak<Godsnode>,
+    pub path: String,
 }
 
 #[derive(Clone)]
@@ -47,36 +85,37 @@ impl GodswoodMeta {
 #[derive(Clone)]
 pub struct GodsnodePath {
     path: String,
-    depth: usize,
 }
 
 impl GodsnodePath {
     pub fn new_path() -> GodsnodePath {
         GodsnodePath {
             path: String::new(),
-            depth: 0,
         }
     }
 
     pub fn append(&mut self, name: &String) {
         self.path.push_str(&(".".to_owned() + name));
-        self.depth += 1;
+    }
+
+    // Build from a dotted path like `.app.node1.node3`
+    pub fn parse(path: &str) -> GodsnodePath {
+        let mut node_path = GodsnodePath::new_path();
+        for token in path.split('.').skip(1) {
+            node_path.append(&token.to_string());
+        }
+        node_path
     }
 
     pub fn new(root: String) -> GodsnodePath {
         GodsnodePath {
             path: root,
-            depth: 1,
         }
     }
 
     pub fn read(&self) -> String {
         self.path.clone()
     }
-
-    pub fn read_depth(&self) -> usize {
-        self.depth
-    }
 }
 
 pub type GodswoodMetaMap = HashMap<String, GodswoodMeta>;
@@ -91,6 +130,23 @@ pub struct GodsnodeProto {
     pub children: Vec<Weak<Godsnode>>,
     pub service_type: GodsnodeClass,
     pub app_meta_map: GodswoodMetaMap,
+    // Global id shared by every wood declaring the node
+    pub global_id: Option<String>,
+    // Free form details like owner team, version, repo or runbook url
+    pub attributes: HashMap<String, String>,
+    pub tags: HashSet<String>,
+    // Effective status, rolled up from the children and the reported status of the node itself
+    pub status: GodsnodeStatus,
+    pub reported: Option<GodsnodeStatus>,
+    pub rollup: GodsnodeRollup,
+    // Latest sample of each metric, with its timestamp in ms
+    pub metrics: HashMap<String, (f64, u64)>,
+    // History of each metric
+    pub series: HashMap<String, MetricSeries>,
+    // Most severe alert active on the node
+    pub alerting: Option<AlertSeverity>,
+    // Highlighted as the node picked by the user
+    pub selected: bool,
 }
 
 impl GodsnodeProto {
@@ -104,9 +160,44 @@ impl GodsnodeProto {
             children: Vec::new(),
             service_type: GodsnodeClass::General,
             app_meta_map: HashMap::new(),
+            global_id: None,
+            attributes: HashMap::new(),
+            tags: HashSet::new(),
+            status: GodsnodeStatus::new(),
+            reported: None,
+            rollup: GodsnodeRollup::Worst,
+            metrics: HashMap::new(),
+            series: HashMap::new(),
+            alerting: None,
+            selected: false,
         }
     }
 
+    // Details declared in wood JSON, all but the name, id, links and children
+    pub fn set_details(&mut self, raw: &Value) {
+        self.display_name = raw.get_str("display_name", "new node");
+        self.service_type = GodsnodeClass::parse(&raw.get_str("class", "general"));
+        self.attributes = raw.get_str_map("attributes");
+        self.tags = raw.get_str_list("tags").into_iter().collect();
+        self.rollup = GodsnodeRollup::parse(&raw.get_str("rollup", "worst")).unwrap_or(GodsnodeRollup::Worst);
+    }
+
+    // Whether the node has the details declared in wood JSON
+    pub fn has_details(&self, raw: &Value) -> bool {
+        let mut declared = GodsnodeProto::new();
+        declared.set_details(raw);
+        self.display_name == declared.display_name && self.service_type == declared.service_type &&
+            self.attributes == declared.attributes && self.tags == declared.tags && self.rollup == declared.rollup
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    pub fn get_attribute(&self, key: &str) -> Option<&String> {
+        self.attributes.get(key)
+    }
+
     pub fn get_children(&self) -> &Vec<Weak<RefCell<Self>>> {
         &self.children
     }
@@ -115,6 +206,23 @@ impl GodsnodeProto {
         &self.parents
     }
 
+    // First parent in the wood of the app, the one the node is declared under
+    pub fn primary_parent(&self, app: &String) -> Option<Rc<Godsnode>> {
+        self.parents.iter().filter_map(|parent| parent.upgrade()).find(|parent| {
+            parent.borrow().app_meta_map.contains_key(app)
+        })
+    }
+
+    // Declared path of the node in the wood of the app
+    pub fn read_path(&self, app: &String) -> Option<String> {
+        self.app_meta_map.get(app).map(|meta| meta.path.read())
+    }
+
+    // Path the node is known by, the lowest of its declared paths when shared by several woods
+    pub fn get_path(&self) -> Option<String> {
+        self.app_meta_map.values().map(|meta| meta.path.read()).min()
+    }
+
     pub fn add_parent(&mut self, node: Weak<Godsnode>) {
         self.parents.push(node);
     }
@@ -122,6 +230,24 @@ impl GodsnodeProto {
     pub fn add_child(&mut self, node: Weak<Godsnode>) {
         self.children.push(node);
     }
+
+    pub fn remove_parent(&mut self, node: &Rc<Godsnode>) {
+        self.parents.retain(|parent| !parent.ptr_eq(&Rc::downgrade(node)));
+    }
+
+    pub fn remove_child(&mut self, node: &Rc<Godsnode>) {
+        self.children.retain(|child| !child.ptr_eq(&Rc::downgrade(node)));
+    }
+}
+
+// Effective health change of a node at one of its paths, `from` is none for the first status of the node
+#[derive(Clone, Debug, PartialEq)]
+pub struct GodsnodeTransition {
+    pub path: String,
+    pub from: Option<GodsnodeHealth>,
+    pub to: GodsnodeHealth,
+    pub reason: String,
+    pub at: u64,
 }
 
 pub type Store = RefCell<StoreProto>;
@@ -129,6 +255,11 @@ pub struct StoreProto {
     id: u64,
     store: HashMap<u64, Rc<Godsnode>>,
     index: HashMap<String, u64>,
+    globals: HashMap<String, u64>,
+    free_ids: Vec<u64>,
+    removed: Vec<u64>,
+    status_changes: HashSet<u64>,
+    transitions: Vec<GodsnodeTransition>,
 }
 
 impl StoreProto {
@@ -137,6 +268,11 @@ impl StoreProto {
             id: 0,
             store: HashMap::new(),
             index: HashMap::new(),
+            globals: HashMap::new(),
+            free_ids: Vec::new(),
+            removed: Vec::new(),
+            status_changes: HashSet::new(),
+            transitions: Vec::new(),
         }))
     }
 }
@@ -144,10 +280,25 @@ impl StoreProto {
 pub trait StoreOps {
     fn new_node(&self) -> Rc<Godsnode>;
     fn add_node(&self, raw: &Value, name: String) -> Rc<Godsnode>;
+    fn declare_node(&self, raw: &Value, name: String) -> (Rc<Godsnode>, bool);
+    fn get_shared_node(&self, id: &str) -> Option<Rc<Godsnode>>;
     fn add_app_node(&self, raw: &Value) -> Rc<Godsnode>;
     fn add_leaf_node(&self, name: &String, raw: &Value) -> Rc<Godsnode>;
     fn update_index(&self, name: &String, index: u64);
+    fn remove_index(&self, path: &str);
+    fn get_node(&self, path: &str) -> Option<Rc<Godsnode>>;
     fn get_weak_node(&self, path: &String) -> Option<Weak<Godsnode>>;
+    fn read_paths<F: Fn(&str) -> bool>(&self, filter: F) -> Vec<(String, Weak<Godsnode>)>;
+    fn remove_node(&self, node: &Rc<Godsnode>);
+    fn remove_subtree(&self, node: &Rc<Godsnode>) -> Vec<u64>;
+    fn take_removed(&self) -> Vec<u64>;
+    fn update_status(&self, node: &Rc<Godsnode>, health: GodsnodeHealth, reason: &str, at: u64) -> bool;
+    fn refresh_status(&self, node: &Rc<Godsnode>) -> bool;
+    fn take_status_changes(&self) -> Vec<u64>;
+    fn take_transitions(&self) -> Vec<GodsnodeTransition>;
+    fn record_metric(&self, node: &Rc<Godsnode>, metric: &str, value: f64, at: u64);
+    fn set_alerting(&self, node: &Rc<Godsnode>, severity: Option<AlertSeverity>) -> bool;
+    fn set_selected(&self, node: &Rc<Godsnode>, selected: bool) -> bool;
 }
 
 
@@ -155,9 +306,15 @@ impl StoreOps for Rc<Store> {
     fn new_node(&self) -> Rc<Godsnode> {
         let mut node = GodsnodeProto::new();
         let mut store = self.borrow_mut();
-        let id = store.id;
+        // Reuse ids of removed nodes first
+        let id = match store.free_ids.pop() {
+            Some(id) => id,
+            None => {
+                store.id += 1;
+                store.id - 1
+            }
+        };
         node.id = id;
-        store.id += 1;
         let new_node = Rc::new(RefCell::new(node));
         store.store.insert(id, new_node.clone());
         new_node
@@ -167,12 +324,30 @@ impl StoreOps for Rc<Store> {
         {
             let mut state = node.borrow_mut();
             state.name = name;
-            state.display_name = raw.get_str("display_name", "new node");
             state.node_type = GodsnodeType::Godsnode;
+            state.set_details(raw);
+            state.global_id = raw["id"].as_str().map(|id| id.to_string());
+            if let Some(global_id) = &state.global_id {
+                self.borrow_mut().globals.insert(global_id.clone(), state.id);
+            }
         }
         node
     }
 
+    // Node for a declaration in a wood, declarations with the global id of a stored node get that
+    // node back, flagged as shared
+    fn declare_node(&self, raw: &Value, name: String) -> (Rc<Godsnode>, bool) {
+        if let Some(node) = raw["id"].as_str().and_then(|id| self.get_shared_node(id)) {
+            return (node, true);
+        }
+        (self.add_node(raw, name), false)
+    }
+
+    fn get_shared_node(&self, id: &str) -> Option<Rc<Godsnode>> {
+        let state = self.borrow();
+        state.globals.get(id).and_then(|id| state.store.get(id)).cloned()
+    }
+
     fn add_leaf_node(&self, name: &String, raw: &Value) -> Rc<Godsnode> {
         let node = self.add_node(raw, name.clone());
         {
@@ -196,14 +371,392 @@ impl StoreOps for Rc<Store> {
         state.index.insert(name.clone(), index);
     }
 
-    fn get_weak_node(&self, path: &String) -> Option<Weak<Godsnode>> {
+    // Drop the path and every path below it
+    fn remove_index(&self, path: &str) {
+        let mut state = self.borrow_mut();
+        let prefix = format!("{}.", path);
+        state.index.retain(|name, _| name != path && !name.starts_with(&prefix));
+    }
+
+    fn get_node(&self, path: &str) -> Option<Rc<Godsnode>> {
         let state = self.borrow();
         if let Some(id) = state.index.get(path) {
-            if let Some(node) = state.store.get(&id) {
-                return Some(Rc::downgrade(&node.clone()));
+            if let Some(node) = state.store.get(id) {
+                return Some(node.clone());
             }
         }
         None
     }
+
+    fn get_weak_node(&self, path: &String) -> Option<Weak<Godsnode>> {
+        self.get_node(path).map(|node| Rc::downgrade(&node))
+    }
+
+    // Indexed paths accepted by the filter, with their nodes
+    fn read_paths<F: Fn(&str) -> bool>(&self, filter: F) -> Vec<(String, Weak<Godsnode>)> {
+        let state = self.borrow();
+        state.index.iter().filter(|(path, _)| filter(path)).filter_map(|(path, id)| {
+            state.store.get(id).map(|node| (path.clone(), Rc::downgrade(node)))
+        }).collect()
+    }
+
+    // Unlink the node from its parents and children and drop it from the store, its id is reclaimed
+    // and queued for the stage to despawn, paths through the node are forgotten by the index and the
+    // descendants
+    fn remove_node(&self, node: &Rc<Godsnode>) {
+        let id = node.borrow().id;
+        let prefixes: Vec<String> = self.borrow().index.iter()
+            .filter(|(_, index)| **index == id)
+            .map(|(path, _)| format!("{}.", path))
+            .collect();
+        let through = |path: &str| prefixes.iter().any(|prefix| path.starts_with(prefix));
+        let mut visited = HashSet::new();
+        let mut tasks: VecDeque<Rc<Godsnode>> = node.borrow().get_children().iter().filter_map(|kid| kid.upgrade()).collect();
+        while let Some(task) = tasks.pop_front() {
+            let mut state = task.borrow_mut();
+            if !visited.insert(state.id) {
+                continue;
+            }
+            state.app_meta_map.retain(|_, meta| !through(&meta.path.read()));
+            tasks.extend(state.get_children().iter().filter_map(|kid| kid.upgrade()));
+        }
+
+        let (global_id, parents, children) = {
+            let mut state = node.borrow_mut();
+            let parents = state.parents.drain(..).collect::<GodsnodeQ>();
+            let children = state.children.drain(..).collect::<GodsnodeQ>();
+            (state.global_id.clone(), parents, children)
+        };
+        for parent in parents.iter().filter_map(|parent| parent.upgrade()) {
+            parent.borrow_mut().remove_child(node);
+        }
+        for kid in children.iter().filter_map(|kid| kid.upgrade()) {
+            kid.borrow_mut().remove_parent(node);
+        }
+
+        let mut state = self.borrow_mut();
+        if state.store.remove(&id).is_some() {
+            state.index.retain(|path, index| *index != id && !through(path));
+            if let Some(global_id) = global_id {
+                state.globals.remove(&global_id);
+            }
+            state.free_ids.push(id);
+            state.removed.push(id);
+        }
+    }
+
+    // Remove the node and every descendant left without parents outside the subtree, nodes still
+    // shared with other parents are kept
+    fn remove_subtree(&self, node: &Rc<Godsnode>) -> Vec<u64> {
+        let mut removed = HashSet::new();
+        removed.insert(node.borrow().id);
+        let mut nodes = vec![node.clone()];
+
+        let mut descendants = Vec::new();
+        let mut visited = HashSet::new();
+        let mut tasks: VecDeque<Rc<Godsnode>> = VecDeque::new();
+        tasks.push_back(node.clone());
+        while let Some(task) = tasks.pop_front() {
+            for kid in task.borrow().get_children().iter().filter_map(|kid| kid.upgrade()) {
+                if visited.insert(kid.borrow().id) {
+                    descendants.push(kid.clone());
+                    tasks.push_back(kid);
+                }
+            }
+        }
+
+        loop {
+            let mut changed = false;
+            for kid in descendants.iter() {
+                let kid_state = kid.borrow();
+                if removed.contains(&kid_state.id) {
+                    continue;
+                }
+                let orphan = kid_state.get_parents().iter().all(|parent| {
+                    match parent.upgrade() {
+                        Some(parent) => removed.contains(&parent.borrow().id),
+                        None => true,
+                    }
+                });
+                if orphan {
+                    removed.insert(kid_state.id);
+                    nodes.push(kid.clone());
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut ids = Vec::new();
+        for node in nodes.iter() {
+            ids.push(node.borrow().id);
+            self.remove_node(node);
+        }
+        ids
+    }
+
+    fn take_removed(&self) -> Vec<u64> {
+        let mut state = self.borrow_mut();
+        state.removed.drain(..).collect()
+    }
+
+    // Report the status of the node, the node and its ancestors are rolled up again through the parents
+    // links, stopping where the effective status stays the same
+    fn update_status(&self, node: &Rc<Godsnode>, health: GodsnodeHealth, reason: &str, at: u64) -> bool {
+        {
+            let mut state = node.borrow_mut();
+            let reported = state.reported.get_or_insert(GodsnodeStatus {
+                health,
+                changed_at: at,
+                reason: reason.to_string(),
+            });
+            reported.update(health, reason, at);
+        }
+
+        let changed = self.refresh_status(node);
+        if !changed {
+            return false;
+        }
+        let mut tasks: VecDeque<Rc<Godsnode>> = node.borrow().get_parents().iter().filter_map(|parent| parent.upgrade()).collect();
+        while let Some(task) = tasks.pop_front() {
+            if self.refresh_status(&task) {
+                tasks.extend(task.borrow().get_parents().iter().filter_map(|parent| parent.upgrade()));
+            }
+        }
+        true
+    }
+
+    // Roll up the effective status of the node from its children, changed nodes are queued for the
+    // stage to redraw
+    fn refresh_status(&self, node: &Rc<Godsnode>) -> bool {
+        let status = {
+            let state = node.borrow();
+            let kids: Vec<(GodsnodeHealth, u64)> = state.get_children().iter().filter_map(|kid| kid.upgrade()).map(|kid| {
+                let kid = kid.borrow();
+                (kid.status.health, kid.status.changed_at)
+            }).collect();
+            let healths: Vec<GodsnodeHealth> = kids.iter().map(|(health, _)| *health).collect();
+            let changed_at = kids.iter().map(|(_, at)| *at).max().unwrap_or(0);
+
+            match (state.reported.as_ref(), state.rollup.aggregate(&healths)) {
+                (Some(reported), None) => reported.clone(),
+                (None, None) => GodsnodeStatus::new(),
+                (reported, Some(health)) => {
+                    // At least N policies read better counting the healthy children
+                    let counted = match state.rollup {
+                        GodsnodeRollup::AtLeast(_) => GodsnodeHealth::Healthy,
+                        _ => health,
+                    };
+                    let count = healths.iter().filter(|kid| **kid == counted).count();
+                    let mut status = GodsnodeStatus {
+                        health,
+                        changed_at,
+                        reason: format!("{} of {} children {}", count, healths.len(), counted.as_str()),
+                    };
+                    if let Some(reported) = reported {
+                        if reported.health.severity() >= health.severity() {
+                            status.health = reported.health;
+                            status.reason = reported.reason.clone();
+                        }
+                        status.changed_at = status.changed_at.max(reported.changed_at);
+                    }
+                    status
+                },
+            }
+        };
+
+        let mut state = node.borrow_mut();
+        let from = state.status.health;
+        let initial = from == GodsnodeHealth::Unknown && state.status.changed_at == 0;
+        if !state.status.update(status.health, &status.reason, status.changed_at) {
+            return false;
+        }
+        let mut store = self.borrow_mut();
+        store.status_changes.insert(state.id);
+        if from != status.health {
+            if let Some(path) = state.get_path() {
+                store.transitions.push(GodsnodeTransition {
+                    path,
+                    from: if initial { None } else { Some(from) },
+                    to: status.health,
+                    reason: status.reason,
+                    at: status.changed_at,
+                });
+            }
+        }
+        true
+    }
+
+    fn take_status_changes(&self) -> Vec<u64> {
+        let mut state = self.borrow_mut();
+        state.status_changes.drain().collect()
+    }
+
+    // Health changes since the last call, in order
+    fn take_transitions(&self) -> Vec<GodsnodeTransition> {
+        let mut state = self.borrow_mut();
+        state.transitions.drain(..).collect()
+    }
+
+    // Samples older than the latest one are dropped, as are infinite and NaN values
+    fn record_metric(&self, node: &Rc<Godsnode>, metric: &str, value: f64, at: u64) {
+        let mut state = node.borrow_mut();
+        if !value.is_finite() {
+            log!("Dropped {} {} of node {}", metric, value, state.get_path().unwrap_or_default());
+            return;
+        }
+        match state.metrics.get(metric) {
+            Some((_, latest)) if *latest > at => {},
+            _ => {
+                state.metrics.insert(metric.to_string(), (value, at));
+                state.series.entry(metric.to_string()).or_insert_with(MetricSeries::new).push(at, value);
+            },
+        }
+    }
+
+    // Counts as a status change so the node gets redrawn
+    fn set_alerting(&self, node: &Rc<Godsnode>, severity: Option<AlertSeverity>) -> bool {
+        let mut state = node.borrow_mut();
+        if state.alerting == severity {
+            return false;
+        }
+        state.alerting = severity;
+        self.borrow_mut().status_changes.insert(state.id);
+        true
+    }
+
+    fn set_selected(&self, node: &Rc<Godsnode>, selected: bool) -> bool {
+        let mut state = node.borrow_mut();
+        if state.selected == selected {
+            return false;
+        }
+        state.selected = selected;
+        self.borrow_mut().status_changes.insert(state.id);
+        true
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::span::godswood::tree::Godswoods;
+
+    // node3 is declared under node1 and linked under node2
+    fn woods() -> Godswoods {
+        let mut woods = Godswoods::new();
+        woods.add_wood(&json!({
+            "name": "app",
+            "children": {
+                "node1": { "children": { "node3": { "children": { "node4": {} } } } },
+                "node2": { "class": "cache", "depends_on": [".app.node1.node3"] },
+            },
+        })).unwrap();
+        woods
+    }
+
+    fn id(woods: &Godswoods, path: &str) -> u64 {
+        woods.store.get_node(path).unwrap().borrow().id
+    }
+
+    #[test]
+    fn parses_classes() {
+        let classes = [
+            GodsnodeClass::General, GodsnodeClass::Database, GodsnodeClass::Cache, GodsnodeClass::Queue,
+            GodsnodeClass::Gateway, GodsnodeClass::Worker, GodsnodeClass::External,
+        ];
+        for class in classes.iter() {
+            assert_eq!(GodsnodeClass::parse(class.as_str()), *class);
+        }
+        assert_eq!(GodsnodeClass::parse("Database"), GodsnodeClass::Database);
+        assert_eq!(GodsnodeClass::parse("mainframe"), GodsnodeClass::General);
+
+        let woods = woods();
+        assert_eq!(woods.store.get_node(".app.node1").unwrap().borrow().service_type, GodsnodeClass::General);
+        assert_eq!(woods.store.get_node(".app.node2").unwrap().borrow().service_type, GodsnodeClass::Cache);
+    }
+
+    #[test]
+    fn removes_subtrees() {
+        let woods = woods();
+        let ids: Vec<u64> = [".app.node1", ".app.node2", ".app.node1.node3", ".app.node1.node3.node4"].iter()
+            .map(|path| id(&woods, path))
+            .collect();
+        let node1 = woods.store.get_node(".app.node1").unwrap();
+        let node3 = woods.store.get_node(".app.node1.node3").unwrap();
+        assert_eq!(woods.store.remove_subtree(&node1), vec![ids[0]]);
+        assert!(woods.store.get_node(".app.node1").is_none());
+        assert_eq!(node3.borrow().get_parents().len(), 1);
+
+        let node2 = woods.store.get_node(".app.node2").unwrap();
+        assert_eq!(woods.store.remove_subtree(&node2), ids[1..].to_vec());
+        assert!(woods.store.get_node(".app.node2.node3.node4").is_none());
+        assert_eq!(woods.store.take_removed(), ids);
+        assert!(woods.store.take_removed().is_empty());
+    }
+
+    #[test]
+    fn forgets_paths_through_removed_nodes() {
+        let woods = woods();
+        let node1 = woods.store.get_node(".app.node1").unwrap();
+        let node3 = woods.store.get_node(".app.node1.node3").unwrap();
+        let node4 = woods.store.get_node(".app.node1.node3.node4").unwrap();
+        woods.store.remove_subtree(&node1);
+        assert!(woods.store.get_node(".app.node1.node3").is_none());
+        assert!(woods.store.get_node(".app.node1.node3.node4").is_none());
+        assert!(node3.borrow().read_path(&"app".to_string()).is_none());
+        assert!(node4.borrow().read_path(&"app".to_string()).is_none());
+        // Still reached through node2
+        assert!(Rc::ptr_eq(&woods.store.get_node(".app.node2.node3.node4").unwrap(), &node4));
+    }
+
+    #[test]
+    fn reuses_ids() {
+        let woods = woods();
+        let node2 = woods.store.get_node(".app.node2").unwrap();
+        let id = node2.borrow().id;
+        woods.store.remove_node(&node2);
+        assert!(woods.store.get_node(".app.node2").is_none());
+        assert_eq!(woods.store.get_node(".app.node1.node3").unwrap().borrow().get_parents().len(), 1);
+        assert_eq!(woods.store.new_node().borrow().id, id);
+        assert_eq!(woods.store.new_node().borrow().id, 5);
+    }
+
+    #[test]
+    fn records_metrics() {
+        let woods = woods();
+        let node2 = woods.store.get_node(".app.node2").unwrap();
+        woods.store.record_metric(&node2, "rps", 12., 20);
+        woods.store.record_metric(&node2, "rps", 9., 10);
+        woods.store.record_metric(&node2, "rps", f64::INFINITY, 30);
+        woods.store.record_metric(&node2, "rps", f64::NAN, 40);
+        woods.store.record_metric(&node2, "errors", f64::NEG_INFINITY, 40);
+        let node2 = node2.borrow();
+        assert_eq!(node2.metrics.get("rps"), Some(&(12., 20)));
+        assert_eq!(node2.series.get("rps").unwrap().get_samples(0, 10).len(), 1);
+        assert!(!node2.metrics.contains_key("errors") && !node2.series.contains_key("errors"));
+    }
+
+    #[test]
+    fn rolls_up_status() {
+        let woods = woods();
+        let health = |path: &str| woods.store.get_node(path).unwrap().borrow().status.health;
+        let node4 = woods.store.get_node(".app.node1.node3.node4").unwrap();
+        assert!(woods.store.update_status(&node4, GodsnodeHealth::Down, "crashed", 10));
+        for path in [".app", ".app.node1", ".app.node2", ".app.node1.node3"].iter() {
+            assert_eq!(health(path), GodsnodeHealth::Down);
+        }
+        let app = woods.store.get_node(".app").unwrap();
+        assert_eq!(app.borrow().status.changed_at, 10);
+
+        // Reported health wins when worse than the children
+        let node1 = woods.store.get_node(".app.node1").unwrap();
+        assert!(!woods.store.update_status(&node1, GodsnodeHealth::Degraded, "slow", 20));
+        assert!(woods.store.update_status(&node4, GodsnodeHealth::Healthy, "", 30));
+        assert_eq!(health(".app.node1"), GodsnodeHealth::Degraded);
+        assert_eq!(health(".app.node2"), GodsnodeHealth::Healthy);
+        assert_eq!(node1.borrow().status.reason, "slow");
+    }
+}
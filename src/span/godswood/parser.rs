@@ -0,0 +1,577 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use serde::de::{self, Deserializer, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Number, Value};
+use crate::span::godswood::error::*;
+use crate::span::godswood::status::GodsnodeRollup;
+
+// Node names become path segments, so dots and glob characters are not allowed
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+pub fn key_json_path(path: &str, key: &str) -> String {
+    if is_valid_name(key) {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{:?}]", path, key)
+    }
+}
+
+pub fn child_json_path(path: &str, name: &str) -> String {
+    key_json_path(&format!("{}.children", path), name)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WoodFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl WoodFormat {
+    pub fn from_file_name(name: &str) -> Option<WoodFormat> {
+        match name.rsplit('.').next() {
+            Some("json") => Some(WoodFormat::Json),
+            Some("yaml") | Some("yml") => Some(WoodFormat::Yaml),
+            Some("toml") => Some(WoodFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+// Parse wood text of any format into the JSON value the wood is built from, rejecting duplicate
+// keys which serde_json would silently overwrite
+pub fn parse_source(raw: &str, format: WoodFormat) -> Result<Value, WoodError> {
+    let duplicate = RefCell::new(None);
+    let seed = SourceSeed { path: "$".to_string(), target: None, duplicate: &duplicate };
+    scan_source(raw, format, seed).map_err(|(msg, location)| {
+        let err = match duplicate.into_inner() {
+            Some(err) => err,
+            None => WoodError::new(WoodErrorKind::Syntax(msg), "$"),
+        };
+        err.with_location(location)
+    })
+}
+
+// Find line and column of the value at the JSON path in the source text
+pub fn locate(raw: &str, format: WoodFormat, path: &str) -> Option<(usize, usize)> {
+    let duplicate = RefCell::new(None);
+    let seed = SourceSeed { path: "$".to_string(), target: Some(path), duplicate: &duplicate };
+    match scan_source(raw, format, seed) {
+        Ok(_) => None,
+        Err((_, location)) => location,
+    }
+}
+
+// Build the value of the source, errors carry the message with the 1-based line and column reported by the format
+fn scan_source(raw: &str, format: WoodFormat, seed: SourceSeed) -> Result<Value, (String, Option<(usize, usize)>)> {
+    match format {
+        WoodFormat::Json => {
+            let mut de = serde_json::Deserializer::from_str(raw);
+            seed.deserialize(&mut de).and_then(|value| de.end().map(|_| value)).map_err(|err| {
+                (err.to_string(), Some((err.line(), err.column())))
+            })
+        },
+        WoodFormat::Yaml => {
+            seed.deserialize(serde_yaml::Deserializer::from_str(raw)).map_err(|err| {
+                (err.to_string(), err.location().map(|location| (location.line(), location.column())))
+            })
+        },
+        // The whole document is parsed before any value is visited, so trailing text fails here
+        // already, unlike JSON there is nothing left for a deprecated `end()` to check
+        WoodFormat::Toml => {
+            seed.deserialize(&mut toml::Deserializer::new(raw)).map_err(|err| {
+                (err.to_string(), err.line_col().map(|(line, column)| (line + 1, column + 1)))
+            })
+        },
+    }
+}
+
+// Builds every value of the source tracking its JSON path, fails on duplicate keys and on reaching
+// the target path, so the format reports the position
+struct SourceSeed<'a> {
+    path: String,
+    target: Option<&'a str>,
+    duplicate: &'a RefCell<Option<WoodError>>,
+}
+
+impl<'a> SourceSeed<'a> {
+    fn child(&self, path: String) -> SourceSeed<'a> {
+        SourceSeed { path, target: self.target, duplicate: self.duplicate }
+    }
+
+    // Fails on the target and on the value of a duplicate key, so the position points at the value
+    fn check_target<E: de::Error>(&self) -> Result<(), E> {
+        if let Some(err) = self.duplicate.borrow().as_ref() {
+            return Err(E::custom(format!("duplicate key at {}", err.path)));
+        }
+        if self.target == Some(self.path.as_str()) {
+            return Err(E::custom(format!("reached {}", self.path)));
+        }
+        Ok(())
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for SourceSeed<'a> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for SourceSeed<'a> {
+    type Value = Value;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Value, E> {
+        self.check_target().map(|_| Value::Bool(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Value, E> {
+        self.check_target().map(|_| Value::from(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Value, E> {
+        self.check_target().map(|_| Value::from(value))
+    }
+
+    // JSON has no NaN or infinity, like serde_json they become null
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Value, E> {
+        self.check_target().map(|_| Number::from_f64(value).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Value, E> {
+        self.check_target().map(|_| Value::String(value.to_string()))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        self.check_target().map(|_| Value::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        self.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        self.check_target()?;
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element_seed(self.child(format!("{}[{}]", self.path, values.len())))? {
+            values.push(value);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        self.check_target()?;
+        let mut values = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let path = key_json_path(&self.path, &key);
+            if values.contains_key(&key) {
+                *self.duplicate.borrow_mut() = Some(WoodError::new(WoodErrorKind::DuplicateName(key), &path));
+                map.next_value_seed(self.child(path.clone()))?;
+                return Err(de::Error::custom(format!("duplicate key at {}", path)));
+            }
+            let value = map.next_value_seed(self.child(path))?;
+            values.insert(key, value);
+        }
+        Ok(Value::Object(values))
+    }
+}
+
+struct WoodNode {
+    children: Vec<(String, String)>,
+}
+
+// A `depends_on` entry of the node at `node`, `json_path` points at the entry
+pub struct WoodLink {
+    pub node: String,
+    pub target: String,
+    pub json_path: String,
+}
+
+// A node declared with a global `id`, `raw` is the declaration
+pub struct WoodShared<'a> {
+    pub id: String,
+    pub name: String,
+    pub json_path: String,
+    pub raw: &'a Value,
+}
+
+// Check the structure of a wood before any node is created in the store
+pub fn validate_wood(raw: &Value) -> Result<(), WoodError> {
+    if !raw.is_object() {
+        return Err(WoodError::new(WoodErrorKind::MissingRoot, "$"));
+    }
+    let name = match &raw["name"] {
+        Value::String(name) => name,
+        Value::Null => return Err(WoodError::new(WoodErrorKind::EmptyName, "$.name")),
+        _ => return Err(WoodError::new(WoodErrorKind::InvalidField("name".to_string()), "$.name")),
+    };
+    if name.is_empty() {
+        return Err(WoodError::new(WoodErrorKind::EmptyName, "$.name"));
+    }
+    if !is_valid_name(name) {
+        return Err(WoodError::new(WoodErrorKind::IllegalName(name.clone()), "$.name"));
+    }
+
+    if !raw["id"].is_null() {
+        return Err(WoodError::new(WoodErrorKind::InvalidField("id".to_string()), "$.id"));
+    }
+
+    let links = validate_tree(raw, &format!(".{}", name))?;
+    if let Some(link) = links.into_iter().next() {
+        return Err(WoodError::new(WoodErrorKind::UnknownDependency(link.target), &link.json_path));
+    }
+    Ok(())
+}
+
+// Check the node at `node_path` and its subtree, links pointing out of the subtree are returned
+// for the caller to resolve
+pub fn validate_tree(raw: &Value, node_path: &str) -> Result<Vec<WoodLink>, WoodError> {
+    let mut nodes: HashMap<String, WoodNode> = HashMap::new();
+    let mut links: Vec<WoodLink> = Vec::new();
+    collect_node(raw, node_path, "$", &mut nodes, &mut links)?;
+
+    // Linked nodes join the children of the dependent node, so their names must not clash
+    let mut external = Vec::new();
+    for link in links.into_iter() {
+        let node = nodes.get_mut(&link.node).unwrap();
+        let target_name = link.target.rsplit('.').next().unwrap_or("").to_string();
+        let mut linked = false;
+        for (child, _) in node.children.iter() {
+            if *child == link.target {
+                linked = true;
+                break;
+            }
+            if child.rsplit('.').next() == Some(target_name.as_str()) {
+                return Err(WoodError::new(WoodErrorKind::DuplicateName(target_name), &link.json_path));
+            }
+        }
+        if !nodes.contains_key(&link.target) {
+            external.push(link);
+            continue;
+        }
+        if !linked {
+            nodes.get_mut(&link.node).unwrap().children.push((link.target, link.json_path));
+        }
+    }
+
+    check_cycles(node_path, &nodes)?;
+    Ok(external)
+}
+
+fn collect_node(
+    raw: &Value,
+    node_path: &str,
+    json_path: &str,
+    nodes: &mut HashMap<String, WoodNode>,
+    links: &mut Vec<WoodLink>,
+) -> Result<(), WoodError> {
+    let mut node = WoodNode {
+        children: Vec::new(),
+    };
+
+    match &raw["class"] {
+        Value::Null | Value::String(_) => {},
+        _ => return Err(WoodError::new(WoodErrorKind::InvalidField("class".to_string()), &format!("{}.class", json_path))),
+    }
+
+    match &raw["attributes"] {
+        Value::Null => {},
+        Value::Object(attributes) => {
+            for (key, value) in attributes.iter() {
+                if value.is_object() || value.is_array() {
+                    return Err(WoodError::new(WoodErrorKind::InvalidField(key.clone()), &key_json_path(&format!("{}.attributes", json_path), key)));
+                }
+            }
+        },
+        _ => return Err(WoodError::new(WoodErrorKind::InvalidField("attributes".to_string()), &format!("{}.attributes", json_path))),
+    }
+
+    match &raw["tags"] {
+        Value::Null => {},
+        Value::Array(tags) => {
+            for (i, tag) in tags.iter().enumerate() {
+                if !tag.is_string() {
+                    return Err(WoodError::new(WoodErrorKind::InvalidField("tags".to_string()), &format!("{}.tags[{}]", json_path, i)));
+                }
+            }
+        },
+        _ => return Err(WoodError::new(WoodErrorKind::InvalidField("tags".to_string()), &format!("{}.tags", json_path))),
+    }
+
+    match &raw["id"] {
+        Value::Null => {},
+        Value::String(id) if is_valid_name(id) => {},
+        _ => return Err(WoodError::new(WoodErrorKind::InvalidField("id".to_string()), &format!("{}.id", json_path))),
+    }
+
+    match &raw["rollup"] {
+        Value::Null => {},
+        Value::String(rollup) if GodsnodeRollup::parse(rollup).is_some() => {},
+        _ => return Err(WoodError::new(WoodErrorKind::InvalidField("rollup".to_string()), &format!("{}.rollup", json_path))),
+    }
+
+    match &raw["depends_on"] {
+        Value::Null => {},
+        Value::Array(paths) => {
+            for (i, path) in paths.iter().enumerate() {
+                let link_path = format!("{}.depends_on[{}]", json_path, i);
+                match path.as_str() {
+                    Some(path) => links.push(WoodLink {
+                        node: node_path.to_string(),
+                        target: path.to_string(),
+                        json_path: link_path,
+                    }),
+                    None => return Err(WoodError::new(WoodErrorKind::InvalidField("depends_on".to_string()), &link_path)),
+                }
+            }
+        },
+        _ => return Err(WoodError::new(WoodErrorKind::InvalidField("depends_on".to_string()), &format!("{}.depends_on", json_path))),
+    }
+
+    match &raw["children"] {
+        Value::Null => {},
+        Value::Object(children) => {
+            for (name, child) in children.iter() {
+                let child_json_path = child_json_path(json_path, name);
+                if !is_valid_name(name) {
+                    let kind = if name.is_empty() { WoodErrorKind::EmptyName } else { WoodErrorKind::IllegalName(name.clone()) };
+                    return Err(WoodError::new(kind, &child_json_path));
+                }
+                // Leaves like `node3:` in YAML come without any field
+                if !child.is_object() && !child.is_null() {
+                    return Err(WoodError::new(WoodErrorKind::InvalidField(name.clone()), &child_json_path));
+                }
+                let child_path = format!("{}.{}", node_path, name);
+                collect_node(child, &child_path, &child_json_path, nodes, links)?;
+                node.children.push((child_path, child_json_path));
+            }
+        },
+        _ => return Err(WoodError::new(WoodErrorKind::InvalidField("children".to_string()), &format!("{}.children", json_path))),
+    }
+
+    nodes.insert(node_path.to_string(), node);
+    Ok(())
+}
+
+// Shared declarations of the node named `name` and its subtree, pass no name for a wood root
+// An id declared twice in the same tree is rejected, `depends_on` links a node twice instead
+pub fn collect_shared<'a>(raw: &'a Value, name: Option<&str>, json_path: &str) -> Result<Vec<WoodShared<'a>>, WoodError> {
+    let mut shared: Vec<WoodShared> = Vec::new();
+    let mut tasks: Vec<(&Value, Option<&str>, String)> = vec![(raw, name, json_path.to_string())];
+    while let Some((raw, name, json_path)) = tasks.pop() {
+        if let (Some(name), Some(id)) = (name, raw["id"].as_str()) {
+            if shared.iter().any(|node| node.id == id) {
+                return Err(WoodError::new(WoodErrorKind::SharedNode(id.to_string()), &format!("{}.id", json_path)));
+            }
+            shared.push(WoodShared {
+                id: id.to_string(),
+                name: name.to_string(),
+                json_path: json_path.clone(),
+                raw,
+            });
+        }
+        if let Some(children) = raw["children"].as_object() {
+            for (name, child) in children.iter() {
+                tasks.push((child, Some(name), child_json_path(&json_path, name)));
+            }
+        }
+    }
+    Ok(shared)
+}
+
+// Depth first walk, reaching a node that is still on the stack closes a cycle
+fn check_cycles(root: &str, nodes: &HashMap<String, WoodNode>) -> Result<(), WoodError> {
+    let mut done: HashSet<&str> = HashSet::new();
+    let mut visiting: HashSet<&str> = HashSet::new();
+    let mut stack: Vec<(&str, usize)> = vec![(root, 0)];
+    visiting.insert(root);
+
+    while let Some((path, next)) = stack.pop() {
+        let node = nodes.get(path).unwrap();
+        if next >= node.children.len() {
+            visiting.remove(path);
+            done.insert(path);
+            continue;
+        }
+        stack.push((path, next + 1));
+        let (child, json_path) = &node.children[next];
+        if visiting.contains(child.as_str()) {
+            return Err(WoodError::new(WoodErrorKind::Cycle(child.clone()), json_path));
+        }
+        if !done.contains(child.as_str()) {
+            visiting.insert(child);
+            stack.push((child, 0));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::span::godswood::tree::Godswoods;
+
+    fn reject(raw: Value) -> (WoodErrorKind, String) {
+        let err = validate_wood(&raw).unwrap_err();
+        (err.kind, err.path)
+    }
+
+    #[test]
+    fn parses_like_serde_json() {
+        let raw = r#"{"name": "app", "children": {"a": {"attributes": {"port": 80, "ratio": 0.5, "up": true, "note": null}, "tags": ["x"]}}}"#;
+        assert_eq!(parse_source(raw, WoodFormat::Json).unwrap(), serde_json::from_str::<Value>(raw).unwrap());
+        assert!(matches!(parse_source(r#"{"name": "app"} {}"#, WoodFormat::Json).unwrap_err().kind, WoodErrorKind::Syntax(_)));
+    }
+
+    #[test]
+    fn rejects_duplicate_keys() {
+        let err = parse_source(r#"{"name": "app", "children": {"a": {}, "a": {}}}"#, WoodFormat::Json).unwrap_err();
+        assert_eq!(err.kind, WoodErrorKind::DuplicateName("a".to_string()));
+        assert_eq!(err.path, "$.children.a");
+
+        let err = parse_source("name: app\nname: other\n", WoodFormat::Yaml).unwrap_err();
+        assert_eq!(err.kind, WoodErrorKind::DuplicateName("name".to_string()));
+        assert_eq!(err.path, "$.name");
+    }
+
+    #[test]
+    fn rejects_bad_names() {
+        assert_eq!(reject(json!([])), (WoodErrorKind::MissingRoot, "$".to_string()));
+        assert_eq!(reject(json!({})), (WoodErrorKind::EmptyName, "$.name".to_string()));
+        assert_eq!(reject(json!({ "name": "a.b" })), (WoodErrorKind::IllegalName("a.b".to_string()), "$.name".to_string()));
+        assert_eq!(
+            reject(json!({ "name": "app", "children": { "a*": {} } })),
+            (WoodErrorKind::IllegalName("a*".to_string()), "$.children[\"a*\"]".to_string()));
+        assert_eq!(
+            reject(json!({ "name": "app", "children": { "a": { "children": { "b": {} } }, "b": { "depends_on": [".app.a.b"] }, "c": { "children": { "b": {} }, "depends_on": [".app.a.b"] } } })),
+            (WoodErrorKind::DuplicateName("b".to_string()), "$.children.c.depends_on[0]".to_string()));
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let (kind, path) = reject(json!({
+            "name": "app",
+            "children": {
+                "a": { "depends_on": [".app.b"] },
+                "b": { "depends_on": [".app.a"] },
+            },
+        }));
+        assert!(matches!(kind, WoodErrorKind::Cycle(_)));
+        assert!(path.ends_with(".depends_on[0]"), "{}", path);
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        let (kind, path) = reject(json!({ "name": "app", "children": { "a": { "tags": ["x", 1] } } }));
+        assert_eq!(kind, WoodErrorKind::InvalidField("tags".to_string()));
+        assert_eq!(path, "$.children.a.tags[1]");
+
+        let (kind, path) = reject(json!({ "name": "app", "children": { "a": { "attributes": { "port": [80] } } } }));
+        assert_eq!(kind, WoodErrorKind::InvalidField("port".to_string()));
+        assert_eq!(path, "$.children.a.attributes.port");
+
+        let (kind, path) = reject(json!({ "name": "app", "children": { "a": { "depends_on": ".app" } } }));
+        assert_eq!(kind, WoodErrorKind::InvalidField("depends_on".to_string()));
+        assert_eq!(path, "$.children.a.depends_on");
+
+        let (kind, path) = reject(json!({ "name": "app", "children": { "a": { "depends_on": [1] } } }));
+        assert_eq!(kind, WoodErrorKind::InvalidField("depends_on".to_string()));
+        assert_eq!(path, "$.children.a.depends_on[0]");
+
+        let (kind, path) = reject(json!({ "name": "app", "children": [] }));
+        assert_eq!(kind, WoodErrorKind::InvalidField("children".to_string()));
+        assert_eq!(path, "$.children");
+
+        let (kind, path) = reject(json!({ "name": "app", "children": { "a": { "rollup": "sometimes" } } }));
+        assert_eq!(kind, WoodErrorKind::InvalidField("rollup".to_string()));
+        assert_eq!(path, "$.children.a.rollup");
+
+        let (kind, path) = reject(json!({ "name": "app", "id": "app" }));
+        assert_eq!(kind, WoodErrorKind::InvalidField("id".to_string()));
+        assert_eq!(path, "$.id");
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies() {
+        let (kind, path) = reject(json!({
+            "name": "app",
+            "children": {
+                "a": {},
+                "b": { "depends_on": [".app.a", ".app.c"] },
+            },
+        }));
+        assert_eq!(kind, WoodErrorKind::UnknownDependency(".app.c".to_string()));
+        assert_eq!(path, "$.children.b.depends_on[1]");
+    }
+
+    const YAML: &str = "\
+name: app
+children:
+  node1:
+    children:
+      node3:
+  node2:
+    tags: [edge, 7]
+";
+
+    #[test]
+    fn accepts_empty_yaml_leaves() {
+        let raw = parse_source(YAML, WoodFormat::Yaml).unwrap();
+        assert!(raw["children"]["node1"]["children"]["node3"].is_null());
+        assert_eq!(reject(raw).1, "$.children.node2.tags[1]");
+    }
+
+    #[test]
+    fn locates_syntax_errors() {
+        let err = parse_source("{\"name\": \"app\",\n  \"children\": {\n    \"node1\": {,}\n  }\n}", WoodFormat::Json).unwrap_err();
+        assert!(matches!(err.kind, WoodErrorKind::Syntax(_)));
+        assert_eq!(err.location, Some((3, 15)));
+
+        let err = parse_source("name = \"app\"\n[children.node1]\nclass = \n", WoodFormat::Toml).unwrap_err();
+        assert!(matches!(err.kind, WoodErrorKind::Syntax(_)));
+        assert_eq!(err.location.map(|(line, _)| line), Some(3));
+
+        let err = parse_source("name = \"app\"\n]]", WoodFormat::Toml).unwrap_err();
+        assert_eq!(err.location, Some((2, 1)));
+
+        let err = parse_source("name: app\nchildren:\n  node1: {}\n  node1: {}\n", WoodFormat::Yaml).unwrap_err();
+        assert_eq!(err.kind, WoodErrorKind::DuplicateName("node1".to_string()));
+        assert_eq!(err.location.map(|(line, _)| line), Some(4));
+
+        let err = parse_source("{\"name\": \"app\",\n \"name\": \"shop\"}", WoodFormat::Json).unwrap_err();
+        assert_eq!(err.kind, WoodErrorKind::DuplicateName("name".to_string()));
+        assert_eq!(err.location, Some((2, 15)));
+    }
+
+    #[test]
+    fn locates_values() {
+        assert_eq!(locate(YAML, WoodFormat::Yaml, "$.children.node2.tags[1]"), Some((7, 18)));
+        assert_eq!(locate(YAML, WoodFormat::Yaml, "$.children.node4"), None);
+
+        let raw = "{\"name\": \"app\", \"children\": {\n  \"node1\": {\"class\": 3}\n}}";
+        assert_eq!(locate(raw, WoodFormat::Json, "$.children.node1.class"), Some((2, 22)));
+
+        let raw = "name = \"app\"\n\n[children.node1]\nclass = 3\n";
+        assert_eq!(locate(raw, WoodFormat::Toml, "$.children.node1.class").map(|(line, _)| line), Some(4));
+    }
+
+    #[test]
+    fn locates_wood_errors() {
+        let err = Godswoods::new().add_wood_from_str(YAML, WoodFormat::Yaml).unwrap_err();
+        assert_eq!(err.kind, WoodErrorKind::InvalidField("tags".to_string()));
+        assert_eq!(err.location, Some((7, 18)));
+    }
+}
@@ -0,0 +1,483 @@
+use std::rc::Rc;
+use std::collections::{HashSet, VecDeque};
+use serde_json::Value;
+use crate::span::godswood::node::*;
+use crate::span::godswood::tree::*;
+use crate::span::godswood::error::*;
+use crate::span::godswood::parser;
+
+// Change to a live wood, nodes are addressed by their dotted path like `.app.node1.node3`
+pub enum GodswoodPatch {
+    // Add a node with its subtree under the parent, `raw` is the node definition as in wood JSON
+    Add { parent: String, name: String, raw: Value },
+    // Detach the node from the parent in the path, it is removed with its subtree once no parent is left
+    Remove { path: String },
+    Rename { path: String, name: String },
+    // Move the node under a new parent, dropping the parent it is declared under in the wood, links
+    // and parents in other woods are kept
+    Reparent { path: String, parent: String },
+    // Replace the details and `depends_on` links of the node with those of the node definition,
+    // its declared children are left alone
+    Update { path: String, raw: Value },
+}
+
+impl GodswoodPatch {
+    // Patch in JSON, like
+    // `{"op": "add", "parent": ".app.node1", "name": "node4", "node": {"class": "cache"}}`
+    // `{"op": "remove", "path": ".app.node2"}`
+    // `{"op": "rename", "path": ".app.node2", "name": "node5"}`
+    // `{"op": "move", "path": ".app.node2", "parent": ".app.node1"}`
+    // `{"op": "update", "path": ".app.node2", "node": {"class": "worker", "depends_on": [".app.node1"]}}`
+    pub fn from_json(raw: &Value) -> Result<GodswoodPatch, WoodError> {
+        let field = |key: &str| match raw[key].as_str() {
+            Some(value) => Ok(value.to_string()),
+            None => Err(WoodError::new(WoodErrorKind::InvalidField(key.to_string()), &format!("$.{}", key))),
+        };
+        match field("op")?.as_str() {
+            "add" => Ok(GodswoodPatch::Add { parent: field("parent")?, name: field("name")?, raw: raw["node"].clone() }),
+            "remove" => Ok(GodswoodPatch::Remove { path: field("path")? }),
+            "rename" => Ok(GodswoodPatch::Rename { path: field("path")?, name: field("name")? }),
+            "move" => Ok(GodswoodPatch::Reparent { path: field("path")?, parent: field("parent")? }),
+            "update" => Ok(GodswoodPatch::Update { path: field("path")?, raw: raw["node"].clone() }),
+            _ => Err(WoodError::new(WoodErrorKind::InvalidField("op".to_string()), "$.op")),
+        }
+    }
+
+    pub fn target(&self) -> &String {
+        match self {
+            GodswoodPatch::Add { parent, .. } => parent,
+            GodswoodPatch::Remove { path } => path,
+            GodswoodPatch::Rename { path, .. } => path,
+            GodswoodPatch::Reparent { path, .. } => path,
+            GodswoodPatch::Update { path, .. } => path,
+        }
+    }
+}
+
+impl Godswoods {
+    pub fn patch(&mut self, patch: &GodswoodPatch) -> Result<(), WoodError> {
+        let target = patch.target();
+        let app = match GodswoodMeta::parse_app_name(target) {
+            Some(app) => app,
+            None => return Err(WoodError::new(WoodErrorKind::UnknownNode(target.clone()), target)),
+        };
+        let wood = match self.woods.borrow().get(&app) {
+            Some(wood) => wood.clone(),
+            None => return Err(WoodError::new(WoodErrorKind::UnknownNode(target.clone()), target)),
+        };
+        let root = wood.borrow().wood.get_root().upgrade();
+        let nodes = root.as_ref().map(GodswoodProto::collect_nodes).unwrap_or_default();
+
+        match patch {
+            GodswoodPatch::Add { parent, name, raw } => {
+                let parent_node = self.find_node(parent)?;
+                let path = format!("{}.{}", parent, name);
+                check_name(name, &path)?;
+                if find_child(&parent_node, name).is_some() {
+                    return Err(WoodError::new(WoodErrorKind::DuplicateName(name.clone()), &path));
+                }
+                for link in parser::validate_tree(raw, &path)?.iter() {
+                    let node = match self.store.get_node(&link.target) {
+                        Some(node) => node,
+                        None => return Err(WoodError::new(WoodErrorKind::UnknownDependency(link.target.clone()), &link.json_path)),
+                    };
+                    if Rc::ptr_eq(&node, &parent_node) || reaches(&node, &parent_node) {
+                        return Err(WoodError::new(WoodErrorKind::Cycle(link.target.clone()), &link.json_path));
+                    }
+                }
+
+                for node in self.check_shared(&parser::collect_shared(raw, Some(name), "$")?)?.iter() {
+                    if Rc::ptr_eq(node, &parent_node) || reaches(node, &parent_node) {
+                        return Err(WoodError::new(WoodErrorKind::Cycle(path.clone()), &path));
+                    }
+                }
+
+                let mut store = self.store.clone();
+                let (node, shared) = store.declare_node(raw, name.clone());
+                let node_path = GodsnodePath::parse(&path);
+                store.update_index(&path, node.borrow().id);
+                let mut links = Vec::new();
+                if !shared {
+                    GodswoodProto::parse_links(&node, raw, &mut links);
+                    if let Some(children) = raw["children"].as_object() {
+                        GodswoodProto::parse_children(&node, children, &node_path, &mut store, &mut links);
+                    }
+                }
+                GodswoodProto::link_nodes(&links, &store);
+                parent_node.borrow_mut().add_child(Rc::downgrade(&node));
+                node.borrow_mut().add_parent(Rc::downgrade(&parent_node));
+            },
+            GodswoodPatch::Remove { path } => {
+                let node = self.find_node(path)?;
+                check_not_root(&node, &root, path)?;
+                let parent = path.rsplit_once('.').map_or(path.as_str(), |(parent, _)| parent);
+                let parent_node = self.find_node(parent)?;
+                unlink(&parent_node, &node);
+                if node.borrow().get_parents().is_empty() {
+                    self.store.remove_subtree(&node);
+                }
+            },
+            GodswoodPatch::Rename { path, name } => {
+                let node = self.find_node(path)?;
+                check_not_root(&node, &root, path)?;
+                check_name(name, path)?;
+                for parent in node.borrow().get_parents().iter().filter_map(|parent| parent.upgrade()) {
+                    match find_child(&parent, name) {
+                        Some(kid) if !Rc::ptr_eq(&kid, &node) => {
+                            return Err(WoodError::new(WoodErrorKind::DuplicateName(name.clone()), path));
+                        },
+                        _ => {},
+                    }
+                }
+                node.borrow_mut().name = name.clone();
+            },
+            GodswoodPatch::Reparent { path, parent } => {
+                let node = self.find_node(path)?;
+                check_not_root(&node, &root, path)?;
+                if GodswoodMeta::parse_app_name(parent) != Some(app.clone()) {
+                    return Err(WoodError::new(WoodErrorKind::UnknownNode(parent.clone()), parent));
+                }
+                let parent_node = self.find_node(parent)?;
+                if Rc::ptr_eq(&node, &parent_node) || reaches(&node, &parent_node) {
+                    return Err(WoodError::new(WoodErrorKind::Cycle(parent.clone()), path));
+                }
+                let name = node.borrow().name.clone();
+                match find_child(&parent_node, &name) {
+                    Some(kid) if !Rc::ptr_eq(&kid, &node) => {
+                        return Err(WoodError::new(WoodErrorKind::DuplicateName(name), parent));
+                    },
+                    _ => {},
+                }
+                let declared = node.borrow().primary_parent(&app);
+                if let Some(declared) = declared {
+                    unlink(&declared, &node);
+                }
+                if find_child(&parent_node, &name).is_none() {
+                    parent_node.borrow_mut().add_child(Rc::downgrade(&node));
+                    node.borrow_mut().add_parent(Rc::downgrade(&parent_node));
+                }
+            },
+            GodswoodPatch::Update { path, raw } => {
+                let node = self.find_node(path)?;
+                if raw["id"].as_str() != node.borrow().global_id.as_deref() {
+                    return Err(WoodError::new(WoodErrorKind::InvalidField("id".to_string()), path));
+                }
+                let mut details = raw.clone();
+                if let Some(details) = details.as_object_mut() {
+                    details.remove("children");
+                }
+                let linked = linked_children(&node, &app);
+                let mut targets: Vec<Rc<Godsnode>> = Vec::new();
+                for link in parser::validate_tree(&details, path)?.iter() {
+                    let target = match self.store.get_node(&link.target) {
+                        Some(target) => target,
+                        None => return Err(WoodError::new(WoodErrorKind::UnknownDependency(link.target.clone()), &link.json_path)),
+                    };
+                    if Rc::ptr_eq(&target, &node) || reaches(&target, &node) {
+                        return Err(WoodError::new(WoodErrorKind::Cycle(link.target.clone()), &link.json_path));
+                    }
+                    if targets.iter().any(|linked| Rc::ptr_eq(linked, &target)) {
+                        continue;
+                    }
+                    // Links dropped by the update free their names
+                    let name = target.borrow().name.clone();
+                    let clash = find_child(&node, &name).filter(|kid| !linked.iter().any(|linked| Rc::ptr_eq(linked, kid)))
+                        .or_else(|| targets.iter().find(|linked| linked.borrow().name == name).cloned());
+                    match clash {
+                        Some(kid) if !Rc::ptr_eq(&kid, &target) => {
+                            return Err(WoodError::new(WoodErrorKind::DuplicateName(name), &link.json_path));
+                        },
+                        _ => {},
+                    }
+                    targets.push(target);
+                }
+
+                for kid in linked.iter() {
+                    unlink(&node, kid);
+                    // Nodes only reached through the link would be left behind in the store
+                    if kid.borrow().get_parents().is_empty() && !targets.iter().any(|target| Rc::ptr_eq(target, kid)) {
+                        self.store.remove_subtree(kid);
+                    }
+                }
+                node.borrow_mut().set_details(raw);
+                for target in targets.iter() {
+                    node.borrow_mut().add_child(Rc::downgrade(target));
+                    target.borrow_mut().add_parent(Rc::downgrade(&node));
+                }
+            },
+        }
+
+        // Paths are rebuilt from the patched links, in every wood as shared nodes reach across woods
+        for (name, wood) in self.woods.borrow().iter() {
+            self.store.remove_index(&format!(".{}", name));
+            wood.borrow_mut().refresh();
+        }
+        // Nodes detached from the wood but kept by other woods or links leave it
+        if let Some(root) = root {
+            let kept = GodswoodProto::collect_nodes(&root);
+            for (_, node) in nodes.iter().filter(|(id, node)| !kept.get(id).is_some_and(|kept| Rc::ptr_eq(kept, node))) {
+                node.borrow_mut().app_meta_map.remove(&app);
+            }
+        }
+        self.mark_changed();
+        Ok(())
+    }
+
+    fn find_node(&self, path: &str) -> Result<Rc<Godsnode>, WoodError> {
+        match self.store.get_node(path) {
+            Some(node) => Ok(node),
+            None => Err(WoodError::new(WoodErrorKind::UnknownNode(path.to_string()), path)),
+        }
+    }
+}
+
+fn check_name(name: &str, path: &str) -> Result<(), WoodError> {
+    if name.is_empty() {
+        return Err(WoodError::new(WoodErrorKind::EmptyName, path));
+    }
+    if !parser::is_valid_name(name) {
+        return Err(WoodError::new(WoodErrorKind::IllegalName(name.to_string()), path));
+    }
+    Ok(())
+}
+
+fn check_not_root(node: &Rc<Godsnode>, root: &Option<Rc<Godsnode>>, path: &str) -> Result<(), WoodError> {
+    match root {
+        Some(root) if Rc::ptr_eq(node, root) => Err(WoodError::new(WoodErrorKind::RootNode, path)),
+        _ => Ok(()),
+    }
+}
+
+fn find_child(node: &Rc<Godsnode>, name: &String) -> Option<Rc<Godsnode>> {
+    node.borrow().get_children().iter()
+        .filter_map(|kid| kid.upgrade())
+        .find(|kid| kid.borrow().name == *name)
+}
+
+// Children linked to the node through `depends_on` rather than declared under it
+pub fn linked_children(node: &Rc<Godsnode>, app: &String) -> Vec<Rc<Godsnode>> {
+    node.borrow().get_children().iter().filter_map(|kid| kid.upgrade()).filter(|kid| {
+        !kid.borrow().primary_parent(app).is_some_and(|parent| Rc::ptr_eq(&parent, node))
+    }).collect()
+}
+
+fn unlink(parent: &Rc<Godsnode>, kid: &Rc<Godsnode>) {
+    parent.borrow_mut().remove_child(kid);
+    kid.borrow_mut().remove_parent(parent);
+}
+
+// Whether `to` is a descendant of `from`
+fn reaches(from: &Rc<Godsnode>, to: &Rc<Godsnode>) -> bool {
+    let mut visited = HashSet::new();
+    let mut tasks: VecDeque<Rc<Godsnode>> = VecDeque::new();
+    tasks.push_back(from.clone());
+    while let Some(node) = tasks.pop_front() {
+        let node = node.borrow();
+        if !visited.insert(node.id) {
+            continue;
+        }
+        for kid in node.get_children().iter().filter_map(|kid| kid.upgrade()) {
+            if Rc::ptr_eq(&kid, to) {
+                return true;
+            }
+            tasks.push_back(kid);
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // node3 is declared under node1 and linked under node2
+    fn woods() -> Godswoods {
+        let mut woods = Godswoods::new();
+        woods.add_wood(&json!({
+            "name": "app",
+            "children": {
+                "node1": { "children": { "node3": {} } },
+                "node2": { "depends_on": [".app.node1.node3"] },
+            },
+        })).unwrap();
+        woods.take_changed();
+        woods
+    }
+
+    // db is declared with its disk by shop and shared by blog
+    fn shared_woods() -> Godswoods {
+        let mut woods = woods();
+        woods.add_wood(&json!({
+            "name": "shop",
+            "children": { "db": { "id": "db", "children": { "disk": {} } }, "api": {} },
+        })).unwrap();
+        woods.add_wood(&json!({ "name": "blog", "children": { "db": { "id": "db" } } })).unwrap();
+        woods
+    }
+
+    fn node(woods: &Godswoods, path: &str) -> Option<Rc<Godsnode>> {
+        woods.store.get_node(path)
+    }
+
+    fn patch(woods: &mut Godswoods, raw: Value) -> Result<(), WoodError> {
+        woods.patch(&GodswoodPatch::from_json(&raw)?)
+    }
+
+    fn reject(raw: Value) -> WoodErrorKind {
+        let mut woods = woods();
+        let revision = woods.revision();
+        let kind = patch(&mut woods, raw).unwrap_err().kind;
+        assert_eq!(woods.revision(), revision);
+        assert!(woods.store.take_removed().is_empty());
+        kind
+    }
+
+    #[test]
+    fn adds_subtrees() {
+        let mut woods = woods();
+        patch(&mut woods, json!({
+            "op": "add", "parent": ".app.node1", "name": "node4",
+            "node": { "class": "cache", "children": { "node5": {} } },
+        })).unwrap();
+        assert!(woods.take_changed());
+        let added = node(&woods, ".app.node1.node4").unwrap();
+        assert_eq!(added.borrow().service_type, GodsnodeClass::Cache);
+        assert!(node(&woods, ".app.node1.node4.node5").is_some());
+        assert!(node(&woods, ".app.node1.node3").is_some());
+    }
+
+    #[test]
+    fn links_dependencies() {
+        let mut woods = woods();
+        let node1 = node(&woods, ".app.node1").unwrap();
+        patch(&mut woods, json!({
+            "op": "add", "parent": ".app.node2", "name": "node6", "node": { "depends_on": [".app.node1"] },
+        })).unwrap();
+        let linked = node(&woods, ".app.node2.node6.node1").unwrap();
+        assert!(Rc::ptr_eq(&linked, &node1));
+        assert!(Rc::ptr_eq(&node(&woods, ".app.node1").unwrap(), &node1));
+        assert_eq!(node1.borrow().get_parents().len(), 2);
+    }
+
+    #[test]
+    fn removes_subtrees() {
+        let mut woods = woods();
+        let id = node(&woods, ".app.node1").unwrap().borrow().id;
+        let node3 = node(&woods, ".app.node1.node3").unwrap();
+        patch(&mut woods, json!({ "op": "remove", "path": ".app.node1" })).unwrap();
+        assert!(node(&woods, ".app.node1").is_none());
+        assert!(node(&woods, ".app.node1.node3").is_none());
+        // Still linked under node2
+        assert!(Rc::ptr_eq(&node(&woods, ".app.node2.node3").unwrap(), &node3));
+        assert_eq!(woods.store.take_removed(), vec![id]);
+    }
+
+    #[test]
+    fn removes_shared_nodes_from_one_wood() {
+        let mut woods = shared_woods();
+        let db = node(&woods, ".shop.db").unwrap();
+        patch(&mut woods, json!({ "op": "remove", "path": ".blog.db" })).unwrap();
+        assert!(node(&woods, ".blog.db").is_none());
+        assert!(Rc::ptr_eq(&node(&woods, ".shop.db").unwrap(), &db));
+        assert!(node(&woods, ".shop.db.disk").is_some());
+        assert_eq!(db.borrow().get_path(), Some(".shop.db".to_string()));
+        assert!(woods.store.take_removed().is_empty());
+
+        // Links only drop the link
+        patch(&mut woods, json!({ "op": "remove", "path": ".app.node2.node3" })).unwrap();
+        assert!(node(&woods, ".app.node2.node3").is_none());
+        assert!(node(&woods, ".app.node1.node3").is_some());
+        assert!(woods.store.take_removed().is_empty());
+
+        patch(&mut woods, json!({ "op": "remove", "path": ".shop.db" })).unwrap();
+        assert_eq!(woods.store.take_removed().len(), 2);
+    }
+
+    #[test]
+    fn moves_shared_nodes_in_one_wood() {
+        let mut woods = shared_woods();
+        let db = node(&woods, ".shop.db").unwrap();
+        patch(&mut woods, json!({ "op": "move", "path": ".shop.db", "parent": ".shop.api" })).unwrap();
+        assert!(node(&woods, ".shop.db").is_none());
+        assert!(Rc::ptr_eq(&node(&woods, ".shop.api.db").unwrap(), &db));
+        assert!(Rc::ptr_eq(&node(&woods, ".blog.db").unwrap(), &db));
+        assert!(node(&woods, ".blog.db.disk").is_some());
+
+        let node3 = node(&woods, ".app.node1.node3").unwrap();
+        patch(&mut woods, json!({ "op": "move", "path": ".app.node2.node3", "parent": ".app" })).unwrap();
+        assert!(node(&woods, ".app.node1.node3").is_none());
+        assert!(Rc::ptr_eq(&node(&woods, ".app.node3").unwrap(), &node3));
+        assert!(Rc::ptr_eq(&node(&woods, ".app.node2.node3").unwrap(), &node3));
+    }
+
+    #[test]
+    fn moves_and_renames_nodes() {
+        let mut woods = woods();
+        let node2 = node(&woods, ".app.node2").unwrap();
+        patch(&mut woods, json!({ "op": "move", "path": ".app.node2", "parent": ".app.node1" })).unwrap();
+        assert!(node(&woods, ".app.node2").is_none());
+        assert!(Rc::ptr_eq(&node(&woods, ".app.node1.node2").unwrap(), &node2));
+        assert!(node(&woods, ".app.node1.node2.node3").is_some());
+
+        patch(&mut woods, json!({ "op": "rename", "path": ".app.node1.node2", "name": "node7" })).unwrap();
+        assert!(node(&woods, ".app.node1.node2").is_none());
+        assert!(Rc::ptr_eq(&node(&woods, ".app.node1.node7").unwrap(), &node2));
+        assert!(woods.store.take_removed().is_empty());
+    }
+
+    #[test]
+    fn updates_nodes() {
+        let mut woods = woods();
+        let node2 = node(&woods, ".app.node2").unwrap();
+        patch(&mut woods, json!({
+            "op": "update", "path": ".app.node2",
+            "node": { "class": "cache", "tags": ["hot"], "depends_on": [".app.node1"] },
+        })).unwrap();
+        assert!(Rc::ptr_eq(&node(&woods, ".app.node2").unwrap(), &node2));
+        assert_eq!(node2.borrow().service_type, GodsnodeClass::Cache);
+        assert!(node2.borrow().has_tag("hot"));
+        assert!(node(&woods, ".app.node2.node3").is_none());
+        assert!(node(&woods, ".app.node2.node1.node3").is_some());
+        assert!(woods.store.take_removed().is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_patches() {
+        assert_eq!(reject(json!({ "op": "copy", "path": ".app.node1" })), WoodErrorKind::InvalidField("op".to_string()));
+        assert_eq!(reject(json!({ "op": "remove" })), WoodErrorKind::InvalidField("path".to_string()));
+        assert_eq!(reject(json!({ "op": "remove", "path": ".app.node4" })), WoodErrorKind::UnknownNode(".app.node4".to_string()));
+        assert_eq!(reject(json!({ "op": "remove", "path": ".shop.node1" })), WoodErrorKind::UnknownNode(".shop.node1".to_string()));
+        assert_eq!(reject(json!({ "op": "remove", "path": ".app" })), WoodErrorKind::RootNode);
+        assert_eq!(reject(json!({ "op": "rename", "path": ".app", "name": "shop" })), WoodErrorKind::RootNode);
+        assert_eq!(reject(json!({ "op": "rename", "path": ".app.node2", "name": "node1" })), WoodErrorKind::DuplicateName("node1".to_string()));
+        assert_eq!(reject(json!({ "op": "rename", "path": ".app.node2", "name": "node.4" })), WoodErrorKind::IllegalName("node.4".to_string()));
+        assert_eq!(reject(json!({ "op": "add", "parent": ".app", "name": "node1" })), WoodErrorKind::DuplicateName("node1".to_string()));
+        assert_eq!(reject(json!({ "op": "add", "parent": ".app", "name": "" })), WoodErrorKind::EmptyName);
+        assert_eq!(
+            reject(json!({ "op": "add", "parent": ".app", "name": "node4", "node": { "depends_on": [".app.node5"] } })),
+            WoodErrorKind::UnknownDependency(".app.node5".to_string()));
+        assert_eq!(
+            reject(json!({ "op": "update", "path": ".app.node2", "node": { "id": "node2" } })),
+            WoodErrorKind::InvalidField("id".to_string()));
+        assert_eq!(
+            reject(json!({ "op": "update", "path": ".app.node2", "node": { "depends_on": [".app.node5"] } })),
+            WoodErrorKind::UnknownDependency(".app.node5".to_string()));
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        assert_eq!(
+            reject(json!({ "op": "move", "path": ".app.node1", "parent": ".app.node1.node3" })),
+            WoodErrorKind::Cycle(".app.node1.node3".to_string()));
+        assert_eq!(
+            reject(json!({ "op": "move", "path": ".app.node1", "parent": ".app.node1" })),
+            WoodErrorKind::Cycle(".app.node1".to_string()));
+        assert_eq!(
+            reject(json!({ "op": "add", "parent": ".app.node1.node3", "name": "node4", "node": { "depends_on": [".app.node1"] } })),
+            WoodErrorKind::Cycle(".app.node1".to_string()));
+        assert_eq!(
+            reject(json!({ "op": "update", "path": ".app.node1.node3", "node": { "depends_on": [".app.node1"] } })),
+            WoodErrorKind::Cycle(".app.node1".to_string()));
+    }
+}
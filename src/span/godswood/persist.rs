@@ -0,0 +1,226 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use serde_json::{json, Value};
+use dragon::{ecs::{WorldState, TransformComponent}, core};
+use crate::utils::JsonParser;
+
+// Snapshots are kept under a single key, bump the version whenever the layout below changes
+const SNAPSHOT_KEY: &str = "maester.godswood";
+const SCHEMA_VERSION: u64 = 1;
+
+// User tunables, saved with the snapshot
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Preferences {
+    pub turn_speed: f32,
+    pub move_speed: f32,
+}
+
+pub type SharedPreferences = Rc<RefCell<Preferences>>;
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            turn_speed: 0.1,
+            move_speed: 0.6,
+        }
+    }
+}
+
+impl Preferences {
+    // Speeds must be positive, none otherwise
+    pub fn new(turn_speed: f32, move_speed: f32) -> Option<Self> {
+        if turn_speed > 0. && turn_speed.is_finite() && move_speed > 0. && move_speed.is_finite() {
+            Some(Self { turn_speed, move_speed })
+        } else {
+            None
+        }
+    }
+
+    pub fn to_json(self) -> Value {
+        json!({
+            "turn_speed": self.turn_speed,
+            "move_speed": self.move_speed,
+        })
+    }
+
+    pub fn from_json(raw: &Value) -> Self {
+        let default = Preferences::default();
+        Self {
+            turn_speed: raw.get_f64("turn_speed", default.turn_speed as f64) as f32,
+            move_speed: raw.get_f64("move_speed", default.move_speed as f64) as f32,
+        }
+    }
+}
+
+// Camera translation and rotation as euler angles (roll, pitch, yaw)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraPose {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 3],
+}
+
+impl CameraPose {
+    fn to_json(self) -> Value {
+        json!({
+            "translation": self.translation,
+            "rotation": self.rotation,
+        })
+    }
+
+    fn from_json(raw: &Value) -> Option<Self> {
+        Some(Self {
+            translation: read_vector(&raw["translation"])?,
+            rotation: read_vector(&raw["rotation"])?,
+        })
+    }
+}
+
+fn read_vector(raw: &Value) -> Option<[f32; 3]> {
+    let items = raw.as_array()?;
+    if items.len() != 3 {
+        return None;
+    }
+    let mut vector = [0f32; 3];
+    for (i, item) in items.iter().enumerate() {
+        vector[i] = item.as_f64()? as f32;
+    }
+    Some(vector)
+}
+
+// Everything restored on page load, woods are kept as canonical wood JSON
+pub struct Snapshot {
+    pub woods: Vec<Value>,
+    pub camera: Option<CameraPose>,
+    pub preferences: Preferences,
+}
+
+impl Snapshot {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "version": SCHEMA_VERSION,
+            "woods": self.woods,
+            "camera": self.camera.map(|camera| camera.to_json()),
+            "preferences": self.preferences.to_json(),
+        })
+    }
+
+    pub fn from_json(raw: &Value) -> Result<Self, String> {
+        match raw["version"].as_u64() {
+            Some(SCHEMA_VERSION) => {},
+            Some(version) => return Err(format!("unsupported schema version {}", version)),
+            None => return Err("missing schema version".to_string()),
+        }
+        let woods = match raw["woods"].as_array() {
+            Some(woods) if woods.iter().all(|wood| wood.is_object()) => woods.clone(),
+            _ => return Err("bad woods".to_string()),
+        };
+        let camera = match &raw["camera"] {
+            Value::Null => None,
+            camera => Some(CameraPose::from_json(camera).ok_or_else(|| "bad camera".to_string())?),
+        };
+        Ok(Self {
+            woods,
+            camera,
+            preferences: Preferences::from_json(&raw["preferences"]),
+        })
+    }
+}
+
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+// Saved snapshot if there is a readable one, corrupt snapshots are dropped for a clean start
+pub fn load() -> Option<Snapshot> {
+    let storage = local_storage()?;
+    let raw = storage.get_item(SNAPSHOT_KEY).ok()??;
+    let snapshot = serde_json::from_str::<Value>(&raw)
+        .map_err(|err| err.to_string())
+        .and_then(|raw| Snapshot::from_json(&raw));
+    match snapshot {
+        Ok(snapshot) => Some(snapshot),
+        Err(err) => {
+            log!("Dropped corrupt snapshot: {}", err);
+            clear();
+            None
+        },
+    }
+}
+
+pub fn save(snapshot: &Snapshot) {
+    if let Some(storage) = local_storage() {
+        if storage.set_item(SNAPSHOT_KEY, &snapshot.to_json().to_string()).is_err() {
+            log!("Failed to save snapshot");
+        }
+    }
+}
+
+pub fn clear() {
+    if let Some(storage) = local_storage() {
+        let _ = storage.remove_item(SNAPSHOT_KEY);
+    }
+}
+
+// Pose of the active camera
+pub fn read_camera(state: &WorldState) -> Option<CameraPose> {
+    let c_store = state.component_store.borrow();
+    let transforms = c_store.get::<TransformComponent>();
+    let camera = transforms.get(&state.active_camera.get())?;
+    let translation = camera.translation();
+    let (roll, pitch, yaw) = camera.rotation().euler_angles();
+    Some(CameraPose {
+        translation: [translation.x, translation.y, translation.z],
+        rotation: [roll, pitch, yaw],
+    })
+}
+
+pub fn apply_camera(state: &WorldState, pose: &CameraPose) {
+    let c_store = state.component_store.borrow();
+    let mut transforms = c_store.get_mut::<TransformComponent>();
+    if let Some(camera) = transforms.get_mut(&state.active_camera.get()) {
+        let [x, y, z] = pose.translation;
+        let [roll, pitch, yaw] = pose.rotation;
+        camera.set_translation_xyz(x, y, z);
+        camera.set_rotation(core::UnitQuaternion::from_euler_angles(roll, pitch, yaw));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_preferences() {
+        assert_eq!(Preferences::new(0.2, 1.), Some(Preferences { turn_speed: 0.2, move_speed: 1. }));
+        assert_eq!(Preferences::new(0., 1.), None);
+        assert_eq!(Preferences::new(0.2, -1.), None);
+        assert_eq!(Preferences::new(f32::NAN, 1.), None);
+        assert_eq!(Preferences::from_json(&json!({ "move_speed": 2. })), Preferences { move_speed: 2., ..Preferences::default() });
+    }
+
+    #[test]
+    fn restores_snapshots() {
+        let snapshot = Snapshot {
+            woods: vec![json!({ "name": "app" })],
+            camera: Some(CameraPose { translation: [1., 2., 3.], rotation: [0., 0.5, 0.25] }),
+            preferences: Preferences { turn_speed: 0.25, move_speed: 1.5 },
+        };
+        let restored = Snapshot::from_json(&snapshot.to_json()).unwrap();
+        assert_eq!(restored.woods, snapshot.woods);
+        assert_eq!(restored.camera, snapshot.camera);
+        assert_eq!(restored.preferences, snapshot.preferences);
+
+        let restored = Snapshot::from_json(&json!({ "version": SCHEMA_VERSION, "woods": [], "preferences": {} })).unwrap();
+        assert_eq!(restored.camera, None);
+        assert_eq!(restored.preferences, Preferences::default());
+    }
+
+    #[test]
+    fn rejects_bad_snapshots() {
+        let reject = |raw: Value| Snapshot::from_json(&raw).err().unwrap();
+        assert_eq!(reject(json!({ "woods": [] })), "missing schema version");
+        assert_eq!(reject(json!({ "version": SCHEMA_VERSION + 1, "woods": [] })), format!("unsupported schema version {}", SCHEMA_VERSION + 1));
+        assert_eq!(reject(json!({ "version": SCHEMA_VERSION, "woods": ["app"] })), "bad woods");
+        assert_eq!(reject(json!({ "version": SCHEMA_VERSION, "woods": [], "camera": { "translation": [1, 2] } })), "bad camera");
+    }
+}
@@ -0,0 +1,74 @@
+use dragon::core::{Point3, Vector3, UnitQuaternion};
+use std::f32::consts::PI;
+
+// Vertical field of view of the world camera
+pub const FOV: f32 = PI / 4.;
+
+// Half line from `origin` along the unit vector `dir`
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub dir: Vector3<f32>,
+}
+
+impl Ray {
+    // Ray from a camera at `translation` looking down its local -z axis, through the point at `x`
+    // and `y` in normalized device coordinates
+    pub fn from_camera(translation: &Vector3<f32>, rotation: &UnitQuaternion<f32>, x: f32, y: f32, aspect: f32) -> Self {
+        let scale = (FOV / 2.).tan();
+        let local = Vector3::new(x * scale * aspect, y * scale, -1.);
+        Self {
+            origin: Point3::from(*translation),
+            dir: (rotation * local).normalize(),
+        }
+    }
+
+    // Distance along the ray to the nearest hit of the sphere in front of the origin
+    pub fn intersect_sphere(&self, center: &Point3<f32>, radius: f32) -> Option<f32> {
+        let to_center = center - self.origin;
+        let along = to_center.dot(&self.dir);
+        let off_sq = to_center.norm_squared() - along * along;
+        let radius_sq = radius * radius;
+        if off_sq > radius_sq {
+            return None;
+        }
+        let half_chord = (radius_sq - off_sq).sqrt();
+        // Origin inside the sphere hits its far side
+        let near = along - half_chord;
+        let distance = if near >= 0. { near } else { along + half_chord };
+        if distance < 0. { None } else { Some(distance) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(x: f32, y: f32) -> Ray {
+        Ray::from_camera(&Vector3::new(0., 0., 10.), &UnitQuaternion::identity(), x, y, 2.)
+    }
+
+    #[test]
+    fn casts_from_camera() {
+        let center = ray(0., 0.);
+        assert!((center.dir - Vector3::new(0., 0., -1.)).norm() < 1e-6);
+        // Corners of the view are half the field of view away vertically
+        let corner = ray(1., 1.);
+        assert!((corner.dir.y / -corner.dir.z - (FOV / 2.).tan()).abs() < 1e-6);
+        assert!((corner.dir.x / corner.dir.y - 2.).abs() < 1e-6);
+
+        let turned = Ray::from_camera(&Vector3::zeros(), &UnitQuaternion::from_euler_angles(0., PI / 2., 0.), 0., 0., 1.);
+        assert!((turned.dir - Vector3::new(-1., 0., 0.)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn hits_spheres() {
+        let center = ray(0., 0.);
+        assert_eq!(center.intersect_sphere(&Point3::new(0., 0., 0.), 2.), Some(8.));
+        assert_eq!(center.intersect_sphere(&Point3::new(0., 3., 0.), 2.), None);
+        // Behind the camera
+        assert_eq!(center.intersect_sphere(&Point3::new(0., 0., 20.), 2.), None);
+        // From inside
+        assert_eq!(center.intersect_sphere(&Point3::new(0., 0., 9.), 2.), Some(3.));
+    }
+}
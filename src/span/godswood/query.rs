@@ -0,0 +1,302 @@
+use std::rc::{Rc, Weak};
+use std::collections::HashSet;
+use crate::span::godswood::node::*;
+
+#[derive(Clone, Debug, PartialEq)]
+enum QuerySegment {
+    // `**`, any number of segments including none
+    AnyDepth,
+    // Single segment, `*` and `?` match any run of characters and any single character
+    Glob(String),
+}
+
+// Compiled glob pattern over node paths
+//
+// .sample-application.*.service10     service10 two levels below the app
+// .**.db-*                            nodes named like db-* at any depth
+// ..service5                          short for .**.service5
+#[derive(Clone, Debug)]
+pub struct GodsnodeQuery {
+    segments: Vec<QuerySegment>,
+}
+
+impl GodsnodeQuery {
+    pub fn parse(pattern: &str) -> Option<GodsnodeQuery> {
+        if !pattern.starts_with('.') || pattern.len() < 2 {
+            return None;
+        }
+        let mut segments = Vec::new();
+        for token in pattern[1..].split('.') {
+            let segment = match token {
+                "" | "**" => QuerySegment::AnyDepth,
+                _ => QuerySegment::Glob(token.to_string()),
+            };
+            // Collapse repeated `**`, they match the same paths
+            if segment == QuerySegment::AnyDepth && segments.last() == Some(&QuerySegment::AnyDepth) {
+                continue;
+            }
+            segments.push(segment);
+        }
+        Some(GodsnodeQuery { segments })
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        if !path.starts_with('.') {
+            return false;
+        }
+        let tokens: Vec<&str> = path[1..].split('.').collect();
+        GodsnodeQuery::match_segments(&self.segments, &tokens)
+    }
+
+    fn match_segments(segments: &[QuerySegment], tokens: &[&str]) -> bool {
+        match segments.first() {
+            None => tokens.is_empty(),
+            Some(QuerySegment::AnyDepth) => {
+                (0..tokens.len() + 1).any(|skip| GodsnodeQuery::match_segments(&segments[1..], &tokens[skip..]))
+            },
+            Some(QuerySegment::Glob(glob)) => {
+                !tokens.is_empty() && match_glob(glob.as_bytes(), tokens[0].as_bytes()) &&
+                    GodsnodeQuery::match_segments(&segments[1..], &tokens[1..])
+            },
+        }
+    }
+}
+
+fn match_glob(glob: &[u8], text: &[u8]) -> bool {
+    let (mut g, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if g < glob.len() && (glob[g] == b'?' || glob[g] == text[t]) {
+            g += 1;
+            t += 1;
+        } else if g < glob.len() && glob[g] == b'*' {
+            star = Some((g, t));
+            g += 1;
+        } else if let Some((star_g, star_t)) = star {
+            g = star_g + 1;
+            t = star_t + 1;
+            star = Some((star_g, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    glob[g..].iter().all(|c| *c == b'*')
+}
+
+// Filter over node details, parsed from space separated terms
+//
+// #critical              nodes tagged critical
+// owner=team-*           attribute matching the glob
+// payments               name, display name or any attribute value containing the text
+#[derive(Clone, Debug, Default)]
+pub struct GodsnodeFilter {
+    pub tags: Vec<String>,
+    pub attributes: Vec<(String, String)>,
+    pub texts: Vec<String>,
+}
+
+impl GodsnodeFilter {
+    pub fn parse(filter: &str) -> GodsnodeFilter {
+        let mut parsed = GodsnodeFilter::default();
+        for term in filter.split_whitespace() {
+            if term.starts_with('#') && term.len() > 1 {
+                parsed.tags.push(term[1..].to_string());
+            } else if let Some(pos) = term.find('=') {
+                parsed.attributes.push((term[..pos].to_string(), term[pos + 1..].to_string()));
+            } else {
+                parsed.texts.push(term.to_lowercase());
+            }
+        }
+        parsed
+    }
+
+    pub fn matches(&self, node: &GodsnodeProto) -> bool {
+        self.tags.iter().all(|tag| node.has_tag(tag)) &&
+        self.attributes.iter().all(|(key, glob)| {
+            node.get_attribute(key).is_some_and(|value| match_glob(glob.as_bytes(), value.as_bytes()))
+        }) &&
+        self.texts.iter().all(|text| {
+            node.name.to_lowercase().contains(text) ||
+            node.display_name.to_lowercase().contains(text) ||
+            node.attributes.values().any(|value| value.to_lowercase().contains(text))
+        })
+    }
+}
+
+pub trait StoreQuery {
+    fn query(&self, query: &GodsnodeQuery) -> Vec<Weak<Godsnode>>;
+    fn query_paths(&self, query: &GodsnodeQuery) -> Vec<(String, Weak<Godsnode>)>;
+    fn search(&self, query: &GodsnodeQuery, filter: &GodsnodeFilter) -> Vec<Weak<Godsnode>>;
+}
+
+impl StoreQuery for Rc<Store> {
+    // Matching nodes ordered by path, a node reachable through several matching paths is listed once
+    fn query(&self, query: &GodsnodeQuery) -> Vec<Weak<Godsnode>> {
+        let mut ids = HashSet::new();
+        self.query_paths(query).into_iter().filter(|(_, node)| {
+            match node.upgrade() {
+                Some(node) => ids.insert(node.borrow().id),
+                None => false,
+            }
+        }).map(|(_, node)| node).collect()
+    }
+
+    // Nodes matching both the path pattern and the filter
+    fn search(&self, query: &GodsnodeQuery, filter: &GodsnodeFilter) -> Vec<Weak<Godsnode>> {
+        self.query(query).into_iter().filter(|node| {
+            node.upgrade().is_some_and(|node| filter.matches(&node.borrow()))
+        }).collect()
+    }
+
+    // Every matching path with its node, ordered by path
+    fn query_paths(&self, query: &GodsnodeQuery) -> Vec<(String, Weak<Godsnode>)> {
+        let mut paths = self.read_paths(|path| query.matches(path));
+        paths.sort_by(|a, b| a.0.cmp(&b.0));
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::span::godswood::tree::Godswoods;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        GodsnodeQuery::parse(pattern).unwrap().matches(path)
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(match_glob(b"db-*", b"db-main"));
+        assert!(match_glob(b"db-*", b"db-"));
+        assert!(!match_glob(b"db-*", b"cache"));
+        assert!(match_glob(b"*", b""));
+        assert!(match_glob(b"?b", b"ab"));
+        assert!(!match_glob(b"?b", b"b"));
+        assert!(match_glob(b"a*b*c", b"axxbyybc"));
+        assert!(!match_glob(b"a*b*c", b"axxbyyb"));
+        assert!(match_glob(b"", b""));
+        assert!(!match_glob(b"", b"a"));
+    }
+
+    #[test]
+    fn rejects_bad_patterns() {
+        assert!(GodsnodeQuery::parse("").is_none());
+        assert!(GodsnodeQuery::parse(".").is_none());
+        assert!(GodsnodeQuery::parse("app.node1").is_none());
+    }
+
+    #[test]
+    fn matches_single_segments() {
+        assert!(matches(".app.*", ".app.node1"));
+        assert!(!matches(".app.*", ".app"));
+        assert!(!matches(".app.*", ".app.node1.node3"));
+        assert!(matches(".app.*.node3", ".app.node1.node3"));
+        assert!(!matches(".app.*", "app.node1"));
+    }
+
+    #[test]
+    fn matches_any_depth() {
+        assert!(matches(".app.**", ".app"));
+        assert!(matches(".app.**", ".app.node1.node3"));
+        assert!(!matches(".app.**", ".shop.node1"));
+        assert!(matches(".**.node3", ".app.node1.node3"));
+        assert!(matches(".app.**.**.node3", ".app.node3"));
+        assert!(matches("..node3", ".node3"));
+        assert!(matches("..node3", ".app.node1.node3"));
+        assert!(!matches("..node3", ".app.node1.node30"));
+        assert!(matches(".app..node3", ".app.node2.node3"));
+    }
+
+    #[test]
+    fn matches_roots() {
+        assert!(matches(".app", ".app"));
+        assert!(!matches(".app", ".app.node1"));
+        assert!(matches(".*", ".app"));
+        assert!(!matches(".*", ".app.node1"));
+        assert!(matches(".**", ".app"));
+    }
+
+    fn proto(attributes: &[(&str, &str)], tags: &[&str]) -> GodsnodeProto {
+        let mut node = GodsnodeProto::new();
+        node.name = "payments-db".to_string();
+        node.display_name = "Payments DB".to_string();
+        node.attributes = attributes.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        node.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        node
+    }
+
+    #[test]
+    fn parses_filters() {
+        let filter = GodsnodeFilter::parse(" #critical  owner=team-*  Payments ");
+        assert_eq!(filter.tags, vec!["critical"]);
+        assert_eq!(filter.attributes, vec![("owner".to_string(), "team-*".to_string())]);
+        assert_eq!(filter.texts, vec!["payments"]);
+    }
+
+    #[test]
+    fn filters_tags() {
+        let node = proto(&[], &["critical", "edge"]);
+        assert!(node.has_tag("critical"));
+        assert!(!node.has_tag("crit"));
+        assert!(GodsnodeFilter::parse("").matches(&node));
+        assert!(GodsnodeFilter::parse("#critical #edge").matches(&node));
+        assert!(!GodsnodeFilter::parse("#critical #internal").matches(&node));
+    }
+
+    #[test]
+    fn filters_attributes() {
+        let node = proto(&[("owner", "team-pay"), ("version", "1.2")], &[]);
+        assert!(GodsnodeFilter::parse("owner=team-*").matches(&node));
+        assert!(GodsnodeFilter::parse("owner=team-pay version=1.?").matches(&node));
+        assert!(!GodsnodeFilter::parse("owner=team").matches(&node));
+        assert!(!GodsnodeFilter::parse("repo=*").matches(&node));
+        assert!(GodsnodeFilter::parse("owner=").matches(&proto(&[("owner", "")], &[])));
+    }
+
+    #[test]
+    fn filters_texts() {
+        let node = proto(&[("runbook", "https://wiki/Oncall")], &[]);
+        assert!(GodsnodeFilter::parse("PAYMENTS").matches(&node));
+        assert!(GodsnodeFilter::parse("db oncall").matches(&node));
+        assert!(!GodsnodeFilter::parse("cache").matches(&node));
+    }
+
+    #[test]
+    fn queries_nodes_once() {
+        let mut woods = Godswoods::new();
+        woods.add_wood(&json!({
+            "name": "app",
+            "children": {
+                "node1": { "children": { "node3": {} } },
+                "node2": { "depends_on": [".app.node1.node3"] },
+            },
+        })).unwrap();
+        let query = GodsnodeQuery::parse("..node3").unwrap();
+        let paths: Vec<String> = woods.store.query_paths(&query).into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, vec![".app.node1.node3", ".app.node2.node3"]);
+        assert_eq!(woods.store.query(&query).len(), 1);
+    }
+
+    #[test]
+    fn searches_nodes() {
+        let mut woods = Godswoods::new();
+        woods.add_wood(&json!({
+            "name": "app",
+            "children": {
+                "node1": { "tags": ["critical"], "children": { "node3": { "tags": ["critical"] } } },
+                "node2": { "attributes": { "owner": "team-a" } },
+            },
+        })).unwrap();
+        let paths = |pattern: &str, filter: &str| -> Vec<String> {
+            let query = GodsnodeQuery::parse(pattern).unwrap();
+            woods.store.search(&query, &GodsnodeFilter::parse(filter)).iter()
+                .filter_map(|node| node.upgrade()).filter_map(|node| node.borrow().get_path()).collect()
+        };
+        assert_eq!(paths(".app.**", "#critical"), vec![".app.node1", ".app.node1.node3"]);
+        assert_eq!(paths(".app.*", "#critical"), vec![".app.node1"]);
+        assert_eq!(paths(".**", "owner=team-*"), vec![".app.node2"]);
+    }
+}
@@ -0,0 +1,291 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use serde_json::{json, Value};
+use crate::utils::JsonParser;
+use crate::span::godswood::source::{SourceDecoder, SourceEvent};
+
+const RECORDING_VERSION: u64 = 1;
+// Oldest status and metric updates are dropped beyond this, topologies are always kept
+const MAX_ENTRIES: usize = 100_000;
+
+// Update applied to the woods at `at` ms
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordEntry {
+    pub at: u64,
+    pub event: SourceEvent,
+}
+
+// Position of a replay in the recording, `clock` is in recording time
+pub struct Replay {
+    pub clock: u64,
+    pub speed: f64,
+    pub paused: bool,
+    next: usize,
+    last_now: Option<u64>,
+    reset: bool,
+}
+
+// Time indexed log of every update, with the replay driving the woods from it when active
+pub struct RecorderProto {
+    entries: Vec<RecordEntry>,
+    topologies: HashMap<String, Value>,
+    replay: Option<Replay>,
+    catch_up: bool,
+}
+
+pub type Recorder = Rc<RefCell<RecorderProto>>;
+
+impl RecorderProto {
+    pub fn new() -> Recorder {
+        Rc::new(RefCell::new(Self {
+            entries: Vec::new(),
+            topologies: HashMap::new(),
+            replay: None,
+            catch_up: false,
+        }))
+    }
+
+    // Keep entries sorted by time, topologies are only recorded when they change
+    pub fn record(&mut self, at: u64, event: SourceEvent) {
+        match &event {
+            SourceEvent::Topology(raw) => {
+                let name = raw.get_str("name", "");
+                if self.topologies.get(&name) == Some(raw) {
+                    return;
+                }
+                self.topologies.insert(name, raw.clone());
+            },
+            // Woods sent again after a reset load anew
+            SourceEvent::Reset => self.topologies.clear(),
+            _ => {},
+        }
+        // Updates mostly come in order, look for the spot from the end
+        let index = self.entries.iter().rposition(|entry| entry.at <= at).map_or(0, |index| index + 1);
+        self.entries.insert(index, RecordEntry { at, event });
+        if let Some(replay) = self.replay.as_mut() {
+            if index < replay.next {
+                replay.next += 1;
+            }
+        }
+        self.trim();
+    }
+
+    // A running replay only loses the updates it already applied
+    fn trim(&mut self) {
+        if self.entries.len() <= MAX_ENTRIES {
+            return;
+        }
+        let applied = self.replay.as_ref().map_or(self.entries.len(), |replay| replay.next);
+        let count = self.entries.len() - MAX_ENTRIES * 9 / 10;
+        let mut drop = count;
+        let mut index = 0;
+        self.entries.retain(|entry| {
+            index += 1;
+            match entry.event {
+                SourceEvent::Topology(_) | SourceEvent::Reset => true,
+                _ if drop > 0 && index <= applied => {
+                    drop -= 1;
+                    false
+                },
+                _ => true,
+            }
+        });
+        if let Some(replay) = self.replay.as_mut() {
+            replay.next -= count - drop;
+        }
+    }
+
+    // First and last update times
+    pub fn get_range(&self) -> Option<(u64, u64)> {
+        Some((self.entries.first()?.at, self.entries.last()?.at))
+    }
+
+    pub fn get_replay(&self) -> Option<&Replay> {
+        self.replay.as_ref()
+    }
+
+    // Replay position for controls, `{"start", "end", "clock", "speed", "paused"}` with a null clock
+    // when live
+    pub fn state_json(&self) -> Value {
+        let (start, end) = self.get_range().unwrap_or((0, 0));
+        let replay = self.get_replay();
+        json!({
+            "start": start,
+            "end": end,
+            "clock": replay.map(|replay| replay.clock),
+            "speed": replay.map_or(1., |replay| replay.speed),
+            "paused": replay.is_some_and(|replay| replay.paused),
+        })
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    pub fn start_replay(&mut self, speed: f64) {
+        let clock = self.get_range().map_or(0, |(start, _)| start);
+        self.replay = Some(Replay {
+            clock,
+            speed,
+            paused: false,
+            next: 0,
+            last_now: None,
+            reset: true,
+        });
+    }
+
+    // Back to live data, the woods catch up with everything recorded
+    pub fn stop_replay(&mut self) {
+        if self.replay.take().is_some() {
+            self.catch_up = true;
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.paused = paused;
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.speed = speed.max(0.);
+        }
+    }
+
+    // Jump to the recording time, the woods are rebuilt from the start of the recording
+    pub fn seek(&mut self, at: u64) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.clock = at;
+            replay.next = 0;
+            replay.reset = true;
+        }
+    }
+
+    // Updates due since the last poll, nothing when not replaying
+    pub fn poll_replay(&mut self, now: u64) -> Vec<SourceEvent> {
+        let entries = &self.entries;
+        let replay = match self.replay.as_mut() {
+            Some(replay) => replay,
+            None => return Vec::new(),
+        };
+        let mut events = Vec::new();
+        if replay.reset {
+            replay.reset = false;
+            events.push(SourceEvent::Reset);
+        }
+        if let Some(last_now) = replay.last_now {
+            if !replay.paused {
+                replay.clock += (now.saturating_sub(last_now) as f64 * replay.speed) as u64;
+            }
+        }
+        replay.last_now = Some(now);
+        while replay.next < entries.len() && entries[replay.next].at <= replay.clock {
+            events.push(entries[replay.next].event.clone());
+            replay.next += 1;
+        }
+        events
+    }
+
+    // Everything recorded, once after a replay stopped
+    pub fn take_catch_up(&mut self) -> Option<Vec<SourceEvent>> {
+        if !self.catch_up {
+            return None;
+        }
+        self.catch_up = false;
+        let mut events = vec![SourceEvent::Reset];
+        events.extend(self.entries.iter().map(|entry| entry.event.clone()));
+        Some(events)
+    }
+
+    // Recording file content, entries are wire messages stamped with their time
+    pub fn to_json(&self) -> Value {
+        json!({
+            "version": RECORDING_VERSION,
+            "entries": self.entries.iter().map(|entry| json!({
+                "at": entry.at,
+                "message": entry.event.to_message(),
+            })).collect::<Vec<Value>>(),
+        })
+    }
+
+    // Replace the recording, a running replay starts over
+    pub fn import(&mut self, raw: &str) -> Result<(), String> {
+        let raw: Value = serde_json::from_str(raw).map_err(|err| err.to_string())?;
+        if raw["version"].as_u64() != Some(RECORDING_VERSION) {
+            return Err("unsupported recording version".to_string());
+        }
+        let mut decoder = SourceDecoder::new();
+        let mut entries = Vec::new();
+        for entry in raw["entries"].as_array().ok_or_else(|| "missing entries".to_string())?.iter() {
+            let at = entry["at"].as_u64().ok_or_else(|| "missing entry time".to_string())?;
+            let event = decoder.decode_message(&entry["message"]).ok_or_else(|| format!("bad entry at {}", at))?;
+            entries.push(RecordEntry { at, event });
+        }
+        entries.sort_by_key(|entry| entry.at);
+
+        self.entries = entries;
+        self.topologies.clear();
+        if let Some(replay) = self.replay.as_ref() {
+            let speed = replay.speed;
+            self.start_replay(speed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metric(at: u64) -> SourceEvent {
+        SourceEvent::Metric { path: ".app.node1".to_string(), metric: "cpu".to_string(), value: at as f64, at: Some(at) }
+    }
+
+    #[test]
+    fn records_changed_topologies() {
+        let recorder = RecorderProto::new();
+        let mut recorder = recorder.borrow_mut();
+        let wood = SourceEvent::Topology(json!({ "name": "app" }));
+        recorder.record(1, wood.clone());
+        recorder.record(2, wood.clone());
+        assert_eq!(recorder.entries.len(), 1);
+        recorder.record(3, SourceEvent::Reset);
+        recorder.record(4, wood.clone());
+        assert_eq!(recorder.entries.iter().map(|entry| entry.at).collect::<Vec<u64>>(), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn trims_oldest_updates() {
+        let recorder = RecorderProto::new();
+        let mut recorder = recorder.borrow_mut();
+        recorder.record(0, SourceEvent::Topology(json!({ "name": "app" })));
+        for at in 1..=MAX_ENTRIES as u64 {
+            recorder.record(at, metric(at));
+        }
+        assert_eq!(recorder.entries.len(), MAX_ENTRIES * 9 / 10);
+        assert_eq!(recorder.entries[0].at, 0);
+        assert_eq!(recorder.entries[1].at, (MAX_ENTRIES / 10 + 2) as u64);
+    }
+
+    #[test]
+    fn trims_updates_replayed() {
+        let recorder = RecorderProto::new();
+        let mut recorder = recorder.borrow_mut();
+        for at in 0..MAX_ENTRIES as u64 {
+            recorder.record(at, metric(at));
+        }
+        recorder.start_replay(1.);
+        recorder.seek(20);
+        assert_eq!(recorder.poll_replay(0).len(), 22);
+
+        recorder.record(MAX_ENTRIES as u64, metric(MAX_ENTRIES as u64));
+        assert_eq!(recorder.entries.len(), MAX_ENTRIES + 1 - 21);
+        assert_eq!(recorder.entries[0].at, 21);
+        assert_eq!(recorder.get_replay().unwrap().next, 0);
+        recorder.seek(22);
+        assert_eq!(recorder.poll_replay(0), vec![SourceEvent::Reset, metric(21), metric(22)]);
+    }
+}
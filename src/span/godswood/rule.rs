@@ -0,0 +1,477 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use serde_json::Value;
+use crate::span::godswood::node::*;
+use crate::span::godswood::query::{GodsnodeQuery, StoreQuery};
+use crate::span::godswood::status::GodsnodeHealth;
+use crate::span::godswood::alert::{Alert, AlertSeverity, AlertsProto};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RuleOp {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl RuleOp {
+    // Longest operators first, `>=` must not be read as `>`
+    const ALL: [(&'static str, RuleOp); 6] = [
+        (">=", RuleOp::Ge), ("<=", RuleOp::Le), ("==", RuleOp::Eq),
+        ("!=", RuleOp::Ne), (">", RuleOp::Gt), ("<", RuleOp::Lt),
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        RuleOp::ALL.iter().find(|(_, op)| op == self).unwrap().0
+    }
+
+    pub fn compare(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            RuleOp::Gt => value > threshold,
+            RuleOp::Ge => value >= threshold,
+            RuleOp::Lt => value < threshold,
+            RuleOp::Le => value <= threshold,
+            RuleOp::Eq => value == threshold,
+            RuleOp::Ne => value != threshold,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuleCondition {
+    // Latest sample of the metric against the threshold
+    Metric { metric: String, op: RuleOp, threshold: f64 },
+    // Effective health, only `==` and `!=`, or the health reported for the node itself so that rules
+    // on every node do not fire again on each ancestor the health rolled up to
+    Status { op: RuleOp, health: GodsnodeHealth, reported: bool },
+}
+
+impl RuleCondition {
+    fn parse(condition: &str) -> Result<RuleCondition, String> {
+        let (pos, token, op) = RuleOp::ALL.iter()
+            .filter_map(|(token, op)| condition.find(token).map(|pos| (pos, *token, *op)))
+            .min_by_key(|(pos, _, _)| *pos)
+            .ok_or_else(|| format!("missing operator in {}", condition))?;
+        let left = condition[..pos].trim();
+        let right = condition[pos + token.len()..].trim();
+        if left.is_empty() || right.is_empty() {
+            return Err(format!("incomplete condition {}", condition));
+        }
+
+        if left == "status" {
+            let health = GodsnodeHealth::parse(right).ok_or_else(|| format!("unknown health {}", right))?;
+            return match op {
+                RuleOp::Eq | RuleOp::Ne => Ok(RuleCondition::Status { op, health, reported: false }),
+                _ => Err(format!("status only compares with == and !=, got {}", token)),
+            };
+        }
+        Ok(RuleCondition::Metric {
+            metric: left.to_string(),
+            op,
+            threshold: parse_value(right)?,
+        })
+    }
+
+    // What makes the condition hold on the node, none when it does not
+    pub fn check(&self, node: &GodsnodeProto) -> Option<String> {
+        match self {
+            RuleCondition::Metric { metric, op, threshold } => {
+                let (value, _) = node.metrics.get(metric)?;
+                if op.compare(*value, *threshold) {
+                    Some(format!("{} {} {} {}", metric, value, op.as_str(), threshold))
+                } else {
+                    None
+                }
+            },
+            RuleCondition::Status { op, health, reported } => {
+                let current = if *reported {
+                    node.reported.as_ref().map_or(GodsnodeHealth::Unknown, |status| status.health)
+                } else {
+                    node.status.health
+                };
+                if (current == *health) == (*op == RuleOp::Eq) {
+                    Some(format!("status {}", current.as_str()))
+                } else {
+                    None
+                }
+            },
+        }
+    }
+}
+
+// Threshold with an optional unit, durations are in ms and percents are plain numbers
+fn parse_value(raw: &str) -> Result<f64, String> {
+    let (number, scale) = if let Some(number) = raw.strip_suffix("ms") {
+        (number, 1.)
+    } else if let Some(number) = raw.strip_suffix('s') {
+        (number, 1000.)
+    } else if let Some(number) = raw.strip_suffix('%') {
+        (number, 1.)
+    } else {
+        (raw, 1.)
+    };
+    number.trim().parse::<f64>().map(|value| value * scale).map_err(|_| format!("bad value {}", raw))
+}
+
+// Duration like `500ms`, `30s`, `2m` or `1h` in ms
+fn parse_duration(raw: &str) -> Result<u64, String> {
+    let units: [(&str, u64); 4] = [("ms", 1), ("s", 1000), ("m", 60_000), ("h", 3_600_000)];
+    let (number, scale) = units.iter()
+        .find(|(unit, _)| raw.ends_with(unit))
+        .map(|(unit, scale)| (&raw[..raw.len() - unit.len()], *scale))
+        .ok_or_else(|| format!("missing unit in duration {}", raw))?;
+    number.parse::<u64>().map(|value| value * scale).map_err(|_| format!("bad duration {}", raw))
+}
+
+// Condition checked on every node matching the pattern, firing once it held for `hold` ms, status
+// rules on every node check the reported status
+//
+// p99_latency > 500ms for 2m on .shop.**
+// status == down
+// error_rate >= 5% on ..payments
+#[derive(Clone, Debug)]
+pub struct AlertRule {
+    pub name: String,
+    pub condition: RuleCondition,
+    pub hold: u64,
+    pub pattern: GodsnodeQuery,
+    pub severity: AlertSeverity,
+    pub message: Option<String>,
+}
+
+impl AlertRule {
+    // Nodes default to all of them, severity to critical for status and warning for metrics
+    pub fn parse(rule: &str) -> Result<AlertRule, String> {
+        let mut condition = Vec::new();
+        let mut hold = 0;
+        let mut pattern = None;
+        let mut tokens = rule.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "for" => {
+                    let raw = tokens.next().ok_or_else(|| "missing duration after for".to_string())?;
+                    hold = parse_duration(raw)?;
+                },
+                "on" => {
+                    let raw = tokens.next().ok_or_else(|| "missing pattern after on".to_string())?;
+                    pattern = Some(GodsnodeQuery::parse(raw).ok_or_else(|| format!("bad pattern {}", raw))?);
+                },
+                _ => condition.push(token),
+            }
+        }
+        let mut condition = RuleCondition::parse(&condition.join(" "))?;
+        if let RuleCondition::Status { reported, .. } = &mut condition {
+            *reported = pattern.is_none();
+        }
+        let severity = match condition {
+            RuleCondition::Status { .. } => AlertSeverity::Critical,
+            RuleCondition::Metric { .. } => AlertSeverity::Warning,
+        };
+        Ok(AlertRule {
+            name: rule.trim().to_string(),
+            condition,
+            hold,
+            pattern: pattern.unwrap_or_else(|| GodsnodeQuery::parse(".**").unwrap()),
+            severity,
+            message: None,
+        })
+    }
+
+    // Rule text, or an object like
+    // `{"name": "slow-shop", "rule": "p99_latency > 500ms for 2m on .shop.**", "severity": "critical", "message": "..."}`
+    pub fn from_json(raw: &Value) -> Result<AlertRule, String> {
+        if let Some(rule) = raw.as_str() {
+            return AlertRule::parse(rule);
+        }
+        let mut rule = AlertRule::parse(raw["rule"].as_str().ok_or_else(|| "missing rule".to_string())?)?;
+        if let Some(name) = raw["name"].as_str() {
+            rule.name = name.to_string();
+        }
+        if let Some(severity) = raw["severity"].as_str() {
+            rule.severity = AlertSeverity::parse(severity).ok_or_else(|| format!("unknown severity {}", severity))?;
+        }
+        rule.message = raw["message"].as_str().map(|message| message.to_string());
+        Ok(rule)
+    }
+}
+
+// State of a rule on a node, nodes the rule does not hold on have none
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RuleState {
+    // Holding since then, not for long enough yet
+    Pending { since: u64 },
+    Firing { since: u64 },
+}
+
+// Rules and their state per rule name and node path, raising and resolving alerts as they fire
+pub struct RuleEngineProto {
+    rules: Vec<AlertRule>,
+    states: HashMap<(String, String), RuleState>,
+}
+
+pub type RuleEngine = Rc<RefCell<RuleEngineProto>>;
+
+impl RuleEngineProto {
+    pub fn new() -> RuleEngine {
+        Rc::new(RefCell::new(Self {
+            rules: Vec::new(),
+            states: HashMap::new(),
+        }))
+    }
+
+    // Rules from configuration, bad rules are dropped
+    pub fn from_json(raw: &Value) -> RuleEngine {
+        let engine = RuleEngineProto::new();
+        for rule in raw.as_array().map_or(&[][..], |rules| &rules[..]).iter() {
+            match AlertRule::from_json(rule) {
+                Ok(rule) => engine.borrow_mut().add_rule(rule),
+                Err(err) => { log!("Dropped bad alert rule {}: {}", rule, err); },
+            }
+        }
+        engine
+    }
+
+    // A rule with the same name is replaced
+    pub fn add_rule(&mut self, rule: AlertRule) {
+        self.rules.retain(|other| other.name != rule.name);
+        self.rules.push(rule);
+    }
+
+    pub fn remove_rule(&mut self, name: &str) -> bool {
+        let count = self.rules.len();
+        self.rules.retain(|rule| rule.name != name);
+        self.rules.len() != count
+    }
+
+    pub fn get_rules(&self) -> &Vec<AlertRule> {
+        &self.rules
+    }
+
+    fn alert_key(rule: &str, path: &str) -> String {
+        format!("rule:{}:{}", rule, path)
+    }
+
+    // Check every rule on its nodes, firing rules keep their alert raised so alerts dropped by a
+    // reset come back, rules and nodes which are gone resolve theirs
+    pub fn evaluate(&mut self, store: &Rc<Store>, alerts: &mut AlertsProto, now: u64) {
+        let mut seen = HashSet::new();
+        for rule in self.rules.iter() {
+            for (path, node) in store.query_paths(&rule.pattern).into_iter() {
+                let node = match node.upgrade() {
+                    Some(node) => node,
+                    None => continue,
+                };
+                let key = (rule.name.clone(), path);
+                let state = self.states.get(&key).cloned();
+                let next = match rule.condition.check(&node.borrow()) {
+                    Some(details) => {
+                        let since = match state {
+                            Some(RuleState::Pending { since }) | Some(RuleState::Firing { since }) => since,
+                            None => now,
+                        };
+                        if now.saturating_sub(since) < rule.hold {
+                            RuleState::Pending { since }
+                        } else {
+                            alerts.raise(Alert {
+                                key: RuleEngineProto::alert_key(&rule.name, &key.1),
+                                severity: rule.severity,
+                                path: key.1.clone(),
+                                message: rule.message.clone().unwrap_or(details),
+                                since,
+                            });
+                            RuleState::Firing { since }
+                        }
+                    },
+                    None => {
+                        if let Some(RuleState::Firing { .. }) = self.states.remove(&key) {
+                            alerts.resolve(&RuleEngineProto::alert_key(&rule.name, &key.1));
+                        }
+                        continue;
+                    },
+                };
+                self.states.insert(key.clone(), next);
+                seen.insert(key);
+            }
+        }
+
+        self.states.retain(|(rule, path), state| {
+            if seen.contains(&(rule.clone(), path.clone())) {
+                return true;
+            }
+            if let RuleState::Firing { .. } = state {
+                alerts.resolve(&RuleEngineProto::alert_key(rule, path));
+            }
+            false
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::span::godswood::tree::Godswoods;
+
+    fn woods() -> Godswoods {
+        let mut woods = Godswoods::new();
+        woods.add_wood(&json!({ "name": "shop", "children": { "api": {}, "db": {} } })).unwrap();
+        woods
+    }
+
+    fn record(woods: &Godswoods, path: &str, metric: &str, value: f64) {
+        let node = woods.store.get_node(path).unwrap();
+        woods.store.record_metric(&node, metric, value, 0);
+    }
+
+    fn engine(rule: &str) -> RuleEngineProto {
+        let mut engine = RuleEngineProto { rules: Vec::new(), states: HashMap::new() };
+        engine.add_rule(AlertRule::parse(rule).unwrap());
+        engine
+    }
+
+    fn state(engine: &RuleEngineProto, rule: &str, path: &str) -> Option<RuleState> {
+        engine.states.get(&(rule.to_string(), path.to_string())).cloned()
+    }
+
+    #[test]
+    fn parses_operators() {
+        let metric = |rule: &str| match AlertRule::parse(rule).unwrap().condition {
+            RuleCondition::Metric { op, threshold, .. } => (op, threshold),
+            condition => panic!("{:?}", condition),
+        };
+        assert_eq!(metric("error_rate >= 5%"), (RuleOp::Ge, 5.));
+        assert_eq!(metric("error_rate>5"), (RuleOp::Gt, 5.));
+        assert_eq!(metric("queue <= 10"), (RuleOp::Le, 10.));
+        assert_eq!(metric("queue != 0"), (RuleOp::Ne, 0.));
+        assert_eq!(metric("p99_latency > 500ms"), (RuleOp::Gt, 500.));
+        assert_eq!(metric("p99_latency > 2s"), (RuleOp::Gt, 2000.));
+        assert!(RuleOp::Ge.compare(5., 5.));
+        assert!(!RuleOp::Gt.compare(5., 5.));
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rule = AlertRule::parse("p99_latency > 500ms for 2m on .shop.**").unwrap();
+        assert_eq!(rule.hold, 120_000);
+        assert_eq!(rule.severity, AlertSeverity::Warning);
+        assert_eq!(AlertRule::parse("cpu > 1 for 500ms").unwrap().hold, 500);
+        assert_eq!(AlertRule::parse("cpu > 1 for 1h").unwrap().hold, 3_600_000);
+
+        let rule = AlertRule::parse("status == down").unwrap();
+        assert_eq!(rule.condition, RuleCondition::Status { op: RuleOp::Eq, health: GodsnodeHealth::Down, reported: true });
+        let rule = AlertRule::parse("status != healthy on .shop").unwrap();
+        assert_eq!(rule.condition, RuleCondition::Status { op: RuleOp::Ne, health: GodsnodeHealth::Healthy, reported: false });
+        assert_eq!(rule.severity, AlertSeverity::Critical);
+
+        assert!(AlertRule::parse("status > down").is_err());
+        assert!(AlertRule::parse("status == sleepy").is_err());
+        assert!(AlertRule::parse("cpu 5").is_err());
+        assert!(AlertRule::parse("cpu >").is_err());
+        assert!(AlertRule::parse("cpu > 2m").is_err());
+        assert!(AlertRule::parse("cpu > 1 for 2").is_err());
+        assert!(AlertRule::parse("cpu > 1 on shop").is_err());
+    }
+
+    #[test]
+    fn fires_after_hold() {
+        let woods = woods();
+        let alerts = AlertsProto::new();
+        let mut alerts = alerts.borrow_mut();
+        let rule = "cpu > 0.9 for 1s on .shop.*";
+        let mut engine = engine(rule);
+
+        record(&woods, ".shop.api", "cpu", 0.95);
+        record(&woods, ".shop.db", "cpu", 0.5);
+        engine.evaluate(&woods.store, &mut alerts, 1000);
+        assert_eq!(state(&engine, rule, ".shop.api"), Some(RuleState::Pending { since: 1000 }));
+        assert_eq!(state(&engine, rule, ".shop.db"), None);
+        engine.evaluate(&woods.store, &mut alerts, 1999);
+        assert!(alerts.get_active().is_empty());
+
+        engine.evaluate(&woods.store, &mut alerts, 2000);
+        assert_eq!(state(&engine, rule, ".shop.api"), Some(RuleState::Firing { since: 1000 }));
+        let active = alerts.get_active();
+        assert_eq!(active.len(), 1);
+        assert_eq!((active[0].path.as_str(), active[0].since), (".shop.api", 1000));
+
+        record(&woods, ".shop.api", "cpu", 0.5);
+        engine.evaluate(&woods.store, &mut alerts, 3000);
+        assert_eq!(state(&engine, rule, ".shop.api"), None);
+        assert!(alerts.get_active().is_empty());
+        assert!(engine.states.is_empty());
+
+        // Holding again starts over
+        record(&woods, ".shop.api", "cpu", 0.95);
+        engine.evaluate(&woods.store, &mut alerts, 4000);
+        assert_eq!(state(&engine, rule, ".shop.api"), Some(RuleState::Pending { since: 4000 }));
+    }
+
+    #[test]
+    fn resolves_removed_rules() {
+        let woods = woods();
+        let alerts = AlertsProto::new();
+        let mut alerts = alerts.borrow_mut();
+        let mut engine = engine("cpu > 0.9");
+
+        record(&woods, ".shop.api", "cpu", 0.95);
+        engine.evaluate(&woods.store, &mut alerts, 0);
+        assert_eq!(alerts.get_active().len(), 1);
+        assert!(engine.remove_rule("cpu > 0.9"));
+        assert!(!engine.remove_rule("cpu > 0.9"));
+        engine.evaluate(&woods.store, &mut alerts, 1);
+        assert!(alerts.get_active().is_empty());
+        assert!(engine.states.is_empty());
+    }
+
+    #[test]
+    fn resolves_removed_nodes() {
+        let mut woods = woods();
+        let alerts = AlertsProto::new();
+        let mut alerts = alerts.borrow_mut();
+        let mut engine = engine("cpu > 0.9");
+
+        record(&woods, ".shop.api", "cpu", 0.95);
+        record(&woods, ".shop.db", "cpu", 0.95);
+        engine.evaluate(&woods.store, &mut alerts, 0);
+        assert_eq!(alerts.get_active().len(), 2);
+        woods.replace_wood(&json!({ "name": "shop", "children": { "db": {} } })).unwrap();
+        engine.evaluate(&woods.store, &mut alerts, 1);
+        let active = alerts.get_active();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].path, ".shop.db");
+    }
+
+    #[test]
+    fn checks_status() {
+        let woods = woods();
+        let alerts = AlertsProto::new();
+        let mut alerts = alerts.borrow_mut();
+        let mut engine = engine("status == down on .shop.api");
+
+        let api = woods.store.get_node(".shop.api").unwrap();
+        woods.store.update_status(&api, GodsnodeHealth::Down, "crashed", 0);
+        engine.evaluate(&woods.store, &mut alerts, 0);
+        let active = alerts.get_active();
+        assert_eq!(active.len(), 1);
+        assert_eq!((active[0].severity, active[0].message.as_str()), (AlertSeverity::Critical, "status down"));
+    }
+
+    #[test]
+    fn checks_reported_status_on_every_node() {
+        let woods = woods();
+        let alerts = AlertsProto::new();
+        let mut alerts = alerts.borrow_mut();
+        let mut engine = engine("status == down");
+
+        let api = woods.store.get_node(".shop.api").unwrap();
+        woods.store.update_status(&api, GodsnodeHealth::Down, "crashed", 0);
+        assert_eq!(woods.store.get_node(".shop").unwrap().borrow().status.health, GodsnodeHealth::Down);
+        engine.evaluate(&woods.store, &mut alerts, 0);
+        let active = alerts.get_active();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].path, ".shop.api");
+    }
+}
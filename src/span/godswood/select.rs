@@ -0,0 +1,129 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+// Node picked by the user, by path, shared by the spans and the world
+pub struct SelectionProto {
+    path: Option<String>,
+    // Camera is moved to the node on the next tick
+    focus: bool,
+    // Pointer in normalized device coordinates with the aspect ratio of the world, resolved to a
+    // node on the next tick
+    pick: Option<(f32, f32, f32)>,
+    // Bumped on every change of the selected node
+    revision: u64,
+    // Pointer over the world like `pick`, picked again every tick while it stays there
+    pointer: Option<(f32, f32, f32)>,
+    // Node under the pointer, with when it came under it in ms
+    hovered: Option<(String, u64)>,
+}
+
+pub type Selection = Rc<RefCell<SelectionProto>>;
+
+impl SelectionProto {
+    pub fn new() -> Selection {
+        Rc::new(RefCell::new(Self {
+            path: None,
+            focus: false,
+            pick: None,
+            revision: 0,
+            pointer: None,
+            hovered: None,
+        }))
+    }
+
+    pub fn select(&mut self, path: &str, focus: bool) {
+        if self.path.as_deref() != Some(path) {
+            self.path = Some(path.to_string());
+            self.revision += 1;
+        }
+        self.focus = focus;
+    }
+
+    pub fn clear(&mut self) {
+        if self.path.take().is_some() {
+            self.revision += 1;
+        }
+        self.focus = false;
+    }
+
+    pub fn get_selected(&self) -> Option<&String> {
+        self.path.as_ref()
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // Selected node waiting for the camera, once
+    pub fn take_focus(&mut self) -> Option<String> {
+        if !self.focus {
+            return None;
+        }
+        self.focus = false;
+        self.path.clone()
+    }
+
+    // `x` and `y` within -1 to 1, up and right being positive, `aspect` is width over height
+    pub fn request_pick(&mut self, x: f32, y: f32, aspect: f32) {
+        self.pick = Some((x, y, aspect));
+    }
+
+    pub fn take_pick(&mut self) -> Option<(f32, f32, f32)> {
+        self.pick.take()
+    }
+
+    // None once the pointer left the world
+    pub fn set_pointer(&mut self, pointer: Option<(f32, f32, f32)>) {
+        self.pointer = pointer;
+    }
+
+    pub fn get_pointer(&self) -> Option<(f32, f32, f32)> {
+        self.pointer
+    }
+
+    // Hovering the same node again keeps when it was first hovered
+    pub fn set_hovered(&mut self, path: Option<String>, now: u64) {
+        if self.hovered.as_ref().map(|(hovered, _)| hovered) == path.as_ref() {
+            return;
+        }
+        self.hovered = path.map(|path| (path, now));
+    }
+
+    pub fn get_hovered(&self) -> Option<(&String, u64)> {
+        self.hovered.as_ref().map(|(path, since)| (path, *since))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_nodes() {
+        let selection = SelectionProto::new();
+        let mut selection = selection.borrow_mut();
+        selection.select(".app.db", true);
+        assert_eq!(selection.take_focus(), Some(".app.db".to_string()));
+        assert_eq!(selection.take_focus(), None);
+        assert_eq!(selection.get_selected().map(|path| path.as_str()), Some(".app.db"));
+
+        selection.clear();
+        assert_eq!(selection.get_selected(), None);
+        selection.select(".app.api", true);
+        selection.clear();
+        assert_eq!(selection.take_focus(), None);
+    }
+
+    #[test]
+    fn keeps_hover_start() {
+        let selection = SelectionProto::new();
+        let mut selection = selection.borrow_mut();
+        selection.set_hovered(Some(".app.db".to_string()), 10);
+        selection.set_hovered(Some(".app.db".to_string()), 20);
+        assert_eq!(selection.get_hovered(), Some((&".app.db".to_string(), 10)));
+        selection.set_hovered(Some(".app.api".to_string()), 30);
+        assert_eq!(selection.get_hovered().map(|(_, since)| since), Some(30));
+        selection.set_hovered(None, 40);
+        assert_eq!(selection.get_hovered(), None);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+
+// Raw samples, then averages over 10 s and 5 min buckets, about 4 minutes, 1 hour and 1 day of
+// history at a sample per second
+const LEVELS: [(u64, usize); 3] = [(0, 240), (10_000, 360), (300_000, 288)];
+
+// Fixed capacity buffer, the oldest item is overwritten once full
+#[derive(Clone, Debug)]
+pub struct RingBuffer<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.items.len() == self.capacity {
+            self.items.pop_front();
+        }
+        self.items.push_back(item);
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() == self.capacity
+    }
+
+    // Oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.items.front()
+    }
+}
+
+// Sample or aggregate of samples starting at `at` ms
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MetricSample {
+    pub at: u64,
+    pub min: f64,
+    pub max: f64,
+    pub sum: f64,
+    pub count: u32,
+}
+
+impl MetricSample {
+    pub fn new(at: u64, value: f64) -> Self {
+        Self {
+            at,
+            min: value,
+            max: value,
+            sum: value,
+            count: 1,
+        }
+    }
+
+    pub fn avg(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+
+    pub fn merge(&mut self, other: &MetricSample) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+}
+
+// Samples bucketed by `bucket` ms, raw when zero, with the bucket being filled kept apart
+#[derive(Clone, Debug)]
+struct SeriesLevel {
+    bucket: u64,
+    samples: RingBuffer<MetricSample>,
+    pending: Option<MetricSample>,
+}
+
+impl SeriesLevel {
+    fn push(&mut self, at: u64, value: f64) {
+        let sample = MetricSample::new(at - at % self.bucket.max(1), value);
+        if self.bucket == 0 {
+            self.samples.push(sample);
+            return;
+        }
+        match self.pending.as_mut() {
+            Some(pending) if pending.at >= sample.at => pending.merge(&sample),
+            _ => {
+                if let Some(pending) = self.pending.replace(sample) {
+                    self.samples.push(pending);
+                }
+            },
+        }
+    }
+
+    // Whether nothing since `since` was dropped yet
+    fn covers(&self, since: u64) -> bool {
+        if !self.samples.is_full() {
+            return true;
+        }
+        self.samples.first().is_none_or(|sample| sample.at <= since)
+    }
+}
+
+// History of a node metric, recent samples are kept as they are and older ones as averages over
+// longer and longer buckets
+#[derive(Clone, Debug)]
+pub struct MetricSeries {
+    levels: Vec<SeriesLevel>,
+}
+
+impl MetricSeries {
+    pub fn new() -> Self {
+        Self {
+            levels: LEVELS.iter().map(|(bucket, capacity)| SeriesLevel {
+                bucket: *bucket,
+                samples: RingBuffer::new(*capacity),
+                pending: None,
+            }).collect(),
+        }
+    }
+
+    // Samples are expected in time order
+    pub fn push(&mut self, at: u64, value: f64) {
+        for level in self.levels.iter_mut() {
+            level.push(at, value);
+        }
+    }
+
+    // Samples since `since` from the finest level still holding them all, merged down to at most
+    // `max_points`
+    pub fn get_samples(&self, since: u64, max_points: usize) -> Vec<MetricSample> {
+        let level = self.levels.iter().find(|level| level.covers(since)).unwrap_or_else(|| self.levels.last().unwrap());
+        let samples: Vec<MetricSample> = level.samples.iter().chain(level.pending.iter())
+            .filter(|sample| sample.at >= since)
+            .cloned()
+            .collect();
+        if max_points == 0 || samples.len() <= max_points {
+            return samples;
+        }
+
+        let chunk = samples.len().div_ceil(max_points);
+        samples.chunks(chunk).map(|chunk| {
+            let mut merged = chunk[0];
+            for sample in chunk[1..].iter() {
+                merged.merge(sample);
+            }
+            merged
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn avgs(samples: &[MetricSample]) -> Vec<(u64, f64)> {
+        samples.iter().map(|sample| (sample.at, sample.avg())).collect()
+    }
+
+    #[test]
+    fn overwrites_oldest_items() {
+        let mut buffer = RingBuffer::new(3);
+        for item in 0..5 {
+            buffer.push(item);
+        }
+        assert!(buffer.is_full());
+        assert_eq!(buffer.first(), Some(&2));
+        assert_eq!(buffer.iter().cloned().collect::<Vec<i32>>(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn merges_buckets() {
+        let mut level = SeriesLevel { bucket: 10_000, samples: RingBuffer::new(4), pending: None };
+        level.push(1_000, 1.);
+        level.push(9_000, 3.);
+        assert!(level.samples.iter().next().is_none());
+        level.push(12_000, 5.);
+        // Late samples go to the bucket being filled
+        level.push(8_000, 7.);
+        level.push(25_000, 1.);
+        assert_eq!(avgs(&level.samples.iter().cloned().collect::<Vec<MetricSample>>()), vec![(0, 2.), (10_000, 6.)]);
+        let pending = level.pending.unwrap();
+        assert_eq!((pending.at, pending.min, pending.max, pending.count), (20_000, 1., 1., 1));
+    }
+
+    #[test]
+    fn reads_finest_level() {
+        let mut series = MetricSeries::new();
+        for second in 0..300 {
+            series.push(second * 1000, second as f64);
+        }
+        // Raw samples only go back 4 minutes
+        let samples = series.get_samples(100_000, 0);
+        assert_eq!(samples.len(), 200);
+        assert_eq!(samples[0].at, 100_000);
+        let samples = series.get_samples(0, 0);
+        assert_eq!(samples.len(), 30);
+        assert_eq!(avgs(&samples[..2]), vec![(0, 4.5), (10_000, 14.5)]);
+    }
+
+    #[test]
+    fn downsamples_to_max_points() {
+        let mut series = MetricSeries::new();
+        for second in 0..10 {
+            series.push(second * 1000, second as f64);
+        }
+        let samples = series.get_samples(0, 4);
+        assert_eq!(avgs(&samples), vec![(0, 1.), (3_000, 4.), (6_000, 7.), (9_000, 9.)]);
+        assert_eq!((samples[1].min, samples[1].max, samples[1].count), (3., 5., 3));
+        assert_eq!(series.get_samples(0, 10).len(), 10);
+        assert_eq!(series.get_samples(5_000, 2).len(), 2);
+    }
+}
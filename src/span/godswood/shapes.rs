@@ -0,0 +1,44 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use dragon::{ecs::WorldState, core};
+
+pub type ShapeLine = (core::Point3<f32>, core::Point3<f32>);
+
+// Lines drawn by several systems, each owning a named layer, written to the shape store together
+pub struct ShapeLayersProto {
+    layers: BTreeMap<&'static str, Vec<ShapeLine>>,
+    dirty: bool,
+}
+
+pub type ShapeLayers = Rc<RefCell<ShapeLayersProto>>;
+
+impl ShapeLayersProto {
+    pub fn new() -> ShapeLayers {
+        Rc::new(RefCell::new(Self {
+            layers: BTreeMap::new(),
+            dirty: false,
+        }))
+    }
+
+    pub fn set_layer(&mut self, name: &'static str, lines: Vec<ShapeLine>) {
+        self.layers.insert(name, lines);
+        self.dirty = true;
+    }
+
+    // Replace the shapes of the world with all layers if any changed
+    pub fn flush(&mut self, state: &WorldState) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        let mut shapes = state.shape_store.borrow_mut();
+        shapes.clear();
+        for (begin, end) in self.layers.values().flatten() {
+            shapes.push(core::Shape::Line {
+                begin: *begin,
+                end: *end,
+            });
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::rc::Rc;
+use std::collections::HashMap;
+use serde_json::Value;
+use crate::utils::JsonParser;
+use crate::span::godswood::node::*;
+use crate::span::godswood::tree::*;
+use crate::span::godswood::error::*;
+use crate::span::godswood::parser;
+use crate::span::godswood::patch::{GodswoodPatch, linked_children};
+use crate::span::godswood::export::declared_children;
+use crate::span::godswood::status::GodsnodeStatus;
+use crate::span::godswood::series::MetricSeries;
+use crate::span::godswood::source::SourceEvent;
+
+// Reported status, metrics and metric history of a node
+type NodeData = (Option<GodsnodeStatus>, HashMap<String, (f64, u64)>, HashMap<String, MetricSeries>);
+
+impl Godswoods {
+    // Apply an update from a data source, updates of unknown nodes are dropped, alerts are kept
+    // apart from the woods
+    pub fn apply_event(&mut self, event: &SourceEvent, now: u64) {
+        match event {
+            SourceEvent::Topology(raw) => {
+                if let Err(err) = self.replace_wood(raw) {
+                    log!("Failed to load wood: {}", err);
+                }
+            },
+            SourceEvent::Status { path, health, reason, at } => {
+                match self.store.get_node(path) {
+                    Some(node) => { self.store.update_status(&node, *health, reason, at.unwrap_or(now)); },
+                    None => { log!("Dropped status of unknown node {}", path); },
+                }
+            },
+            SourceEvent::Metric { path, metric, value, at } => {
+                match self.store.get_node(path) {
+                    Some(node) => self.store.record_metric(&node, metric, *value, at.unwrap_or(now)),
+                    None => { log!("Dropped metric of unknown node {}", path); },
+                }
+            },
+            SourceEvent::Alert { .. } | SourceEvent::Resolve { .. } => {},
+            SourceEvent::Reset => {
+                let names: Vec<String> = self.woods.borrow().keys().cloned().collect();
+                for name in names.iter() {
+                    self.remove_wood(name);
+                }
+            },
+        }
+    }
+
+    // Load the wood in place of the one with the same name, reported status, metrics and their
+    // history of nodes keeping their path survive, a wood failing to load leaves the old one in place
+    // Only the nodes that changed are patched, so that the others keep their entities
+    pub fn replace_wood(&mut self, raw: &Value) -> Result<(), WoodError> {
+        parser::validate_wood(raw)?;
+        let name = raw.get_str("name", "");
+        let wood = self.woods.borrow().get(&name).cloned();
+        let (root, old) = match wood {
+            Some(wood) => {
+                let wood = wood.borrow();
+                (wood.wood.get_root().upgrade(), wood.wood.to_json())
+            },
+            None => return self.add_wood(raw),
+        };
+
+        let saved = self.save_data(&name);
+        let mut diff = WoodDiff::default();
+        let patched = match root {
+            Some(root) => self.diff_node(&root, &format!(".{}", name), &name, raw, &mut diff) && self.apply_diff(diff),
+            None => false,
+        };
+        if patched {
+            return Ok(());
+        }
+
+        self.remove_wood(&name);
+        let result = self.add_wood(raw).or_else(|err| self.add_wood(&old).and(Err(err)));
+        self.restore_data(saved);
+        result
+    }
+
+    fn save_data(&self, name: &str) -> HashMap<String, NodeData> {
+        let prefix = format!(".{}", name);
+        let nodes = self.store.read_paths(|path| path == prefix || path.starts_with(&(prefix.clone() + ".")));
+        let mut saved = HashMap::new();
+        for (path, node) in nodes.into_iter() {
+            if let Some(node) = node.upgrade() {
+                let node = node.borrow();
+                saved.insert(path, (node.reported.clone(), node.metrics.clone(), node.series.clone()));
+            }
+        }
+        saved
+    }
+
+    fn restore_data(&self, saved: HashMap<String, NodeData>) {
+        for (path, (reported, metrics, series)) in saved.into_iter() {
+            if let Some(node) = self.store.get_node(&path) {
+                if let Some(reported) = reported {
+                    self.store.update_status(&node, reported.health, &reported.reason, reported.changed_at);
+                }
+                let mut node = node.borrow_mut();
+                node.metrics = metrics;
+                node.series = series;
+            }
+        }
+    }
+
+    // Collect the patches turning the live node at `path` into its declaration, false when the
+    // declaration changes nodes shared with other woods, which only a rebuild sorts out
+    fn diff_node(&self, node: &Rc<Godsnode>, path: &str, app: &String, raw: &Value, diff: &mut WoodDiff) -> bool {
+        let state = node.borrow();
+        if state.app_meta_map.len() > 1 {
+            // Only the first wood declaring the node shapes it, others may only repeat its subtree
+            return declares_subtree(node, raw);
+        }
+
+        let linked = linked_children(node, app);
+        let links: Vec<Option<Rc<Godsnode>>> = raw.get_str_list("depends_on").iter().map(|path| self.store.get_node(path)).collect();
+        let same_links = links.iter().all(|link| {
+            link.as_ref().is_some_and(|link| linked.iter().any(|kid| Rc::ptr_eq(kid, link)))
+        }) && linked.iter().all(|kid| links.iter().flatten().any(|link| Rc::ptr_eq(kid, link)));
+        if !state.has_details(raw) || !same_links {
+            diff.updated.push(GodswoodPatch::Update { path: path.to_string(), raw: raw.clone() });
+        }
+
+        let declared = declared_children(node, app);
+        let children = raw["children"].as_object();
+        for kid in declared.iter() {
+            let (name, global_id, shared) = {
+                let kid = kid.borrow();
+                (kid.name.clone(), kid.global_id.clone(), kid.app_meta_map.len() > 1)
+            };
+            let kid_path = format!("{}.{}", path, name);
+            match children.and_then(|children| children.get(&name)) {
+                Some(kid_raw) if kid_raw["id"].as_str() == global_id.as_deref() => {
+                    if !self.diff_node(kid, &kid_path, app, kid_raw, diff) {
+                        return false;
+                    }
+                },
+                // Nodes of other woods are only dropped or swapped by a rebuild
+                _ if shared => return false,
+                // A node with another id takes the place of the declared one
+                Some(kid_raw) => {
+                    diff.removed.push(GodswoodPatch::Remove { path: kid_path });
+                    diff.added.push(GodswoodPatch::Add { parent: path.to_string(), name, raw: kid_raw.clone() });
+                },
+                None => diff.removed.push(GodswoodPatch::Remove { path: kid_path }),
+            }
+        }
+        for (name, kid_raw) in children.into_iter().flatten() {
+            if !declared.iter().any(|kid| kid.borrow().name == *name) {
+                diff.added.push(GodswoodPatch::Add { parent: path.to_string(), name: name.clone(), raw: kid_raw.clone() });
+            }
+        }
+        true
+    }
+
+    // Removals go first to free names, additions are retried until the nodes they link to are added,
+    // and updates go last as their links may point at added nodes
+    fn apply_diff(&mut self, diff: WoodDiff) -> bool {
+        let mut pending = diff.added;
+        let mut result = diff.removed.iter().try_for_each(|patch| self.patch(patch));
+        while result.is_ok() && !pending.is_empty() {
+            let count = pending.len();
+            let mut failed = Vec::new();
+            for patch in pending.into_iter() {
+                if let Err(err) = self.patch(&patch) {
+                    failed.push(patch);
+                    result = Err(err);
+                }
+            }
+            if failed.len() < count {
+                result = Ok(());
+            }
+            pending = failed;
+        }
+        if result.is_ok() {
+            result = diff.updated.iter().try_for_each(|patch| self.patch(patch));
+        }
+        if let Err(err) = &result {
+            log!("Rebuilding wood failing to patch: {}", err);
+        }
+        result.is_ok()
+    }
+}
+
+// Patches turning a live wood into a new declaration of it
+#[derive(Default)]
+struct WoodDiff {
+    removed: Vec<GodswoodPatch>,
+    added: Vec<GodswoodPatch>,
+    updated: Vec<GodswoodPatch>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::span::godswood::status::GodsnodeHealth;
+
+    fn wood() -> Value {
+        json!({
+            "name": "app",
+            "children": {
+                "node1": { "class": "worker", "children": { "node3": {} } },
+                "node2": { "depends_on": [".app.node1.node3"] },
+            },
+        })
+    }
+
+    // Loaded from `wood()` with status and a metric reported on node1
+    fn woods() -> Godswoods {
+        let mut woods = Godswoods::new();
+        woods.replace_wood(&wood()).unwrap();
+        let node1 = node(&woods, ".app.node1").unwrap();
+        woods.store.update_status(&node1, GodsnodeHealth::Down, "crashed", 10);
+        woods.store.record_metric(&node1, "cpu", 0.5, 10);
+        woods.take_changed();
+        woods.store.take_removed();
+        woods
+    }
+
+    fn node(woods: &Godswoods, path: &str) -> Option<Rc<Godsnode>> {
+        woods.store.get_node(path)
+    }
+
+    fn id(woods: &Godswoods, path: &str) -> u64 {
+        node(woods, path).unwrap().borrow().id
+    }
+
+    fn assert_data(woods: &Godswoods) {
+        let node1 = node(woods, ".app.node1").unwrap();
+        let node1 = node1.borrow();
+        assert_eq!(node1.reported.as_ref().map(|status| status.health), Some(GodsnodeHealth::Down));
+        assert_eq!(node1.metrics.get("cpu"), Some(&(0.5, 10)));
+    }
+
+    #[test]
+    fn keeps_woods_failing_to_load() {
+        let mut woods = woods();
+        let ids = (id(&woods, ".app.node1"), id(&woods, ".app.node2"));
+        let mut raw = wood();
+        raw["children"]["node1"]["children"]["node3"]["depends_on"] = json!([".app.node2"]);
+        assert!(matches!(woods.replace_wood(&raw).unwrap_err().kind, WoodErrorKind::Cycle(_)));
+        assert_eq!((id(&woods, ".app.node1"), id(&woods, ".app.node2")), ids);
+        assert!(woods.store.take_removed().is_empty());
+        assert_data(&woods);
+    }
+
+    #[test]
+    fn keeps_unchanged_nodes() {
+        let mut woods = woods();
+        let ids = (id(&woods, ".app.node1"), id(&woods, ".app.node1.node3"));
+        woods.replace_wood(&wood()).unwrap();
+        assert!(!woods.take_changed());
+
+        let mut raw = wood();
+        raw["children"]["node1"]["class"] = json!("cache");
+        raw["children"]["node1"]["children"]["node4"] = json!({});
+        woods.replace_wood(&raw).unwrap();
+        assert!(woods.take_changed());
+        assert_eq!((id(&woods, ".app.node1"), id(&woods, ".app.node1.node3")), ids);
+        assert_eq!(node(&woods, ".app.node1").unwrap().borrow().service_type, GodsnodeClass::Cache);
+        assert!(node(&woods, ".app.node1.node4").is_some());
+        assert!(woods.store.take_removed().is_empty());
+        assert_data(&woods);
+    }
+
+    #[test]
+    fn removes_dropped_nodes() {
+        let mut woods = woods();
+        let node2 = id(&woods, ".app.node2");
+        let mut raw = wood();
+        raw["children"].as_object_mut().unwrap().remove("node2");
+        woods.replace_wood(&raw).unwrap();
+        assert!(node(&woods, ".app.node2").is_none());
+        assert!(node(&woods, ".app.node1.node3").is_some());
+        assert_eq!(woods.store.take_removed(), vec![node2]);
+        assert_data(&woods);
+    }
+
+    #[test]
+    fn relinks_dependencies() {
+        let mut woods = woods();
+        let node2 = id(&woods, ".app.node2");
+        let mut raw = wood();
+        raw["children"]["node2"]["depends_on"] = json!([".app.node5"]);
+        raw["children"]["node5"] = json!({ "class": "database" });
+        woods.replace_wood(&raw).unwrap();
+        assert_eq!(id(&woods, ".app.node2"), node2);
+        assert!(node(&woods, ".app.node2.node3").is_none());
+        assert!(Rc::ptr_eq(&node(&woods, ".app.node2.node5").unwrap(), &node(&woods, ".app.node5").unwrap()));
+        assert!(woods.store.take_removed().is_empty());
+    }
+
+    #[test]
+    fn replaces_nodes_changing_id() {
+        let mut woods = woods();
+        let node1 = node(&woods, ".app.node1").unwrap();
+        let mut raw = wood();
+        raw["children"]["node1"]["id"] = json!("worker");
+        woods.replace_wood(&raw).unwrap();
+        let replaced = node(&woods, ".app.node1").unwrap();
+        assert!(!Rc::ptr_eq(&replaced, &node1));
+        assert_eq!(replaced.borrow().global_id.as_deref(), Some("worker"));
+        assert!(node(&woods, ".app.node2.node3").is_some());
+        assert!(woods.store.take_removed().contains(&node1.borrow().id));
+    }
+}
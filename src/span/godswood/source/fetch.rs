@@ -0,0 +1,70 @@
+use std::rc::Rc;
+use std::cell::Cell;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::Response;
+use crate::span::godswood::source::{DataSource, SourceEvent, SourceInbox, SourceInboxProto};
+
+// Polls a URL answering text messages, one request in flight at a time
+pub struct FetchSource {
+    url: String,
+    interval: u64,
+    last_poll: Option<u64>,
+    in_flight: Rc<Cell<bool>>,
+    inbox: SourceInbox,
+}
+
+impl FetchSource {
+    pub fn new(url: &str, interval: u64) -> Self {
+        Self {
+            url: url.to_string(),
+            interval,
+            last_poll: None,
+            in_flight: Rc::new(Cell::new(false)),
+            inbox: SourceInboxProto::new(),
+        }
+    }
+
+    fn request(&self) {
+        let window = match web_sys::window() {
+            Some(window) => window,
+            None => return,
+        };
+        let promise = window.fetch_with_str(&self.url);
+        let url = self.url.clone();
+        let inbox = self.inbox.clone();
+        let in_flight = self.in_flight.clone();
+        in_flight.set(true);
+        spawn_local(async move {
+            match fetch_text(promise).await {
+                Ok(text) => inbox.borrow_mut().push_text(&text),
+                Err(err) => { log!("Failed to fetch {}: {:?}", url, err); },
+            }
+            in_flight.set(false);
+        });
+    }
+}
+
+async fn fetch_text(promise: js_sys::Promise) -> Result<String, JsValue> {
+    let response: Response = JsFuture::from(promise).await?.dyn_into()?;
+    if !response.ok() {
+        return Err(JsValue::from_str(&format!("status {}", response.status())));
+    }
+    let text = JsFuture::from(response.text()?).await?;
+    text.as_string().ok_or_else(|| JsValue::from_str("body is not text"))
+}
+
+impl DataSource for FetchSource {
+    fn get_name(&self) -> &str {
+        "fetch"
+    }
+
+    fn poll(&mut self, now: u64) -> Vec<SourceEvent> {
+        let due = self.last_poll.is_none_or(|last| now.saturating_sub(last) >= self.interval);
+        if due && !self.in_flight.get() {
+            self.last_poll = Some(now);
+            self.request();
+        }
+        self.inbox.borrow_mut().take()
+    }
+}
@@ -0,0 +1,185 @@
+use serde_json::{json, Value};
+use crate::utils::JsonParser;
+use crate::span::godswood::status::GodsnodeHealth;
+use crate::span::godswood::alert::AlertSeverity;
+use crate::span::godswood::source::{DataSource, SourceDecoder, SourceEvent};
+
+// Scripted events, each due a number of ms after the first poll, replayed every `period` ms when set
+pub struct MockSource {
+    script: Vec<(u64, SourceEvent)>,
+    period: Option<u64>,
+    start: Option<u64>,
+    next: usize,
+}
+
+impl MockSource {
+    pub fn new(mut script: Vec<(u64, SourceEvent)>, period: Option<u64>) -> Self {
+        script.sort_by_key(|(after, _)| *after);
+        Self {
+            script,
+            period,
+            start: None,
+            next: 0,
+        }
+    }
+
+    // Script from configuration like
+    // `{"kind": "mock", "period": 10000, "script": [{"after": 1000, "message": {...}}]}`
+    // with messages as in the wire protocol
+    pub fn from_json(config: &Value) -> Self {
+        let mut decoder = SourceDecoder::new();
+        let mut script = Vec::new();
+        if let Some(steps) = config["script"].as_array() {
+            for step in steps.iter() {
+                if let Some(event) = decoder.decode_message(&step["message"]) {
+                    script.push((step.get_u64("after", 0), event));
+                }
+            }
+        }
+        MockSource::new(script, config["period"].as_u64())
+    }
+
+    // Sample woods with a database going down and back up every 12 seconds, raising alerts meanwhile
+    pub fn demo() -> Self {
+        let application = json!({
+            "name": "sample-application",
+            "children": {
+                "service1": {
+                    "children": {
+                        "service5": {
+                            "children": {
+                                "service6": { "class": "queue" },
+                                "service7": { "class": "worker" }
+                            }
+                        }
+                    }
+                },
+                "service2": {
+                    "children": {
+                        "service10": {
+                            "depends_on": [".sample-application.service4.service3"],
+                            "children": {
+                                "service21": {},
+                                "service22": { "class": "cache" },
+                                "service23": { "class": "external" }
+                            }
+                        },
+                        "service11": {}
+                    }
+                },
+                "service4": {
+                    "class": "gateway",
+                    "children": {
+                        "service3": { "class": "database", "id": "main-db" }
+                    }
+                }
+            }
+        });
+        // Shares the database of the sample application
+        let billing = json!({
+            "name": "sample-billing",
+            "children": {
+                "invoices": {
+                    "class": "worker",
+                    "children": {
+                        "service3": { "id": "main-db" }
+                    }
+                },
+                "payments": { "class": "gateway" }
+            }
+        });
+
+        let leaves = [
+            ".sample-application.service1.service5.service6",
+            ".sample-application.service1.service5.service7",
+            ".sample-application.service2.service10.service21",
+            ".sample-application.service2.service10.service22",
+            ".sample-application.service2.service10.service23",
+            ".sample-application.service2.service11",
+            ".sample-application.service4.service3",
+            ".sample-billing.payments",
+        ];
+        let status = |path: &str, health: &str, reason: &str| {
+            SourceEvent::Status {
+                path: path.to_string(),
+                health: GodsnodeHealth::parse(health).unwrap(),
+                reason: reason.to_string(),
+                at: None,
+            }
+        };
+
+        let mut script = vec![
+            (0, SourceEvent::Topology(application)),
+            (0, SourceEvent::Topology(billing)),
+        ];
+        for leaf in leaves.iter() {
+            script.push((0, status(leaf, "healthy", "")));
+        }
+        let metric = |path: &str, metric: &str, value: f64| {
+            SourceEvent::Metric {
+                path: path.to_string(),
+                metric: metric.to_string(),
+                value,
+                at: None,
+            }
+        };
+        // Database latency climbing until it goes down, cache hit rate dipping meanwhile and the
+        // gateway failing requests while the database is down
+        let gateway = ".sample-application.service4";
+        for second in 0..12u64 {
+            let latency = if second < 6 { 20. + 15. * second as f64 } else { 25. };
+            let hit_rate = if (3..10).contains(&second) { 55. } else { 92. };
+            let error_rate = if (6..9).contains(&second) { 12. } else { 0.5 };
+            script.push((second * 1000, metric(leaves[6], "p99_latency", latency)));
+            script.push((second * 1000, metric(leaves[3], "hit_rate", hit_rate)));
+            script.push((second * 1000, metric(gateway, "request_rate", 120. + 10. * (second % 4) as f64)));
+            script.push((second * 1000, metric(gateway, "error_rate", error_rate)));
+        }
+        script.push((3000, status(leaves[3], "degraded", "hit rate below 60%")));
+        script.push((3000, SourceEvent::Alert {
+            key: "cache-hit-rate".to_string(),
+            severity: AlertSeverity::Warning,
+            path: leaves[3].to_string(),
+            message: "cache hit rate below 60%".to_string(),
+            at: None,
+        }));
+        script.push((6000, status(leaves[6], "down", "connection refused")));
+        script.push((6000, SourceEvent::Alert {
+            key: "main-db-down".to_string(),
+            severity: AlertSeverity::Critical,
+            path: leaves[6].to_string(),
+            message: "main database refuses connections".to_string(),
+            at: None,
+        }));
+        script.push((9000, status(leaves[6], "healthy", "")));
+        script.push((9000, SourceEvent::Resolve { key: "main-db-down".to_string(), at: None }));
+        script.push((10000, status(leaves[3], "healthy", "")));
+        script.push((10000, SourceEvent::Resolve { key: "cache-hit-rate".to_string(), at: None }));
+        MockSource::new(script, Some(12000))
+    }
+}
+
+impl DataSource for MockSource {
+    fn get_name(&self) -> &str {
+        "mock"
+    }
+
+    fn poll(&mut self, now: u64) -> Vec<SourceEvent> {
+        let start = *self.start.get_or_insert(now);
+        let mut elapsed = now.saturating_sub(start);
+        if let Some(period) = self.period {
+            if self.next >= self.script.len() && elapsed >= period {
+                self.start = Some(start + elapsed / period * period);
+                self.next = 0;
+                elapsed %= period;
+            }
+        }
+
+        let mut events = Vec::new();
+        while self.next < self.script.len() && self.script[self.next].0 <= elapsed {
+            events.push(self.script[self.next].1.clone());
+            self.next += 1;
+        }
+        events
+    }
+}
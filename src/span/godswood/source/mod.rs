@@ -0,0 +1,304 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use serde_json::{json, Value};
+use crate::utils::{JsonParser, CodecError};
+use crate::span::godswood::status::GodsnodeHealth;
+use crate::span::godswood::alert::AlertSeverity;
+use crate::span::godswood::frame::{self, Frame};
+pub mod fetch;
+pub mod socket;
+pub mod mock;
+pub mod apply;
+
+// Update delivered by a data source, a missing `at` is stamped when the update is applied
+#[derive(Clone, Debug, PartialEq)]
+pub enum SourceEvent {
+    // Full wood JSON, replacing the wood of the same name
+    Topology(Value),
+    Status { path: String, health: GodsnodeHealth, reason: String, at: Option<u64> },
+    Metric { path: String, metric: String, value: f64, at: Option<u64> },
+    // Raise or update the alert with the key
+    Alert { key: String, severity: AlertSeverity, path: String, message: String, at: Option<u64> },
+    Resolve { key: String, at: Option<u64> },
+    // Drop all woods
+    Reset,
+}
+
+impl SourceEvent {
+    // Event with a missing `at` set to `now`
+    pub fn stamped(&self, now: u64) -> SourceEvent {
+        let mut event = self.clone();
+        match &mut event {
+            SourceEvent::Status { at, .. } | SourceEvent::Metric { at, .. } |
+            SourceEvent::Alert { at, .. } | SourceEvent::Resolve { at, .. } => {
+                at.get_or_insert(now);
+            },
+            _ => {},
+        }
+        event
+    }
+
+    // Wire message of the event, see `SourceDecoder`
+    pub fn to_message(&self) -> Value {
+        match self {
+            SourceEvent::Topology(wood) => json!({ "type": "topology", "wood": wood }),
+            SourceEvent::Status { path, health, reason, at } => json!({
+                "type": "status", "path": path, "health": health.as_str(), "reason": reason, "at": at,
+            }),
+            SourceEvent::Metric { path, metric, value, at } => json!({
+                "type": "metric", "path": path, "metric": metric, "value": value, "at": at,
+            }),
+            SourceEvent::Alert { key, severity, path, message, at } => json!({
+                "type": "alert", "key": key, "severity": severity.as_str(), "path": path, "message": message, "at": at,
+            }),
+            SourceEvent::Resolve { key, at } => json!({ "type": "resolve", "key": key, "at": at }),
+            SourceEvent::Reset => json!({ "type": "reset" }),
+        }
+    }
+}
+
+// Feeds the stage with topology and live data, polled once per tick with the current time in ms
+pub trait DataSource {
+    fn get_name(&self) -> &str;
+    fn poll(&mut self, now: u64) -> Vec<SourceEvent>;
+}
+
+// Pick the data source from configuration like `{"kind": "websocket", "url": "wss://..."}`,
+// unknown or missing kinds run the demo mock
+pub fn create_source(config: &Value) -> Box<dyn DataSource> {
+    match config.get_str("kind", "mock").as_str() {
+        "fetch" => Box::new(fetch::FetchSource::new(
+            &config.get_str("url", ""),
+            config.get_u64("interval_ms", 5000),
+        )),
+        "websocket" => Box::new(socket::SocketSource::new(
+            &config.get_str("url", ""),
+            config.get_u64("retry_ms", 3000),
+        )),
+        "mock" => match config["script"].as_array() {
+            Some(_) => Box::new(mock::MockSource::from_json(config)),
+            None => Box::new(mock::MockSource::demo()),
+        },
+        kind => {
+            log!("Unknown data source {}, running the demo", kind);
+            Box::new(mock::MockSource::demo())
+        },
+    }
+}
+
+// Turns wire messages into events
+//
+// Text messages are JSON, one message or an array of them:
+//   {"type": "topology", "wood": {...}}
+//   {"type": "status", "path": ".app.db", "health": "down", "reason": "...", "at": 1571000000000}
+//   {"type": "metric", "path": ".app.db", "metric": "p99_latency", "value": 12.5, "at": ...}
+//   {"type": "alert", "key": "db-down", "severity": "critical", "path": ".app.db", "message": "...", "at": ...}
+//   {"type": "resolve", "key": "db-down", "at": ...}
+//   {"type": "ids", "nodes": {"1": ".app.db"}, "metrics": {"1": "p99_latency"}}
+//   {"type": "reset"}
+// Binary messages are frames, with node and metric ids announced by `ids` messages first.
+pub struct SourceDecoder {
+    nodes: HashMap<u32, String>,
+    metrics: HashMap<u16, String>,
+}
+
+impl SourceDecoder {
+    pub fn new() -> Self {
+        Self {
+            nodes: HashMap::new(),
+            metrics: HashMap::new(),
+        }
+    }
+
+    pub fn decode_text(&mut self, raw: &str) -> Vec<SourceEvent> {
+        match serde_json::from_str::<Value>(raw) {
+            Ok(Value::Array(messages)) => messages.iter().filter_map(|message| self.decode_message(message)).collect(),
+            Ok(message) => self.decode_message(&message).into_iter().collect(),
+            Err(err) => {
+                log!("Dropped bad message: {}", err);
+                Vec::new()
+            },
+        }
+    }
+
+    pub fn decode_message(&mut self, message: &Value) -> Option<SourceEvent> {
+        let at = message["at"].as_u64();
+        match message.get_str("type", "").as_str() {
+            "topology" if message["wood"].is_object() => Some(SourceEvent::Topology(message["wood"].clone())),
+            "status" => Some(SourceEvent::Status {
+                path: message["path"].as_str()?.to_string(),
+                health: GodsnodeHealth::parse(message["health"].as_str()?)?,
+                reason: message.get_str("reason", ""),
+                at,
+            }),
+            "metric" => Some(SourceEvent::Metric {
+                path: message["path"].as_str()?.to_string(),
+                metric: message["metric"].as_str()?.to_string(),
+                value: message["value"].as_f64()?,
+                at,
+            }),
+            "alert" => {
+                let path = message["path"].as_str()?.to_string();
+                Some(SourceEvent::Alert {
+                    key: message.get_str("key", &path),
+                    severity: AlertSeverity::parse(message["severity"].as_str()?)?,
+                    path,
+                    message: message.get_str("message", ""),
+                    at,
+                })
+            },
+            "resolve" => Some(SourceEvent::Resolve {
+                key: message["key"].as_str()?.to_string(),
+                at,
+            }),
+            "reset" => Some(SourceEvent::Reset),
+            "ids" => {
+                for (id, path) in message.get_str_map("nodes").into_iter() {
+                    if let Ok(id) = id.parse() {
+                        self.nodes.insert(id, path);
+                    }
+                }
+                for (id, metric) in message.get_str_map("metrics").into_iter() {
+                    if let Ok(id) = id.parse() {
+                        self.metrics.insert(id, metric);
+                    }
+                }
+                None
+            },
+            kind => {
+                log!("Dropped unknown message {}", kind);
+                None
+            },
+        }
+    }
+
+    // Frames of unannounced nodes are dropped
+    pub fn decode_binary(&self, raw: &[u8]) -> Result<Vec<SourceEvent>, CodecError> {
+        let frames = frame::decode_frames(raw)?;
+        Ok(frames.iter().filter_map(|frame| {
+            let path = self.nodes.get(&frame.node())?.clone();
+            match *frame {
+                Frame::Status { health, at, .. } => Some(SourceEvent::Status {
+                    path,
+                    health,
+                    reason: String::new(),
+                    at: Some(at),
+                }),
+                Frame::Metric { metric, value, at, .. } => Some(SourceEvent::Metric {
+                    path,
+                    metric: self.metrics.get(&metric).cloned().unwrap_or_else(|| metric.to_string()),
+                    value: value as f64,
+                    at: Some(at),
+                }),
+            }
+        }).collect())
+    }
+}
+
+// Decoded events waiting for the next poll, shared with the callbacks of browser sources
+pub struct SourceInboxProto {
+    decoder: SourceDecoder,
+    events: Vec<SourceEvent>,
+}
+
+pub type SourceInbox = Rc<RefCell<SourceInboxProto>>;
+
+impl SourceInboxProto {
+    pub fn new() -> SourceInbox {
+        Rc::new(RefCell::new(Self {
+            decoder: SourceDecoder::new(),
+            events: Vec::new(),
+        }))
+    }
+
+    pub fn push_text(&mut self, raw: &str) {
+        let events = self.decoder.decode_text(raw);
+        self.events.extend(events);
+    }
+
+    pub fn push_binary(&mut self, raw: &[u8]) {
+        match self.decoder.decode_binary(raw) {
+            Ok(events) => self.events.extend(events),
+            Err(err) => { log!("Dropped bad frames: {}", err); },
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<SourceEvent> {
+        self.events.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::mock::MockSource;
+
+    fn status(path: &str, health: GodsnodeHealth, at: Option<u64>) -> SourceEvent {
+        SourceEvent::Status { path: path.to_string(), health, reason: String::new(), at }
+    }
+
+    #[test]
+    fn decodes_text() {
+        let mut decoder = SourceDecoder::new();
+        assert_eq!(
+            decoder.decode_text(r#"{"type": "status", "path": ".app.db", "health": "down", "at": 10}"#),
+            vec![status(".app.db", GodsnodeHealth::Down, Some(10))]);
+        assert_eq!(decoder.decode_text(r#"[
+            {"type": "alert", "path": ".app.db", "severity": "critical"},
+            {"type": "metric", "path": ".app.db", "metric": "cpu"},
+            {"type": "resolve", "key": ".app.db"},
+            {"type": "reboot"},
+            {"type": "reset"}
+        ]"#), vec![
+            SourceEvent::Alert {
+                key: ".app.db".to_string(),
+                severity: AlertSeverity::Critical,
+                path: ".app.db".to_string(),
+                message: String::new(),
+                at: None,
+            },
+            SourceEvent::Resolve { key: ".app.db".to_string(), at: None },
+            SourceEvent::Reset,
+        ]);
+        assert!(decoder.decode_text("{").is_empty());
+        assert!(decoder.decode_text(r#"{"type": "topology", "wood": "app"}"#).is_empty());
+    }
+
+    #[test]
+    fn decodes_frames() {
+        let mut decoder = SourceDecoder::new();
+        let raw = frame::encode_frames(&[
+            Frame::Status { node: 1, health: GodsnodeHealth::Down, at: 10 },
+            Frame::Metric { node: 1, metric: 2, value: 0.5, at: 20 },
+            Frame::Metric { node: 1, metric: 3, value: 1.5, at: 30 },
+            Frame::Status { node: 2, health: GodsnodeHealth::Healthy, at: 40 },
+        ]);
+        assert!(decoder.decode_text(r#"{"type": "ids", "nodes": {"1": ".app.db"}, "metrics": {"2": "cpu"}}"#).is_empty());
+        let metric = |metric: &str, value: f64, at: u64| SourceEvent::Metric {
+            path: ".app.db".to_string(),
+            metric: metric.to_string(),
+            value,
+            at: Some(at),
+        };
+        assert_eq!(decoder.decode_binary(&raw).unwrap(), vec![
+            status(".app.db", GodsnodeHealth::Down, Some(10)),
+            metric("cpu", 0.5, 20),
+            metric("3", 1.5, 30),
+        ]);
+        assert!(decoder.decode_binary(&raw[..raw.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn plays_scripts() {
+        let mut source = MockSource::new(vec![
+            (1000, status(".app.db", GodsnodeHealth::Down, None)),
+            (0, SourceEvent::Reset),
+        ], Some(2000));
+        assert_eq!(source.poll(500), vec![SourceEvent::Reset]);
+        assert!(source.poll(1000).is_empty());
+        assert_eq!(source.poll(1500), vec![status(".app.db", GodsnodeHealth::Down, None)]);
+        assert_eq!(source.poll(4600), vec![SourceEvent::Reset]);
+        assert_eq!(source.poll(5500), vec![status(".app.db", GodsnodeHealth::Down, None)]);
+    }
+}
@@ -0,0 +1,83 @@
+use std::rc::Rc;
+use std::cell::Cell;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+use crate::span::godswood::source::{DataSource, SourceEvent, SourceInbox, SourceInboxProto};
+
+// WebSocket client, text messages are JSON and binary ones are frames, closed sockets are opened
+// again every `retry` ms
+pub struct SocketSource {
+    url: String,
+    retry: u64,
+    retry_at: u64,
+    socket: Option<WebSocket>,
+    closed: Rc<Cell<bool>>,
+    inbox: SourceInbox,
+    // Callbacks must live as long as the socket
+    on_message: Option<Closure<dyn FnMut(MessageEvent)>>,
+    on_close: Option<Closure<dyn FnMut(JsValue)>>,
+}
+
+impl SocketSource {
+    pub fn new(url: &str, retry: u64) -> Self {
+        Self {
+            url: url.to_string(),
+            retry,
+            retry_at: 0,
+            socket: None,
+            closed: Rc::new(Cell::new(true)),
+            inbox: SourceInboxProto::new(),
+            on_message: None,
+            on_close: None,
+        }
+    }
+
+    fn connect(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            let _ = socket.close();
+        }
+        let socket = match WebSocket::new(&self.url) {
+            Ok(socket) => socket,
+            Err(err) => {
+                log!("Failed to open {}: {:?}", self.url, err);
+                return;
+            },
+        };
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let inbox = self.inbox.clone();
+        let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+            let data = event.data();
+            if let Some(text) = data.as_string() {
+                inbox.borrow_mut().push_text(&text);
+            } else if let Ok(buffer) = data.dyn_into::<js_sys::ArrayBuffer>() {
+                inbox.borrow_mut().push_binary(&js_sys::Uint8Array::new(&buffer).to_vec());
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        let closed = self.closed.clone();
+        let on_close = Closure::wrap(Box::new(move |_: JsValue| {
+            closed.set(true);
+        }) as Box<dyn FnMut(JsValue)>);
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        self.closed.set(false);
+        self.socket = Some(socket);
+        self.on_message = Some(on_message);
+        self.on_close = Some(on_close);
+    }
+}
+
+impl DataSource for SocketSource {
+    fn get_name(&self) -> &str {
+        "websocket"
+    }
+
+    fn poll(&mut self, now: u64) -> Vec<SourceEvent> {
+        if self.closed.get() && now >= self.retry_at {
+            self.retry_at = now + self.retry;
+            self.connect();
+        }
+        self.inbox.borrow_mut().take()
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GodsnodeHealth {
+    Healthy,
+    Degraded,
+    Down,
+    Unknown,
+    Maintenance,
+}
+
+impl GodsnodeHealth {
+    pub fn parse(name: &str) -> Option<GodsnodeHealth> {
+        match name.to_lowercase().as_str() {
+            "healthy" => Some(GodsnodeHealth::Healthy),
+            "degraded" => Some(GodsnodeHealth::Degraded),
+            "down" => Some(GodsnodeHealth::Down),
+            "unknown" => Some(GodsnodeHealth::Unknown),
+            "maintenance" => Some(GodsnodeHealth::Maintenance),
+            _ => None,
+        }
+    }
+
+    // Higher is worse, maintenance is planned so it ranks right above healthy
+    pub fn severity(&self) -> u8 {
+        match self {
+            GodsnodeHealth::Healthy => 0,
+            GodsnodeHealth::Maintenance => 1,
+            GodsnodeHealth::Unknown => 2,
+            GodsnodeHealth::Degraded => 3,
+            GodsnodeHealth::Down => 4,
+        }
+    }
+
+    pub fn worst(self, other: GodsnodeHealth) -> GodsnodeHealth {
+        if other.severity() > self.severity() { other } else { self }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GodsnodeHealth::Healthy => "healthy",
+            GodsnodeHealth::Degraded => "degraded",
+            GodsnodeHealth::Down => "down",
+            GodsnodeHealth::Unknown => "unknown",
+            GodsnodeHealth::Maintenance => "maintenance",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GodsnodeStatus {
+    pub health: GodsnodeHealth,
+    // Milliseconds since epoch of the last health change
+    pub changed_at: u64,
+    pub reason: String,
+}
+
+impl GodsnodeStatus {
+    pub fn new() -> Self {
+        Self {
+            health: GodsnodeHealth::Unknown,
+            changed_at: 0,
+            reason: String::new(),
+        }
+    }
+
+    // Returns whether anything changed, the timestamp only moves with the health
+    pub fn update(&mut self, health: GodsnodeHealth, reason: &str, at: u64) -> bool {
+        if self.health == health && self.reason == reason {
+            return false;
+        }
+        if self.health != health {
+            self.changed_at = at;
+        }
+        self.health = health;
+        self.reason = reason.to_string();
+        true
+    }
+}
+
+// How a node derives its health from its children
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GodsnodeRollup {
+    // Worst health among the children
+    Worst,
+    // Health shared by most children, ties go to the worse one
+    Majority,
+    // Healthy with at least N healthy children, degraded with fewer, else the worst of the children
+    AtLeast(usize),
+}
+
+impl GodsnodeRollup {
+    // Parse `worst`, `majority` or `at_least:N`
+    pub fn parse(name: &str) -> Option<GodsnodeRollup> {
+        match name {
+            "worst" => Some(GodsnodeRollup::Worst),
+            "majority" => Some(GodsnodeRollup::Majority),
+            _ if name.starts_with("at_least:") => {
+                name["at_least:".len()..].parse().ok().map(GodsnodeRollup::AtLeast)
+            },
+            _ => None,
+        }
+    }
+
+    pub fn aggregate(&self, kids: &[GodsnodeHealth]) -> Option<GodsnodeHealth> {
+        if kids.is_empty() {
+            return None;
+        }
+        let worst = kids.iter().fold(GodsnodeHealth::Healthy, |worst, kid| worst.worst(*kid));
+        let health = match self {
+            GodsnodeRollup::Worst => worst,
+            GodsnodeRollup::Majority => {
+                let mut best: Option<(usize, GodsnodeHealth)> = None;
+                for health in kids.iter() {
+                    let count = kids.iter().filter(|kid| *kid == health).count();
+                    best = match best {
+                        Some((max, top)) if max > count || (max == count && top.severity() >= health.severity()) => Some((max, top)),
+                        _ => Some((count, *health)),
+                    };
+                }
+                best.unwrap().1
+            },
+            GodsnodeRollup::AtLeast(count) => {
+                let healthy = kids.iter().filter(|kid| **kid == GodsnodeHealth::Healthy).count();
+                if healthy >= *count {
+                    GodsnodeHealth::Healthy
+                } else if healthy > 0 {
+                    GodsnodeHealth::Degraded
+                } else {
+                    worst
+                }
+            },
+        };
+        Some(health)
+    }
+}
+
+impl fmt::Display for GodsnodeRollup {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            GodsnodeRollup::Worst => fmt.write_str("worst"),
+            GodsnodeRollup::Majority => fmt.write_str("majority"),
+            GodsnodeRollup::AtLeast(count) => write!(fmt, "at_least:{}", count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use GodsnodeHealth::*;
+
+    #[test]
+    fn updates_status() {
+        let mut status = GodsnodeStatus::new();
+        assert_eq!(status.health, Unknown);
+        assert!(status.update(Down, "crashed", 10));
+        assert!(!status.update(Down, "crashed", 20));
+        assert!(status.update(Down, "restarting", 30));
+        assert_eq!((status.health, status.changed_at, status.reason.as_str()), (Down, 10, "restarting"));
+        assert!(status.update(Healthy, "", 40));
+        assert_eq!(status.changed_at, 40);
+    }
+
+    #[test]
+    fn parses_health() {
+        for health in [Healthy, Degraded, Down, Unknown, Maintenance].iter() {
+            assert_eq!(GodsnodeHealth::parse(health.as_str()), Some(*health));
+        }
+        assert_eq!(GodsnodeHealth::parse("Down"), Some(Down));
+        assert_eq!(GodsnodeHealth::parse("sleepy"), None);
+    }
+
+    #[test]
+    fn ranks_health() {
+        assert_eq!(Healthy.worst(Maintenance), Maintenance);
+        assert_eq!(Down.worst(Degraded), Down);
+        assert_eq!(Unknown.worst(Degraded), Degraded);
+    }
+
+    #[test]
+    fn parses_rollups() {
+        for rollup in ["worst", "majority", "at_least:2"].iter() {
+            assert_eq!(GodsnodeRollup::parse(rollup).unwrap().to_string(), *rollup);
+        }
+        assert_eq!(GodsnodeRollup::parse("at_least:"), None);
+        assert_eq!(GodsnodeRollup::parse("best"), None);
+    }
+
+    #[test]
+    fn aggregates_children() {
+        let kids = [Healthy, Down, Degraded, Degraded];
+        assert_eq!(GodsnodeRollup::Worst.aggregate(&[]), None);
+        assert_eq!(GodsnodeRollup::Worst.aggregate(&kids), Some(Down));
+        assert_eq!(GodsnodeRollup::Majority.aggregate(&kids), Some(Degraded));
+        // Ties go to the worse health
+        assert_eq!(GodsnodeRollup::Majority.aggregate(&[Healthy, Down]), Some(Down));
+        assert_eq!(GodsnodeRollup::AtLeast(1).aggregate(&kids), Some(Healthy));
+        assert_eq!(GodsnodeRollup::AtLeast(2).aggregate(&kids), Some(Degraded));
+        assert_eq!(GodsnodeRollup::AtLeast(2).aggregate(&[Down, Unknown]), Some(Down));
+    }
+}
@@ -0,0 +1,64 @@
+use std::rc::Rc;
+use std::collections::HashMap;
+use dragon::ecs::*;
+use crate::utils::timestamp_ms;
+use crate::span::godswood::node::{Godsnode, StoreOps};
+use crate::span::godswood::tree::Godswoods;
+use crate::span::godswood::alert::{AlertSeverity, Alerts};
+use crate::span::godswood::rule::RuleEngine;
+
+// Evaluates the alert rules every tick, and marks nodes with their most severe active alert
+pub struct AlertSystem {
+    woods: Godswoods,
+    alerts: Alerts,
+    engine: RuleEngine,
+    // Alerts and woods revisions the nodes were last marked at
+    marked: Option<(u64, u64)>,
+}
+
+impl AlertSystem {
+    pub fn new(woods: Godswoods, alerts: Alerts, engine: RuleEngine) -> Self {
+        Self {
+            woods,
+            alerts,
+            engine,
+            marked: None,
+        }
+    }
+
+    fn mark_nodes(&self) {
+        let alerts = self.alerts.borrow();
+        let mut worst: HashMap<&str, AlertSeverity> = HashMap::new();
+        for alert in alerts.get_active().into_iter() {
+            let severity = worst.entry(alert.path.as_str()).or_insert(alert.severity);
+            *severity = (*severity).max(alert.severity);
+        }
+
+        // Shared nodes take the worst alert across their paths
+        let mut nodes: HashMap<u64, (Rc<Godsnode>, Option<AlertSeverity>)> = HashMap::new();
+        for (path, node) in self.woods.store.read_paths(|_| true).into_iter() {
+            if let Some(node) = node.upgrade() {
+                let id = node.borrow().id;
+                let severity = worst.get(path.as_str()).cloned();
+                let entry = nodes.entry(id).or_insert((node, None));
+                entry.1 = entry.1.max(severity);
+            }
+        }
+        for (node, severity) in nodes.values() {
+            self.woods.store.set_alerting(node, *severity);
+        }
+    }
+}
+
+impl System for AlertSystem {
+    fn tick(&mut self) {
+        let now = timestamp_ms();
+        self.engine.borrow_mut().evaluate(&self.woods.store, &mut self.alerts.borrow_mut(), now);
+
+        let revisions = (self.alerts.borrow().revision(), self.woods.revision());
+        if self.marked != Some(revisions) {
+            self.marked = Some(revisions);
+            self.mark_nodes();
+        }
+    }
+}
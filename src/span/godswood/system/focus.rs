@@ -0,0 +1,156 @@
+use std::rc::Rc;
+use dragon::ecs::*;
+use dragon::core::*;
+use crate::span::godswood::node::{Godsnode, StoreOps};
+use crate::span::godswood::tree::Godswoods;
+use crate::span::godswood::select::Selection;
+use crate::span::godswood::control::CameraControl;
+use crate::span::godswood::component::GodsnodeComponent;
+use crate::span::godswood::pick::FOV;
+
+// Closest the camera gets to a focused node
+const FOCUS_DISTANCE: f32 = 60.;
+// Room kept around the framed nodes, wider than any node shape
+const FRAME_MARGIN: f32 = 12.;
+const FLIGHT_TICKS: u32 = 45;
+// Camera moved by anything else than the flight by more than this cancels it
+const MANUAL_EPSILON: f32 = 1e-3;
+
+// Slow start and slow end, `t` within 0 to 1
+fn ease_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        4. * t * t * t
+    } else {
+        1. - (-2. * t + 2.).powi(3) / 2.
+    }
+}
+
+// Camera move in progress, `last` is where the flight put the camera on the previous tick
+struct Flight {
+    from: Vector3<f32>,
+    to: Vector3<f32>,
+    tick: u32,
+    last: (Vector3<f32>, UnitQuaternion<f32>),
+}
+
+// Flies the camera to frame the node selected for focus and its direct children, keeping its
+// orientation, any other move of the camera during the flight cancels it
+pub struct FocusSystem {
+    state: Rc<WorldState>,
+    woods: Godswoods,
+    selection: Selection,
+    control: CameraControl,
+    flight: Option<Flight>,
+}
+
+impl FocusSystem {
+    pub fn new(state: Rc<WorldState>, woods: Godswoods, selection: Selection, control: CameraControl) -> Self {
+        Self {
+            state,
+            woods,
+            selection,
+            control,
+            flight: None,
+        }
+    }
+
+    // Center and radius of the sphere around the node and its direct children
+    fn frame(&self, node: &Rc<Godsnode>) -> Option<(Vector3<f32>, f32)> {
+        let c_store = self.state.component_store.borrow();
+        let nodes = c_store.get::<GodsnodeComponent>();
+        let transforms = c_store.get::<TransformComponent>();
+        let position = |node: &Rc<Godsnode>| nodes.iter().find(|(_, component)| Rc::ptr_eq(&component.node, node))
+            .and_then(|(entity, _)| transforms.get(entity))
+            .map(|transform| *transform.translation());
+
+        let mut points = vec![position(node)?];
+        for child in node.borrow().get_children().iter().filter_map(|child| child.upgrade()) {
+            points.extend(position(&child));
+        }
+        let center = points.iter().fold(Vector3::zeros(), |sum, point| sum + point) / points.len() as f32;
+        let radius = points.iter().map(|point| (point - center).norm()).fold(0., f32::max);
+        Some((center, radius + FRAME_MARGIN))
+    }
+
+    fn start(&mut self, path: &str) {
+        let node = match self.woods.store.get_node(path) {
+            Some(node) => node,
+            None => {
+                log!("Cannot focus unknown node {}", path);
+                return;
+            },
+        };
+        let (center, radius) = match self.frame(&node) {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        let c_store = self.state.component_store.borrow();
+        let transforms = c_store.get::<TransformComponent>();
+        if let Some(camera) = transforms.get(&self.state.active_camera.get()) {
+            // Far enough for the framing sphere to fit the view
+            let distance = (radius / (FOV / 2.).sin()).max(FOCUS_DISTANCE);
+            self.flight = Some(Flight {
+                from: *camera.translation(),
+                to: center + camera.rotation() * Vector3::new(0., 0., distance),
+                tick: 0,
+                last: (*camera.translation(), *camera.rotation()),
+            });
+            // Dragging then orbits around the framed nodes
+            self.control.borrow_mut().set_target(center);
+        }
+    }
+
+    fn fly(&mut self) {
+        let flight = match self.flight.as_mut() {
+            Some(flight) => flight,
+            None => return,
+        };
+        let c_store = self.state.component_store.borrow();
+        let mut transforms = c_store.get_mut::<TransformComponent>();
+        let camera = match transforms.get_mut(&self.state.active_camera.get()) {
+            Some(camera) => camera,
+            None => return,
+        };
+        let (last_translation, last_rotation) = flight.last;
+        if (camera.translation() - last_translation).norm() > MANUAL_EPSILON
+            || camera.rotation().angle_to(&last_rotation) > MANUAL_EPSILON {
+            self.flight = None;
+            return;
+        }
+
+        flight.tick += 1;
+        let t = ease_in_out(flight.tick as f32 / FLIGHT_TICKS as f32);
+        let pos = flight.from.lerp(&flight.to, t);
+        camera.set_translation_xyz(pos.x, pos.y, pos.z);
+        flight.last = (*camera.translation(), *camera.rotation());
+        if flight.tick >= FLIGHT_TICKS {
+            self.flight = None;
+        }
+    }
+}
+
+impl System for FocusSystem {
+    fn tick(&mut self) {
+        // A new focus takes over the flight in progress from where the camera is
+        let path = self.selection.borrow_mut().take_focus();
+        if let Some(path) = path {
+            self.start(&path);
+        }
+        self.fly();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eases_flights() {
+        assert_eq!(ease_in_out(0.), 0.);
+        assert_eq!(ease_in_out(0.5), 0.5);
+        assert_eq!(ease_in_out(1.), 1.);
+        assert!(ease_in_out(0.1) < 0.1 && ease_in_out(0.9) > 0.9);
+        assert!((ease_in_out(0.25) + ease_in_out(0.75) - 1.).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,45 @@
+use std::rc::Rc;
+use std::collections::HashSet;
+use dragon::ecs::*;
+use crate::span::godswood::node::StoreOps;
+use crate::span::godswood::tree::Godswoods;
+use crate::span::godswood::component::GodsnodeComponent;
+use crate::span::godswood::system::topology::{node_mesh, node_widget};
+
+// Redraws the sphere colours and label of nodes whose status changed
+pub struct HealthSystem {
+    state: Rc<WorldState>,
+    woods: Godswoods,
+}
+
+impl HealthSystem {
+    pub fn new(state: Rc<WorldState>, woods: Godswoods) -> Self {
+        Self {
+            state,
+            woods,
+        }
+    }
+}
+
+impl System for HealthSystem {
+    fn tick(&mut self) {
+        let changes: HashSet<u64> = self.woods.store.take_status_changes().into_iter().collect();
+        if changes.is_empty() {
+            return;
+        }
+
+        let targets: Vec<_> = {
+            let c_store = self.state.component_store.borrow();
+            let nodes = c_store.get::<GodsnodeComponent>();
+            nodes.iter().filter(|(_, component)| {
+                changes.contains(&component.node.borrow().id)
+            }).map(|(entity, component)| (*entity, component.node.clone())).collect()
+        };
+
+        for (entity, node) in targets.into_iter() {
+            let node = node.borrow();
+            self.state.bind_component(entity, node_mesh(&node));
+            self.state.bind_component(entity, node_widget(&node));
+        }
+    }
+}
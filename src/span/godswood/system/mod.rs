@@ -1,2 +1,12 @@
 
 pub mod movement;
+pub mod orbit;
+pub mod topology;
+pub mod focus;
+pub mod pick;
+pub mod health;
+pub mod persist;
+pub mod source;
+pub mod alert;
+pub mod worklog;
+pub mod sparkline;
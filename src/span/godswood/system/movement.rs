@@ -2,23 +2,27 @@ use std::rc::Rc;
 use dragon::ecs::*;
 use dragon::core::*;
 use wand::input::Input;
+use crate::span::godswood::persist::SharedPreferences;
 
 pub struct MovementSystem {
     state: Rc<WorldState>,
     input: Input,
+    preferences: SharedPreferences,
 }
 
 impl MovementSystem {
-    pub fn new(state: Rc<WorldState>, input: Input) -> Self {
+    pub fn new(state: Rc<WorldState>, input: Input, preferences: SharedPreferences) -> Self {
         Self {
             state,
             input,
+            preferences,
         }
     }
 }
 
 impl System for MovementSystem {
     fn tick(&mut self) {
+        let preferences = *self.preferences.borrow();
         let c_store = self.state.component_store.borrow();
         let meshes = c_store.get::<MeshComponent>();
         let mut transforms = c_store.get_mut::<TransformComponent>();
@@ -27,14 +31,14 @@ impl System for MovementSystem {
         }) {
             transform.append_rotation(
                 Vector3::y_axis(),
-                self.input.borrow_mut().axis("a", "d") * 0.1
+                self.input.borrow_mut().axis("a", "d") * preferences.turn_speed
             );
             transform.append_rotation(
                 Vector3::x_axis(),
-                self.input.borrow_mut().axis("w", "s") * 0.1
+                self.input.borrow_mut().axis("w", "s") * preferences.turn_speed
             );
             transform.prepend_translation(
-                Vector3::new(0., 0., self.input.borrow_mut().axis("v", "b") * 0.6)
+                Vector3::new(0., 0., self.input.borrow_mut().axis("v", "b") * preferences.move_speed)
             );
         }
         
@@ -42,14 +46,14 @@ impl System for MovementSystem {
         let camera = transforms.get_mut(&active_camera).unwrap();
         camera.append_rotation(
             Vector3::y_axis(),
-            self.input.borrow_mut().axis("ArrowLeft", "ArrowRight") * 0.1
+            self.input.borrow_mut().axis("ArrowLeft", "ArrowRight") * preferences.turn_speed
         );
         camera.append_rotation(
             Vector3::x_axis(),
-            self.input.borrow_mut().axis("ArrowUp", "ArrowDown") * 0.1
+            self.input.borrow_mut().axis("ArrowUp", "ArrowDown") * preferences.turn_speed
         );
         camera.append_translation(
-            Vector3::new(0., 0., self.input.borrow_mut().axis("z", "x") * 0.6)
+            Vector3::new(0., 0., self.input.borrow_mut().axis("z", "x") * preferences.move_speed)
         );
 
         camera.append_translation(
@@ -0,0 +1,112 @@
+use std::rc::Rc;
+use std::f32::consts::PI;
+use dragon::ecs::*;
+use dragon::core::*;
+use crate::span::godswood::control::{CameraControl, CameraInput};
+use crate::span::godswood::pick::{Ray, FOV};
+
+// Radians turned by dragging across the whole world
+const ORBIT_SPEED: f32 = PI;
+// Camera never looks straight up or down, so that it cannot flip over the target
+const MAX_PITCH: f32 = 85. * PI / 180.;
+// Share of the distance to the target moved per wheel pixel
+const ZOOM_SPEED: f32 = 0.001;
+const MIN_DISTANCE: f32 = 10.;
+const MAX_DISTANCE: f32 = 2000.;
+
+// Orbits the camera around a target point in front of it by dragging, pans it with the target, and
+// zooms toward the pointer with the wheel, next to the keyboard moves
+pub struct OrbitSystem {
+    state: Rc<WorldState>,
+    control: CameraControl,
+    // Point the camera orbits around and pans with
+    target: Vector3<f32>,
+}
+
+impl OrbitSystem {
+    pub fn new(state: Rc<WorldState>, control: CameraControl) -> Self {
+        Self {
+            state,
+            control,
+            target: Vector3::zeros(),
+        }
+    }
+
+    // Target put back in front of the camera at its distance along the view, as other moves may
+    // have turned the camera away from it
+    fn anchor(&mut self, camera: &TransformComponent) -> f32 {
+        let forward = camera.rotation() * Vector3::new(0., 0., -1.);
+        let distance = (self.target - camera.translation()).dot(&forward).clamp(MIN_DISTANCE, MAX_DISTANCE);
+        self.target = camera.translation() + forward * distance;
+        distance
+    }
+
+    fn orbit(&self, camera: &mut TransformComponent, distance: f32, dx: f32, dy: f32) {
+        let offset = camera.translation() - self.target;
+        let yaw = offset.x.atan2(offset.z) - dx * ORBIT_SPEED;
+        let pitch = ((offset.y / distance).clamp(-1., 1.).asin() - dy * ORBIT_SPEED).clamp(-MAX_PITCH, MAX_PITCH);
+        // Local +z points from the target to the camera, which looks down its -z
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw)
+            * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), -pitch);
+        let pos = self.target + rotation * Vector3::new(0., 0., distance);
+        camera.set_rotation(rotation);
+        camera.set_translation_xyz(pos.x, pos.y, pos.z);
+    }
+
+    // Moves the camera and the target so that the world follows the pointer
+    fn pan(&mut self, camera: &mut TransformComponent, distance: f32, dx: f32, dy: f32, aspect: f32) {
+        let scale = (FOV / 2.).tan() * distance;
+        let shift = camera.rotation() * Vector3::new(-dx * aspect * scale, -dy * scale, 0.);
+        self.target += shift;
+        let pos = camera.translation() + shift;
+        camera.set_translation_xyz(pos.x, pos.y, pos.z);
+    }
+
+    // Moves the camera along the ray through the pointer, the target slides sideways with it to stay
+    // in front of the camera
+    fn zoom(&mut self, camera: &mut TransformComponent, distance: f32, input: &CameraInput) {
+        let (x, y) = input.pointer;
+        let ray = Ray::from_camera(camera.translation(), camera.rotation(), x, y, input.aspect);
+        let forward = camera.rotation() * Vector3::new(0., 0., -1.);
+        let along = ray.dir.dot(&forward);
+        let wanted = (distance * (1. - input.zoom * ZOOM_SPEED)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+        let step = (distance - wanted) / along;
+        let shift = ray.dir * step;
+        self.target += shift - forward * shift.dot(&forward);
+        let pos = camera.translation() + shift;
+        camera.set_translation_xyz(pos.x, pos.y, pos.z);
+    }
+}
+
+impl System for OrbitSystem {
+    fn tick(&mut self) {
+        let (input, target) = {
+            let mut control = self.control.borrow_mut();
+            (control.take_input(), control.take_target())
+        };
+        if let Some(target) = target {
+            self.target = target;
+        }
+        if input == CameraInput::default() {
+            return;
+        }
+
+        let state = self.state.clone();
+        let c_store = state.component_store.borrow();
+        let mut transforms = c_store.get_mut::<TransformComponent>();
+        let camera = match transforms.get_mut(&state.active_camera.get()) {
+            Some(camera) => camera,
+            None => return,
+        };
+        let distance = self.anchor(camera);
+        if input.orbit != (0., 0.) {
+            self.orbit(camera, distance, input.orbit.0, input.orbit.1);
+        }
+        if input.pan != (0., 0.) {
+            self.pan(camera, distance, input.pan.0, input.pan.1, input.aspect);
+        }
+        if input.zoom != 0. {
+            self.zoom(camera, distance, &input);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::rc::Rc;
+use dragon::ecs::*;
+use crate::span::godswood::tree::Godswoods;
+use crate::span::godswood::persist::{self, CameraPose, Preferences, SharedPreferences, Snapshot};
+
+// Ticks between two checks for unsaved changes
+const SAVE_INTERVAL: usize = 60;
+
+// Saves a snapshot to local storage whenever the woods, the camera or the preferences changed
+pub struct PersistSystem {
+    state: Rc<WorldState>,
+    woods: Godswoods,
+    preferences: SharedPreferences,
+    saved: Option<(u64, Option<CameraPose>, Preferences)>,
+    ticks: usize,
+}
+
+impl PersistSystem {
+    pub fn new(state: Rc<WorldState>, woods: Godswoods, preferences: SharedPreferences) -> Self {
+        Self {
+            state,
+            woods,
+            preferences,
+            saved: None,
+            ticks: 0,
+        }
+    }
+
+    fn snapshot(&self, camera: Option<CameraPose>, preferences: Preferences) -> Snapshot {
+        let woods = self.woods.woods.borrow();
+        let mut names: Vec<&String> = woods.keys().collect();
+        names.sort();
+        Snapshot {
+            woods: names.iter().map(|name| woods.get(*name).unwrap().borrow().wood.to_json()).collect(),
+            camera,
+            preferences,
+        }
+    }
+}
+
+impl System for PersistSystem {
+    fn tick(&mut self) {
+        self.ticks += 1;
+        if !self.ticks.is_multiple_of(SAVE_INTERVAL) {
+            return;
+        }
+
+        let current = (self.woods.revision(), persist::read_camera(&self.state), *self.preferences.borrow());
+        if self.saved == Some(current) {
+            return;
+        }
+        persist::save(&self.snapshot(current.1, current.2));
+        self.saved = Some(current);
+    }
+}
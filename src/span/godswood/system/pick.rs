@@ -0,0 +1,117 @@
+use std::rc::Rc;
+use dragon::ecs::*;
+use dragon::core;
+use crate::utils::timestamp_ms;
+use crate::span::godswood::node::{Godsnode, StoreOps};
+use crate::span::godswood::tree::Godswoods;
+use crate::span::godswood::select::Selection;
+use crate::span::godswood::component::GodsnodeComponent;
+use crate::span::godswood::pick::Ray;
+use crate::span::godswood::system::topology::class_brushes;
+
+// Hovered node is kept while the pointer slips off it for less than this, so that it does not
+// flicker on sphere edges or while the camera moves
+const HOVER_GRACE_MS: u64 = 200;
+
+// Resolves picks requested by the pointer to the nearest node under it, tracks the node hovered
+// by the pointer, and highlights the selected node
+pub struct PickSystem {
+    state: Rc<WorldState>,
+    woods: Godswoods,
+    selection: Selection,
+    // Selection and woods revisions the nodes were last highlighted at
+    marked: Option<(u64, u64)>,
+    // When the pointer slipped off the hovered node
+    missed_at: Option<u64>,
+}
+
+impl PickSystem {
+    pub fn new(state: Rc<WorldState>, woods: Godswoods, selection: Selection) -> Self {
+        Self {
+            state,
+            woods,
+            selection,
+            marked: None,
+            missed_at: None,
+        }
+    }
+
+    // Node with the sphere first hit by the ray
+    fn pick(&self, x: f32, y: f32, aspect: f32) -> Option<Rc<Godsnode>> {
+        let c_store = self.state.component_store.borrow();
+        let nodes = c_store.get::<GodsnodeComponent>();
+        let transforms = c_store.get::<TransformComponent>();
+        let camera = transforms.get(&self.state.active_camera.get())?;
+        let ray = Ray::from_camera(camera.translation(), camera.rotation(), x, y, aspect);
+
+        let mut nearest: Option<(f32, &Rc<Godsnode>)> = None;
+        for (entity, component) in nodes.iter() {
+            let transform = match transforms.get(entity) {
+                Some(transform) => transform,
+                None => continue,
+            };
+            let origin = core::Point3::from(*transform.translation());
+            for brush in class_brushes(component.node.borrow().service_type).iter() {
+                if let core::Brush::Sphere { center, radius, .. } = brush {
+                    let center = origin + transform.rotation() * center.coords;
+                    match ray.intersect_sphere(&center, *radius) {
+                        Some(distance) if nearest.is_none_or(|(best, _)| distance < best) => {
+                            nearest = Some((distance, &component.node));
+                        },
+                        _ => {},
+                    }
+                }
+            }
+        }
+        nearest.map(|(_, node)| node.clone())
+    }
+
+    // Picked every tick as the camera moves under a resting pointer too
+    fn hover(&mut self, now: u64) {
+        let pointer = self.selection.borrow().get_pointer();
+        let path = pointer.and_then(|(x, y, aspect)| self.pick(x, y, aspect)).and_then(|node| node.borrow().get_path());
+        if path.is_some() || pointer.is_none() {
+            self.missed_at = None;
+            self.selection.borrow_mut().set_hovered(path, now);
+            return;
+        }
+        if self.selection.borrow().get_hovered().is_none() {
+            return;
+        }
+        let missed_at = *self.missed_at.get_or_insert(now);
+        if now.saturating_sub(missed_at) >= HOVER_GRACE_MS {
+            self.missed_at = None;
+            self.selection.borrow_mut().set_hovered(None, now);
+        }
+    }
+
+    fn highlight(&self) {
+        let selected = self.selection.borrow().get_selected().and_then(|path| self.woods.store.get_node(path));
+        for (_, node) in self.woods.store.read_paths(|_| true).into_iter() {
+            if let Some(node) = node.upgrade() {
+                let is_selected = selected.as_ref().is_some_and(|selected| Rc::ptr_eq(selected, &node));
+                self.woods.store.set_selected(&node, is_selected);
+            }
+        }
+    }
+}
+
+impl System for PickSystem {
+    fn tick(&mut self) {
+        let pick = self.selection.borrow_mut().take_pick();
+        if let Some((x, y, aspect)) = pick {
+            // Picked nodes are focused, picking empty space drops the selection
+            match self.pick(x, y, aspect).and_then(|node| node.borrow().get_path()) {
+                Some(path) => self.selection.borrow_mut().select(&path, true),
+                None => self.selection.borrow_mut().clear(),
+            }
+        }
+        self.hover(timestamp_ms());
+
+        let revisions = (self.selection.borrow().revision(), self.woods.revision());
+        if self.marked != Some(revisions) {
+            self.marked = Some(revisions);
+            self.highlight();
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use serde_json::Value;
+use dragon::ecs::*;
+use crate::utils::{timestamp_ms, JsonParser};
+use crate::span::godswood::tree::Godswoods;
+use crate::span::godswood::source::{DataSource, SourceEvent};
+use crate::span::godswood::record::Recorder;
+use crate::span::godswood::alert::{Alert, Alerts};
+
+// Polls the data source every tick, records its updates and applies them to the woods and alerts
+// While a replay runs, live updates are only recorded and the woods follow the recording
+pub struct SourceSystem {
+    woods: Godswoods,
+    source: Box<dyn DataSource>,
+    recorder: Recorder,
+    alerts: Alerts,
+    // Last topology applied per wood, sources resending the same wood leave it untouched
+    topologies: HashMap<String, Value>,
+    started: bool,
+}
+
+impl SourceSystem {
+    pub fn new(woods: Godswoods, source: Box<dyn DataSource>, recorder: Recorder, alerts: Alerts) -> Self {
+        log!("Using data source {}", source.get_name());
+        Self {
+            woods,
+            source,
+            recorder,
+            alerts,
+            topologies: HashMap::new(),
+            started: false,
+        }
+    }
+
+    fn apply(&mut self, event: &SourceEvent, now: u64) {
+        match event {
+            SourceEvent::Topology(raw) => {
+                let name = raw.get_str("name", "");
+                if self.topologies.get(&name) == Some(raw) {
+                    return;
+                }
+                self.topologies.insert(name, raw.clone());
+            },
+            SourceEvent::Alert { key, severity, path, message, at } => {
+                self.alerts.borrow_mut().raise(Alert {
+                    key: key.clone(),
+                    severity: *severity,
+                    path: path.clone(),
+                    message: message.clone(),
+                    since: at.unwrap_or(now),
+                });
+            },
+            SourceEvent::Resolve { key, .. } => {
+                self.alerts.borrow_mut().resolve(key);
+            },
+            SourceEvent::Reset => {
+                self.topologies.clear();
+                self.alerts.borrow_mut().clear();
+            },
+            _ => {},
+        }
+        self.woods.apply_event(event, now);
+    }
+}
+
+impl System for SourceSystem {
+    fn tick(&mut self) {
+        let now = timestamp_ms();
+        let mut recorder = self.recorder.borrow_mut();
+
+        // Woods restored before the first update open the recording
+        if !self.started {
+            self.started = true;
+            for wood in self.woods.woods.borrow().values() {
+                recorder.record(now, SourceEvent::Topology(wood.borrow().wood.to_json()));
+            }
+        }
+
+        let replaying = recorder.is_replaying();
+        let mut events = Vec::new();
+        for event in self.source.poll(now).iter() {
+            let event = event.stamped(now);
+            recorder.record(now, event.clone());
+            if !replaying {
+                events.push(event);
+            }
+        }
+        if let Some(catch_up) = recorder.take_catch_up() {
+            events.extend(catch_up);
+        }
+        events.extend(recorder.poll_replay(now));
+        drop(recorder);
+
+        for event in events.iter() {
+            self.apply(event, now);
+        }
+    }
+}
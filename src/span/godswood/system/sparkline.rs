@@ -0,0 +1,91 @@
+use std::rc::Rc;
+use dragon::ecs::*;
+use dragon::core;
+use crate::utils::timestamp_ms;
+use crate::span::godswood::node::GodsnodeProto;
+use crate::span::godswood::component::GodsnodeComponent;
+use crate::span::godswood::series::MetricSeries;
+use crate::span::godswood::shapes::{ShapeLayers, ShapeLine};
+
+// Sparklines are redrawn every this many ticks
+const REDRAW_TICKS: u64 = 30;
+const WINDOW_MS: u64 = 5 * 60_000;
+const POINTS: usize = 30;
+const WIDTH: f32 = 20.;
+const HEIGHT: f32 = 6.;
+// Bottom left corner relative to the node, beside its label
+const OFFSET: (f32, f32) = (20., 12.);
+
+// Metric charted for the node, named by its `sparkline` attribute or the first of its metrics
+fn sparkline_metric(node: &GodsnodeProto) -> Option<&MetricSeries> {
+    match node.get_attribute("sparkline") {
+        Some(metric) => node.series.get(metric),
+        None => node.series.iter().min_by(|a, b| a.0.cmp(b.0)).map(|(_, series)| series),
+    }
+}
+
+// Line segments of the recent trend of the series, scaled to its own range
+fn sparkline(series: &MetricSeries, origin: &core::Vector3<f32>, now: u64) -> Vec<ShapeLine> {
+    let since = now.saturating_sub(WINDOW_MS);
+    let samples = series.get_samples(since, POINTS);
+    if samples.len() < 2 {
+        return Vec::new();
+    }
+    let min = samples.iter().map(|sample| sample.avg()).fold(f64::INFINITY, f64::min);
+    let max = samples.iter().map(|sample| sample.avg()).fold(f64::NEG_INFINITY, f64::max);
+    let points: Vec<core::Point3<f32>> = samples.iter().map(|sample| {
+        let x = (sample.at.saturating_sub(since)) as f32 / WINDOW_MS as f32;
+        // Flat series sit in the middle
+        let y = if max > min { ((sample.avg() - min) / (max - min)) as f32 } else { 0.5 };
+        core::Point3::new(origin.x + OFFSET.0 + x * WIDTH, origin.y + OFFSET.1 + y * HEIGHT, origin.z)
+    }).collect();
+    points.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+// Draws a small trend line of a metric next to every node having one
+pub struct SparklineSystem {
+    state: Rc<WorldState>,
+    shapes: ShapeLayers,
+    ticks: u64,
+}
+
+impl SparklineSystem {
+    pub fn new(state: Rc<WorldState>, shapes: ShapeLayers) -> Self {
+        Self {
+            state,
+            shapes,
+            ticks: 0,
+        }
+    }
+}
+
+impl System for SparklineSystem {
+    fn tick(&mut self) {
+        self.ticks += 1;
+        if !self.ticks.is_multiple_of(REDRAW_TICKS) {
+            return;
+        }
+
+        let now = timestamp_ms();
+        let mut lines = Vec::new();
+        {
+            let c_store = self.state.component_store.borrow();
+            let nodes = c_store.get::<GodsnodeComponent>();
+            let transforms = c_store.get::<TransformComponent>();
+            for (entity, component) in nodes.iter() {
+                let transform = match transforms.get(entity) {
+                    Some(transform) => transform,
+                    None => continue,
+                };
+                let node = component.node.borrow();
+                if let Some(series) = sparkline_metric(&node) {
+                    lines.extend(sparkline(series, transform.translation(), now));
+                }
+            }
+        }
+
+        let mut shapes = self.shapes.borrow_mut();
+        shapes.set_layer("sparklines", lines);
+        shapes.flush(&self.state);
+    }
+}
@@ -0,0 +1,290 @@
+use std::rc::Rc;
+use std::collections::{VecDeque, HashMap};
+use std::f32::consts::PI;
+use dragon::ecs::*;
+use dragon::core;
+use crate::span::godswood::node::{Godsnode, GodsnodeProto, GodsnodeClass, StoreOps};
+use crate::span::godswood::status::GodsnodeHealth;
+use crate::span::godswood::tree::*;
+use crate::span::godswood::component::GodsnodeComponent;
+use crate::span::godswood::shapes::ShapeLayers;
+
+pub type GodsnodePosition = (f32, f32, f32);
+
+// Entity drawn for a node, with the label and class it was drawn with
+struct GodsnodeEntity {
+    entity: Entity,
+    label: String,
+    class: GodsnodeClass,
+}
+
+// Fill and stroke replacing the class colours for the health, healthy nodes keep their class colours
+pub fn health_colors(health: GodsnodeHealth) -> Option<(Option<&'static str>, &'static str)> {
+    match health {
+        GodsnodeHealth::Healthy => None,
+        GodsnodeHealth::Unknown => Some((Some("rgba(128, 128, 128, 0.1)"), "gray")),
+        GodsnodeHealth::Degraded => Some((Some("rgba(245, 164, 66, 0.3)"), "#f5a442")),
+        GodsnodeHealth::Down => Some((Some("rgba(255, 0, 0, 0.35)"), "red")),
+        GodsnodeHealth::Maintenance => Some((Some("rgba(66, 200, 245, 0.15)"), "#42c8f5")),
+    }
+}
+
+macro_rules! sphere {
+    ($fill: expr, $stroke: expr, ($x: expr, $y: expr, $z: expr), $radius: expr) => {
+        core::Brush::Sphere {
+            fill: $fill.map(|fill: &str| fill.to_string()),
+            stroke: Some($stroke.to_string()),
+            center: core::Point3::new($x, $y, $z),
+            radius: $radius,
+            action: 3,
+        }
+    }
+}
+
+// Halo drawn around the selected node, wider than any class shape
+const SELECTED_RADIUS: f32 = 10.;
+
+// Mesh of the node, shaped by its class and coloured by its health, with the outline of its most
+// severe alert and a halo when selected
+pub fn node_mesh(node: &GodsnodeProto) -> core::Mesh {
+    let mut mesh = core::ComplexMesh::new();
+    let mut brushes = class_brushes(node.service_type);
+    if let Some((health_fill, health_stroke)) = health_colors(node.status.health) {
+        for brush in brushes.iter_mut() {
+            if let core::Brush::Sphere { fill, stroke, .. } = brush {
+                // Hollow classes stay hollow
+                if fill.is_some() {
+                    *fill = health_fill.map(|color| color.to_string());
+                }
+                *stroke = Some(health_stroke.to_string());
+            }
+        }
+    }
+    if let Some(severity) = node.alerting {
+        for brush in brushes.iter_mut() {
+            if let core::Brush::Sphere { stroke, .. } = brush {
+                *stroke = Some(severity.color().to_string());
+            }
+        }
+    }
+    if node.selected {
+        brushes.push(sphere!(None, "white", (0., 0., 0.), SELECTED_RADIUS));
+    }
+    mesh.brushes.extend(brushes);
+    Box::new(mesh)
+}
+
+pub fn node_label(node: &GodsnodeProto) -> String {
+    match node.status.health {
+        GodsnodeHealth::Healthy => node.display_name.clone(),
+        health => format!("{} ({})", node.display_name, health.as_str()),
+    }
+}
+
+pub fn node_widget(node: &GodsnodeProto) -> WidgetComponent {
+    WidgetComponent::framed_text_widget(&node_label(node), 0., -12., 35., 9.)
+}
+
+// Class specific mesh, shapes are composed of spheres
+pub fn class_brushes(class: GodsnodeClass) -> Vec<core::Brush> {
+    match class {
+        GodsnodeClass::General => vec![
+            sphere!(Some("rgba(100, 100, 100, 0.2)"), "orange", (0., 0., 0.), 5.),
+        ],
+        // Stacked disks
+        GodsnodeClass::Database => vec![
+            sphere!(Some("rgba(66, 135, 245, 0.2)"), "#4287f5", (0., -3., 0.), 4.),
+            sphere!(Some("rgba(66, 135, 245, 0.2)"), "#4287f5", (0., 0., 0.), 4.),
+            sphere!(Some("rgba(66, 135, 245, 0.2)"), "#4287f5", (0., 3., 0.), 4.),
+        ],
+        // Hot core inside a shell
+        GodsnodeClass::Cache => vec![
+            sphere!(Some("rgba(235, 64, 52, 0.15)"), "#eb4034", (0., 0., 0.), 5.),
+            sphere!(Some("rgba(235, 64, 52, 0.5)"), "#eb4034", (0., 0., 0.), 2.),
+        ],
+        // Row of messages
+        GodsnodeClass::Queue => vec![
+            sphere!(Some("rgba(245, 197, 66, 0.2)"), "#f5c542", (-6., 0., 0.), 3.),
+            sphere!(Some("rgba(245, 197, 66, 0.2)"), "#f5c542", (0., 0., 0.), 3.),
+            sphere!(Some("rgba(245, 197, 66, 0.2)"), "#f5c542", (6., 0., 0.), 3.),
+        ],
+        GodsnodeClass::Gateway => vec![
+            sphere!(Some("rgba(66, 245, 129, 0.2)"), "#42f581", (0., 0., 0.), 7.),
+        ],
+        GodsnodeClass::Worker => vec![
+            sphere!(Some("rgba(171, 66, 245, 0.2)"), "#ab42f5", (-3., 0., 0.), 4.),
+            sphere!(Some("rgba(171, 66, 245, 0.2)"), "#ab42f5", (3., 0., 0.), 4.),
+        ],
+        // Hollow, it is not ours
+        GodsnodeClass::External => vec![
+            sphere!(None, "lightgray", (0., 0., 0.), 5.),
+        ],
+    }
+}
+
+// Keeps the ECS entities and edges in line with the woods, only nodes which appeared, vanished or
+// got a new label spawn or despawn entities, the others are moved in place
+pub struct TopologySystem {
+    state: Rc<WorldState>,
+    woods: Godswoods,
+    shapes: ShapeLayers,
+    entities: HashMap<u64, GodsnodeEntity>,
+}
+
+impl TopologySystem {
+    pub fn new(state: Rc<WorldState>, woods: Godswoods, shapes: ShapeLayers) -> Self {
+        Self {
+            state,
+            woods,
+            shapes,
+            entities: HashMap::new(),
+        }
+    }
+
+    fn create_node(&self, node: Rc<Godsnode>, pos: GodsnodePosition) -> Entity {
+        log!("new node{:?}", pos);
+        // Attach cube entity
+        let entity = self.state.create_entity();
+        let mut transform = TransformComponent::default();
+        transform.set_translation_xyz(pos.0, pos.1, pos.2);
+        let mesh = node_mesh(&node.borrow());
+        let widget = node_widget(&node.borrow());
+        self.state.bind_component(entity, mesh);
+        self.state.bind_component(entity, transform);
+        self.state.bind_component(entity, widget);
+        self.state.bind_component(entity, GodsnodeComponent { node });
+        entity
+    }
+
+    // Place nodes level by level, shared nodes are placed once below the first parent reaching them
+    fn layout(wood: &Godswood, origin: GodsnodePosition, positions: &mut HashMap<u64, GodsnodePosition>, drawn: &mut Vec<Rc<Godsnode>>) {
+        let mut nodes = VecDeque::new();
+        nodes.push_back((origin, wood.wood.get_root()));
+
+        loop {
+            let node = nodes.pop_front();
+            if node.is_none() {
+                break;
+            }
+
+            let ((x, y, z), node) = node.unwrap();
+            let node_arc = match node.upgrade() {
+                Some(node) => node,
+                None => continue,
+            };
+            let node = node_arc.borrow();
+            if positions.contains_key(&node.id) {
+                continue;
+            }
+            let depth = wood.wood.get_node_depth(node.id).unwrap_or(1);
+            let scale = wood.scales.get(&depth).unwrap_or(&1.0) * wood.base_scale;
+            positions.insert(node.id, (x, y, z));
+            drawn.push(node_arc.clone());
+
+            let children: Vec<_> = node.get_children().iter().filter_map(|kid| kid.upgrade()).filter(|kid| {
+                !positions.contains_key(&kid.borrow().id)
+            }).collect();
+            let size = children.len();
+            if size == 0 {
+                continue;
+            }
+
+            let angle = 2f32 * PI / size as f32;
+            for (i, kid) in children.iter().enumerate() {
+                let kid_depth = wood.wood.get_node_depth(kid.borrow().id).unwrap_or(depth + 1);
+                let kid_y = y - wood.base_gap * (kid_depth - depth) as f32;
+                if size == 1 {
+                    nodes.push_back(((x, kid_y, z), Rc::downgrade(kid)));
+                    continue;
+                }
+                let angle = angle * i as f32;
+                let kid_x = x - scale * angle.cos();
+                let kid_z = z - scale * angle.sin();
+                nodes.push_back(((kid_x, kid_y, kid_z), Rc::downgrade(kid)));
+            }
+        }
+    }
+
+    fn sync(&mut self) {
+        let mut positions: HashMap<u64, GodsnodePosition> = HashMap::new();
+        let mut drawn = Vec::new();
+        {
+            // Woods stand side by side in name order, so nodes shared across woods stay where the
+            // first wood placed them
+            let woods = self.woods.woods.borrow();
+            let mut names: Vec<&String> = woods.keys().collect();
+            names.sort();
+            let mut x = 0.0;
+            for name in names.iter() {
+                let wood = woods.get(*name).unwrap().borrow();
+                let radius = wood.radius();
+                TopologySystem::layout(&wood, (x + radius, 0.0, 0.), &mut positions, &mut drawn);
+                x += 2.0 * radius + wood.base_gap;
+            }
+        }
+
+        // Despawn nodes no longer in any wood, and nodes with a new label or class to be spawned again
+        let looks: HashMap<u64, (String, GodsnodeClass)> = drawn.iter().map(|node| {
+            let node = node.borrow();
+            (node.id, (node.display_name.clone(), node.service_type))
+        }).collect();
+        let stale: Vec<u64> = self.entities.iter().filter(|(id, drawn)| {
+            match looks.get(id) {
+                Some((label, class)) => drawn.label != *label || drawn.class != *class,
+                None => true,
+            }
+        }).map(|(id, _)| *id).collect();
+        for id in stale.iter() {
+            let drawn = self.entities.remove(id).unwrap();
+            self.state.remove_entity(drawn.entity);
+        }
+
+        {
+            let c_store = self.state.component_store.borrow();
+            let mut transforms = c_store.get_mut::<TransformComponent>();
+            for (id, drawn) in self.entities.iter() {
+                if let (Some(pos), Some(transform)) = (positions.get(id), transforms.get_mut(&drawn.entity)) {
+                    transform.set_translation_xyz(pos.0, pos.1, pos.2);
+                }
+            }
+        }
+
+        for node in drawn.iter() {
+            let id = node.borrow().id;
+            if !self.entities.contains_key(&id) {
+                let entity = self.create_node(node.clone(), *positions.get(&id).unwrap());
+                let (label, class) = looks.get(&id).unwrap().clone();
+                self.entities.insert(id, GodsnodeEntity { entity, label, class });
+            }
+        }
+
+        // Edges are cheap, draw them all again from every parent so shared nodes show all of their dependents
+        let mut edges = Vec::new();
+        for node in drawn.iter() {
+            let node = node.borrow();
+            let (x, y, z) = *positions.get(&node.id).unwrap();
+            for kid in node.get_children().iter().filter_map(|kid| kid.upgrade()) {
+                if let Some((kid_x, kid_y, kid_z)) = positions.get(&kid.borrow().id) {
+                    edges.push((core::Point3::new(x, y, z), core::Point3::new(*kid_x, *kid_y, *kid_z)));
+                }
+            }
+        }
+        let mut shapes = self.shapes.borrow_mut();
+        shapes.set_layer("edges", edges);
+        shapes.flush(&self.state);
+    }
+}
+
+impl System for TopologySystem {
+    fn tick(&mut self) {
+        // Despawn removed nodes first, their ids may already be taken by new nodes
+        for id in self.woods.store.take_removed().iter() {
+            if let Some(drawn) = self.entities.remove(id) {
+                self.state.remove_entity(drawn.entity);
+            }
+        }
+        if self.woods.take_changed() {
+            self.sync();
+        }
+    }
+}
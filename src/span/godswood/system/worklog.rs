@@ -0,0 +1,232 @@
+use std::rc::{Rc, Weak};
+use std::collections::HashMap;
+use dragon::ecs::*;
+use crate::utils::timestamp_ms;
+use crate::span::godswood::node::{Godsnode, StoreOps};
+use crate::span::godswood::tree::{Godswoods, GodswoodProto};
+use crate::span::godswood::status::GodsnodeHealth;
+use crate::span::godswood::alert::{AlertEvent, AlertSeverity, Alerts};
+use crate::span::godswood::worklog::{Worklog, WorklogEntry, WorklogKind};
+
+fn health_severity(health: GodsnodeHealth) -> AlertSeverity {
+    match health {
+        GodsnodeHealth::Down => AlertSeverity::Critical,
+        GodsnodeHealth::Degraded | GodsnodeHealth::Unknown => AlertSeverity::Warning,
+        GodsnodeHealth::Healthy | GodsnodeHealth::Maintenance => AlertSeverity::Info,
+    }
+}
+
+// Declared path of every node in a wood, keyed by wood and node, the weak link keeps the key of a
+// dropped node from being reused
+type Topology = HashMap<(String, *const Godsnode), (String, Weak<Godsnode>)>;
+
+// Logs status transitions, alerts fired or resolved and nodes added, removed or moved
+pub struct WorklogSystem {
+    woods: Godswoods,
+    alerts: Alerts,
+    worklog: Worklog,
+    // Topology at the last logged revision
+    topology: Topology,
+    revision: Option<u64>,
+}
+
+impl WorklogSystem {
+    pub fn new(woods: Godswoods, alerts: Alerts, worklog: Worklog) -> Self {
+        Self {
+            woods,
+            alerts,
+            worklog,
+            topology: HashMap::new(),
+            revision: None,
+        }
+    }
+
+    fn read_topology(&self) -> Topology {
+        let mut topology = HashMap::new();
+        for (name, wood) in self.woods.woods.borrow().iter() {
+            let root = match wood.borrow().wood.get_root().upgrade() {
+                Some(root) => root,
+                None => continue,
+            };
+            for node in GodswoodProto::collect_nodes(&root).values() {
+                if let Some(path) = node.borrow().read_path(name) {
+                    topology.insert((name.clone(), Rc::as_ptr(node)), (path, Rc::downgrade(node)));
+                }
+            }
+        }
+        topology
+    }
+
+    // Nodes added, removed or moved since the last call, subtrees are logged by their top node only
+    fn log_topology(&mut self, now: u64) -> Vec<WorklogEntry> {
+        let topology = self.read_topology();
+        let mut added = Vec::new();
+        let mut moved = Vec::new();
+        for (key, (path, _)) in topology.iter() {
+            match self.topology.get(key) {
+                None => added.push((path.clone(), "added".to_string())),
+                Some((old, _)) if old != path => moved.push((path.clone(), format!("moved from {}", old))),
+                _ => {},
+            }
+        }
+        let removed: Vec<(String, String)> = self.topology.iter()
+            .filter(|(key, _)| !topology.contains_key(key))
+            .map(|(_, (path, _))| (path.clone(), "removed".to_string()))
+            .collect();
+        self.topology = topology;
+
+        let mut entries = Vec::new();
+        for mut changes in vec![removed, added, moved].into_iter() {
+            changes.sort();
+            let tops: Vec<String> = changes.iter().map(|(path, _)| format!("{}.", path)).collect();
+            for (path, change) in changes.iter() {
+                if tops.iter().any(|top| path.starts_with(top)) {
+                    continue;
+                }
+                let what = if path.rfind('.') == Some(0) { "wood" } else { "node" };
+                entries.push(WorklogEntry {
+                    at: now,
+                    kind: WorklogKind::Topology,
+                    severity: AlertSeverity::Info,
+                    path: Some(path.clone()),
+                    message: format!("{} {}", what, change),
+                });
+            }
+        }
+        entries
+    }
+
+    fn update(&mut self, now: u64) {
+        let mut entries = Vec::new();
+
+        let revision = self.woods.revision();
+        if self.revision != Some(revision) {
+            self.revision = Some(revision);
+            entries.extend(self.log_topology(now));
+        }
+
+        // First reports of healthy nodes are not worth an entry
+        for transition in self.woods.store.take_transitions().into_iter() {
+            let message = match transition.from {
+                None if transition.to == GodsnodeHealth::Healthy => continue,
+                None => transition.to.as_str().to_string(),
+                Some(from) => format!("{} -> {}", from.as_str(), transition.to.as_str()),
+            };
+            let message = match transition.reason.as_str() {
+                "" => message,
+                reason => format!("{}: {}", message, reason),
+            };
+            entries.push(WorklogEntry {
+                at: transition.at,
+                kind: WorklogKind::Status,
+                severity: health_severity(transition.to),
+                path: Some(transition.path),
+                message,
+            });
+        }
+
+        for event in self.alerts.borrow_mut().take_events().into_iter() {
+            let (severity, alert, state) = match event {
+                AlertEvent::Fired(alert) => (alert.severity, alert, "fired"),
+                AlertEvent::Resolved(alert) => (AlertSeverity::Info, alert, "resolved"),
+            };
+            entries.push(WorklogEntry {
+                at: now,
+                kind: WorklogKind::Alert,
+                severity,
+                path: Some(alert.path),
+                message: format!("{} {}: {}", alert.severity.as_str(), state, alert.message),
+            });
+        }
+
+        let mut worklog = self.worklog.borrow_mut();
+        for entry in entries.into_iter() {
+            worklog.push(entry);
+        }
+    }
+}
+
+impl System for WorklogSystem {
+    fn tick(&mut self) {
+        self.update(timestamp_ms());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::span::godswood::alert::{Alert, AlertsProto};
+    use crate::span::godswood::worklog::{WorklogFilter, WorklogProto};
+    use crate::span::godswood::patch::GodswoodPatch;
+
+    fn logged(worklog: &Worklog, since: u64) -> Vec<(WorklogKind, Option<String>, String)> {
+        worklog.borrow().get_entries(&WorklogFilter::new()).into_iter()
+            .filter(|(seq, _)| *seq >= since)
+            .map(|(_, entry)| (entry.kind, entry.path.clone(), entry.message.clone()))
+            .collect()
+    }
+
+    fn topology(path: &str, message: &str) -> (WorklogKind, Option<String>, String) {
+        (WorklogKind::Topology, Some(path.to_string()), message.to_string())
+    }
+
+    #[test]
+    fn logs_topologies() {
+        let mut woods = Godswoods::new();
+        let worklog = WorklogProto::new();
+        let mut system = WorklogSystem::new(woods.clone(), AlertsProto::new(), worklog.clone());
+        woods.add_wood(&json!({ "name": "shop", "children": { "db": { "children": { "disk": {} } } } })).unwrap();
+        woods.add_wood(&json!({ "name": "blog" })).unwrap();
+        system.update(0);
+        assert_eq!(logged(&worklog, 0), vec![topology(".blog", "wood added"), topology(".shop", "wood added")]);
+        system.update(5);
+        assert_eq!(logged(&worklog, 2), vec![]);
+
+        woods.patch(&GodswoodPatch::Reparent { path: ".shop.db.disk".to_string(), parent: ".shop".to_string() }).unwrap();
+        woods.patch(&GodswoodPatch::Add { parent: ".shop".to_string(), name: "api".to_string(), raw: json!({ "children": { "cache": {} } }) }).unwrap();
+        woods.remove_wood(&"blog".to_string());
+        system.update(10);
+        assert_eq!(logged(&worklog, 2), vec![
+            topology(".blog", "wood removed"),
+            topology(".shop.api", "node added"),
+            topology(".shop.disk", "node moved from .shop.db.disk"),
+        ]);
+        assert_eq!(worklog.borrow().get_entries(&WorklogFilter::new())[2].1.at, 10);
+    }
+
+    #[test]
+    fn logs_transitions_and_alerts() {
+        let mut woods = Godswoods::new();
+        let alerts = AlertsProto::new();
+        let worklog = WorklogProto::new();
+        let mut system = WorklogSystem::new(woods.clone(), alerts.clone(), worklog.clone());
+        woods.add_wood(&json!({ "name": "shop", "children": { "db": {} } })).unwrap();
+        system.update(0);
+
+        // First reports of healthy nodes are skipped
+        let db = woods.store.get_node(".shop.db").unwrap();
+        woods.store.update_status(&db, GodsnodeHealth::Healthy, "", 10);
+        system.update(15);
+        assert_eq!(logged(&worklog, 1), vec![]);
+
+        woods.store.update_status(&db, GodsnodeHealth::Down, "crashed", 20);
+        alerts.borrow_mut().raise(Alert {
+            key: "db".to_string(),
+            severity: AlertSeverity::Critical,
+            path: ".shop.db".to_string(),
+            message: "db down".to_string(),
+            since: 20,
+        });
+        system.update(30);
+        assert_eq!(logged(&worklog, 1), vec![
+            (WorklogKind::Status, Some(".shop.db".to_string()), "healthy -> down: crashed".to_string()),
+            (WorklogKind::Status, Some(".shop".to_string()), "healthy -> down: 1 of 1 children down".to_string()),
+            (WorklogKind::Alert, Some(".shop.db".to_string()), "critical fired: db down".to_string()),
+        ]);
+        let entries = worklog.borrow().get_entries(&WorklogFilter::new()).into_iter()
+            .map(|(_, entry)| (entry.at, entry.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(entries[1..], [(20, AlertSeverity::Critical), (20, AlertSeverity::Critical), (30, AlertSeverity::Critical)]);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::span::picker::{Tooltip, TooltipProvider};
+use crate::span::godswood::GodswoodHandle;
+use crate::span::godswood::node::StoreOps;
+use crate::span::godswood::system::topology::health_colors;
+
+// Attributes listed beyond this are counted only
+const MAX_ATTRIBUTES: usize = 5;
+const HEALTHY_COLOR: &str = "#42f581";
+
+impl GodswoodHandle {
+    // Tooltip of the node at a path, with every path it is declared at, its class, status and
+    // attributes in key order
+    pub fn node_tooltip(&self) -> TooltipProvider {
+        let handle = self.clone();
+        Box::new(move |path| {
+            let node = handle.woods.store.get_node(path)?;
+            let node = node.borrow();
+            let mut paths: Vec<String> = node.app_meta_map.values().map(|meta| meta.path.read()).collect();
+            paths.sort();
+
+            let mut rows: Vec<(String, String)> = paths.into_iter().map(|path| ("path".to_string(), path)).collect();
+            rows.push(("class".to_string(), node.service_type.as_str().to_string()));
+            let status = if node.status.reason.is_empty() {
+                node.status.health.as_str().to_string()
+            } else {
+                format!("{}: {}", node.status.health.as_str(), node.status.reason)
+            };
+            rows.push(("status".to_string(), status));
+
+            let mut attributes: Vec<(&String, &String)> = node.attributes.iter().collect();
+            attributes.sort();
+            for (key, value) in attributes.iter().take(MAX_ATTRIBUTES) {
+                rows.push((key.to_string(), value.to_string()));
+            }
+            if attributes.len() > MAX_ATTRIBUTES {
+                rows.push((String::new(), format!("+{} more", attributes.len() - MAX_ATTRIBUTES)));
+            }
+
+            Some(Tooltip {
+                title: node.display_name.clone(),
+                color: health_colors(node.status.health).map_or(HEALTHY_COLOR, |(_, stroke)| stroke).to_string(),
+                rows,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::span::godswood::tree::Godswoods;
+    use crate::span::godswood::record::RecorderProto;
+    use crate::span::godswood::alert::AlertsProto;
+    use crate::span::godswood::rule::RuleEngineProto;
+    use crate::span::godswood::select::SelectionProto;
+    use crate::span::godswood::control::CameraControlProto;
+    use crate::span::godswood::worklog::WorklogProto;
+    use crate::span::godswood::status::GodsnodeHealth;
+    use crate::span::godswood::persist::Preferences;
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    fn handle() -> GodswoodHandle {
+        let mut woods = Godswoods::new();
+        woods.add_wood(&json!({
+            "name": "shop",
+            "children": {
+                "db": {
+                    "id": "db", "display_name": "Main database", "class": "database",
+                    "attributes": { "g": "7", "b": "2", "a": "1", "f": "6", "c": "3", "e": "5", "d": "4" },
+                },
+            },
+        })).unwrap();
+        woods.add_wood(&json!({ "name": "blog", "children": { "db": { "id": "db" } } })).unwrap();
+        GodswoodHandle {
+            woods,
+            recorder: RecorderProto::new(),
+            alerts: AlertsProto::new(),
+            rules: RuleEngineProto::new(),
+            selection: SelectionProto::new(),
+            control: CameraControlProto::new(),
+            worklog: WorklogProto::new(),
+            preferences: Rc::new(RefCell::new(Preferences::default())),
+        }
+    }
+
+    fn rows(tooltip: &Tooltip) -> Vec<(&str, &str)> {
+        tooltip.rows.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect()
+    }
+
+    #[test]
+    fn describes_nodes() {
+        let handle = handle();
+        let provider = handle.node_tooltip();
+        assert!(provider(".shop.cache").is_none());
+
+        let tooltip = provider(".blog.db").unwrap();
+        assert_eq!((tooltip.title.as_str(), tooltip.color.as_str()), ("Main database", "gray"));
+        assert_eq!(rows(&tooltip), vec![
+            ("path", ".blog.db"), ("path", ".shop.db"), ("class", "database"), ("status", "unknown"),
+            ("a", "1"), ("b", "2"), ("c", "3"), ("d", "4"), ("e", "5"), ("", "+2 more"),
+        ]);
+
+        let db = handle.woods.store.get_node(".shop.db").unwrap();
+        handle.woods.store.update_status(&db, GodsnodeHealth::Healthy, "", 10);
+        let tooltip = provider(".shop.db").unwrap();
+        assert_eq!(tooltip.color, HEALTHY_COLOR);
+        assert_eq!(tooltip.rows[3], ("status".to_string(), "healthy".to_string()));
+        handle.woods.store.update_status(&db, GodsnodeHealth::Down, "disk full", 20);
+        let tooltip = provider(".shop.db").unwrap();
+        assert_eq!((tooltip.color.as_str(), tooltip.rows[3].1.as_str()), ("red", "down: disk full"));
+    }
+}
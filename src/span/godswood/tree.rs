@@ -1,14 +1,20 @@
 use std::rc::{Rc, Weak};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::f64::consts::PI;
 use std::collections::{VecDeque, HashMap};
 use crate::span::godswood::node::*;
+use crate::span::godswood::error::*;
+use crate::span::godswood::parser;
 use serde_json::Value;
-use crate::utils::JsonMap;
+use crate::utils::{JsonMap, JsonParser};
 
+// Shared handle of all woods, clones see the same woods and store
+#[derive(Clone)]
 pub struct Godswoods {
     pub woods: Rc<RefCell<HashMap<String, Rc<RefCell<Godswood>>>>>,
     pub store: Rc<Store>,
+    changed: Rc<Cell<bool>>,
+    revision: Rc<Cell<u64>>,
 }
 
 impl Godswoods {
@@ -16,13 +22,46 @@ impl Godswoods {
         Self {
             woods: Rc::new(RefCell::new(HashMap::new())),
             store: StoreProto::new(),
+            changed: Rc::new(Cell::new(false)),
+            revision: Rc::new(Cell::new(0)),
         }
     }
 
-    pub fn add_wood(&mut self, raw: &Value) {
+    // Flag topology changes so the stage can catch up on next tick
+    pub fn mark_changed(&self) {
+        self.changed.set(true);
+        self.revision.set(self.revision.get() + 1);
+    }
+
+    // Bumped on every topology change, unlike the changed flag it is never reset
+    pub fn revision(&self) -> u64 {
+        self.revision.get()
+    }
+
+    pub fn take_changed(&self) -> bool {
+        self.changed.replace(false)
+    }
+
+    // Load a wood from JSON, YAML or TOML text, errors point at line and column in the text
+    pub fn add_wood_from_str(&mut self, raw: &str, format: parser::WoodFormat) -> Result<(), WoodError> {
+        let value = parser::parse_source(raw, format)?;
+        self.add_wood(&value).map_err(|err| {
+            let location = parser::locate(raw, format, &err.path);
+            err.with_location(location)
+        })
+    }
+
+    pub fn add_wood(&mut self, raw: &Value) -> Result<(), WoodError> {
+        parser::validate_wood(raw)?;
+        if self.woods.borrow().contains_key(&raw.get_str("name", "")) {
+            return Err(WoodError::new(WoodErrorKind::DuplicateName(raw.get_str("name", "")), "$.name"));
+        }
+        self.check_shared(&parser::collect_shared(raw, None, "$")?)?;
+
         let mut wood = GodswoodProto::default(self.store.clone());
         wood.parse_from_json(raw);
         wood.init_nodes();
+        wood.rollup_status();
         let mut woods = self.woods.borrow_mut();
         let name = wood.read_name();
         let mut godswood = Godswood {
@@ -34,11 +73,65 @@ impl Godswoods {
 
         godswood.calculate_scales();
         woods.insert(name, Rc::new(RefCell::new(godswood)));
+        self.mark_changed();
+        Ok(())
+    }
+
+    // Stored nodes matching shared declarations, a declaration may repeat the subtree of the stored
+    // node but must not add to it, the subtree belongs to the wood declaring the node first
+    pub fn check_shared(&self, shared: &[parser::WoodShared]) -> Result<Vec<Rc<Godsnode>>, WoodError> {
+        let mut nodes = Vec::new();
+        for declared in shared.iter() {
+            if let Some(node) = self.store.get_shared_node(&declared.id) {
+                if node.borrow().name != declared.name {
+                    return Err(WoodError::new(WoodErrorKind::SharedNode(declared.id.clone()), &declared.json_path));
+                }
+                if !declares_subtree(&node, declared.raw) {
+                    return Err(WoodError::new(WoodErrorKind::SharedNode(declared.id.clone()), &format!("{}.children", declared.json_path)));
+                }
+                nodes.push(node);
+            }
+        }
+        Ok(nodes)
+    }
+
+    // Drop the wood and all of its nodes not shared with other woods
+    pub fn remove_wood(&mut self, name: &String) -> bool {
+        let wood = self.woods.borrow_mut().remove(name);
+        match wood {
+            Some(wood) => {
+                if let Some(root) = wood.borrow().wood.get_root().upgrade() {
+                    let nodes = GodswoodProto::collect_nodes(&root);
+                    self.store.remove_subtree(&root);
+                    // Nodes kept by other woods leave this one
+                    for node in nodes.values() {
+                        node.borrow_mut().app_meta_map.remove(name);
+                    }
+                }
+                self.store.remove_index(&format!(".{}", name));
+                self.mark_changed();
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+// Whether the node already has every child declared in `raw`, all the way down
+pub fn declares_subtree(node: &Rc<Godsnode>, raw: &Value) -> bool {
+    match raw["children"].as_object() {
+        Some(children) => children.iter().all(|(name, child)| {
+            node.borrow().get_children().iter().filter_map(|kid| kid.upgrade()).find(|kid| {
+                kid.borrow().name == *name
+            }).is_some_and(|kid| declares_subtree(&kid, child))
+        }),
+        None => true,
     }
 }
 
 pub struct GodswoodProto {
     depth: usize,
+    depths: HashMap<u64, usize>,
     nodes_by_depth: Rc<RefCell<HashMap<usize, Vec<Weak<Godsnode>>>>>,
     root: Weak<Godsnode>,
     store: Rc<Store>,
@@ -48,66 +141,147 @@ impl GodswoodProto {
     pub fn init_nodes(&mut self) {
         let nodes_by_depth = self.nodes_by_depth.clone();
         log!("Initializing nodes for wood");
-        if let Some(node) = self.root.upgrade() {
+        if let Some(root) = self.root.upgrade() {
             let mut nodes_by_depth = nodes_by_depth.borrow_mut();
 
             // Flush nodes queue first
             nodes_by_depth.clear();
-            let app_name: String;
-            let app_display_name: String;
-            let mut app_meta: GodswoodMeta;
-            {
-                let mut app = node.borrow_mut();
-                // info!("Drawing tree architecture of application {}", app.display_name);
-                app_display_name = app.display_name.clone();
-                app_meta = GodswoodMeta::new();
-                app_name = app.name.clone();
-                app_meta.path.append(&app.name);
-                self.depth = app_meta.path.read_depth();
-                app.app_meta_map.insert(app_name.clone(), app_meta.clone());
-                self.store.update_index(&app_meta.path.read(), app.id);
-                let entry = nodes_by_depth.entry(self.depth).or_insert(Vec::new());
-                entry.push(self.root.clone());
+            self.depths.clear();
+            self.depth = 0;
+
+            let app_name = root.borrow().name.clone();
+            let reachable = GodswoodProto::collect_nodes(&root);
 
+            // Count parents inside this wood, a node is ready once all of them are initialized
+            let mut pending: HashMap<u64, usize> = HashMap::new();
+            for (id, node) in reachable.iter() {
+                let node = node.borrow();
+                let count = node.get_parents().iter().filter(|parent| {
+                    match parent.upgrade() {
+                        Some(parent) => reachable.contains_key(&parent.borrow().id),
+                        None => false,
+                    }
+                }).count();
+                pending.insert(*id, count);
             }
-            let mut tasks: VecDeque<InitGodsnodeQ> = VecDeque::new();
-            tasks.push_back(InitGodsnodeQ {
-                app_meta: app_meta,
-                nodes: node.borrow().children.clone(),
-            });
+
+            let mut paths: HashMap<u64, Vec<GodsnodePath>> = HashMap::new();
+            let mut app_path = GodsnodePath::new_path();
+            app_path.append(&app_name);
+            let root_id = root.borrow().id;
+            paths.insert(root_id, vec![app_path]);
+            self.depths.insert(root_id, 1);
+
+            let mut tasks: VecDeque<Rc<Godsnode>> = VecDeque::new();
+            tasks.push_back(root.clone());
+            let mut initialized = 0;
 
             loop {
                 let task = tasks.pop_front();
                 if task.is_none() {
                     break;
                 }
-                let task = task.unwrap();
-
-                for child in task.nodes.iter() {
-                    if let Some(node) = child.upgrade() {
-                        let mut kid_app_meta = task.app_meta.clone();
-                        let mut kid = node.borrow_mut();
-                        kid_app_meta.path.append(&kid.name);
-                        log!("Initializing {}", kid_app_meta.path.read());
-                        self.depth = kid_app_meta.path.read_depth();
-                        kid.app_meta_map.insert(app_name.clone(), kid_app_meta.clone());
-                        self.store.update_index(&kid_app_meta.path.read(), kid.id);
-                        let entry = nodes_by_depth.entry(self.depth).or_insert(Vec::new());
-                        entry.push(child.clone());
-
-                        tasks.push_back(InitGodsnodeQ {
-                            app_meta: kid_app_meta.clone(),
-                            nodes: kid.children.clone(),
-                        });
+                let node = task.unwrap();
+
+                let id: u64;
+                let children: GodsnodeQ;
+                let node_paths = {
+                    let mut state = node.borrow_mut();
+                    id = state.id;
+                    children = state.children.clone();
+                    let node_paths = paths.remove(&id).unwrap_or_default();
+                    // The first path is the declared one, others come from shared links
+                    if let Some(path) = node_paths.first() {
+                        state.app_meta_map.insert(app_name.clone(), GodswoodMeta { path: path.clone() });
+                    }
+                    node_paths
+                };
+                for path in node_paths.iter() {
+                    log!("Initializing {}", path.read());
+                    self.store.update_index(&path.read(), id);
+                }
+
+                initialized += 1;
+                let depth = *self.depths.get(&id).unwrap();
+                if depth > self.depth {
+                    self.depth = depth;
+                }
+                let entry = nodes_by_depth.entry(depth).or_default();
+                entry.push(Rc::downgrade(&node));
+
+                for child in children.iter() {
+                    if let Some(kid) = child.upgrade() {
+                        let kid = kid.borrow();
+                        // Paths through the first parent come first, that is where the kid is declared
+                        let primary = kid.get_parents().iter().filter_map(|parent| parent.upgrade()).find(|parent| {
+                            reachable.contains_key(&parent.borrow().id)
+                        }).is_some_and(|parent| Rc::ptr_eq(&parent, &node));
+                        let kid_paths = paths.entry(kid.id).or_default();
+                        for (i, path) in node_paths.iter().enumerate() {
+                            let mut kid_path = path.clone();
+                            kid_path.append(&kid.name);
+                            if primary {
+                                kid_paths.insert(i, kid_path);
+                            } else {
+                                kid_paths.push(kid_path);
+                            }
+                        }
+
+                        // A shared node sits right below the deepest of its parents
+                        let kid_depth = self.depths.entry(kid.id).or_insert(0);
+                        if *kid_depth < depth + 1 {
+                            *kid_depth = depth + 1;
+                        }
+
+                        if let Some(count) = pending.get_mut(&kid.id) {
+                            *count -= 1;
+                            if *count == 0 {
+                                tasks.push_back(reachable.get(&kid.id).unwrap().clone());
+                            }
+                        }
                     }
                 }
             }
 
-            // info!("Finished drawing tree architecture of application {}", app_display_name);
+            if initialized < reachable.len() {
+                log!("Skipped {} nodes linked in a cycle", reachable.len() - initialized);
+            }
         }
 
     }
 
+    // Roll up the status of every node, deepest level first so children are done before parents
+    pub fn rollup_status(&self) {
+        let nodes_by_depth = self.nodes_by_depth.borrow();
+        for depth in (1..self.depth + 1).rev() {
+            if let Some(nodes) = nodes_by_depth.get(&depth) {
+                for node in nodes.iter().filter_map(|node| node.upgrade()) {
+                    self.store.refresh_status(&node);
+                }
+            }
+        }
+    }
+
+    // Collect all nodes reachable from the node, nodes shared by several parents are collected once
+    pub fn collect_nodes(node: &Rc<Godsnode>) -> HashMap<u64, Rc<Godsnode>> {
+        let mut nodes = HashMap::new();
+        let mut tasks: VecDeque<Rc<Godsnode>> = VecDeque::new();
+        tasks.push_back(node.clone());
+        while let Some(node) = tasks.pop_front() {
+            let id = node.borrow().id;
+            if nodes.contains_key(&id) {
+                continue;
+            }
+            for child in node.borrow().get_children().iter() {
+                if let Some(child) = child.upgrade() {
+                    tasks.push_back(child);
+                }
+            }
+            nodes.insert(id, node);
+        }
+        nodes
+    }
+
     // Sample application tree
     // app:
     //   children:
@@ -116,24 +290,45 @@ impl GodswoodProto {
     //          node3:
     //             children:
     //     node2:
+    //       depends_on:
+    //         - .app.node1.node3
     //       children
+    //
+    // Nodes listed in `depends_on` are linked as extra children, so node3 has both node1 and node2
+    // as parents.
 
     pub fn parse(&mut self, raw:& Value) {
         let root = self.store.add_app_node(&raw);
         self.root = Rc::downgrade(&root);
+        let mut path = GodsnodePath::new_path();
+        path.append(&root.borrow().name);
+        self.store.update_index(&path.read(), root.borrow().id);
+        let mut links = Vec::new();
+        GodswoodProto::parse_links(&root, raw, &mut links);
         if let Some(children) = raw["children"].as_object() {
             if !children.is_empty() {
-                GodswoodProto::parse_children(&root, &children, &mut self.store);
+                GodswoodProto::parse_children(&root, children, &path, &mut self.store, &mut links);
             }
         }
+        GodswoodProto::link_nodes(&links, &self.store);
     }
 
-    pub fn parse_children(parent_node: &Rc<Godsnode>, children: & JsonMap, store: &mut Rc<Store>) {
+    pub fn parse_children(parent_node: &Rc<Godsnode>, children: & JsonMap, path: &GodsnodePath, store: &mut Rc<Store>, links: &mut Vec<GodsnodeLink>) {
         for (name, raw) in children.iter() {
-            let mut node = store.add_node(&raw, name.clone());
-            if let Some(sub_children) = raw["children"].as_object() {
+            let (mut node, shared) = store.declare_node(raw, name.clone());
+            let mut node_path = path.clone();
+            node_path.append(name);
+            store.update_index(&node_path.read(), node.borrow().id);
+            if shared {
+                // Declared by another wood already, only link it here
+                log!("Sharing node {} at {}", name, node_path.read());
+                GodswoodProto::index_subtree(&node, &node_path, store);
+            } else {
+                GodswoodProto::parse_links(&node, raw, links);
+            }
+            if let Some(sub_children) = raw["children"].as_object().filter(|_| !shared) {
                 if ! sub_children.is_empty() {
-                    GodswoodProto::parse_children(&mut node, sub_children, store);
+                    GodswoodProto::parse_children(&mut node, sub_children, &node_path, store, links);
                 }
             }
             let mut parent = parent_node.borrow_mut();
@@ -144,10 +339,60 @@ impl GodswoodProto {
         }
     }
 
+    // Index the paths below a shared node, so links of this wood can point into its subtree
+    fn index_subtree(node: &Rc<Godsnode>, path: &GodsnodePath, store: &Rc<Store>) {
+        for kid in node.borrow().get_children().iter().filter_map(|kid| kid.upgrade()) {
+            let mut kid_path = path.clone();
+            kid_path.append(&kid.borrow().name);
+            store.update_index(&kid_path.read(), kid.borrow().id);
+            GodswoodProto::index_subtree(&kid, &kid_path, store);
+        }
+    }
+
+    pub fn parse_links(node: &Rc<Godsnode>, raw: &Value, links: &mut Vec<GodsnodeLink>) {
+        if let Some(paths) = raw["depends_on"].as_array() {
+            for path in paths.iter() {
+                if let Some(path) = path.as_str() {
+                    links.push(GodsnodeLink {
+                        node: Rc::downgrade(node),
+                        path: path.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    // Link dependencies once all declared paths are indexed
+    pub fn link_nodes(links: &[GodsnodeLink], store: &Rc<Store>) {
+        for link in links.iter() {
+            let parent_node = link.node.upgrade();
+            let node = store.get_weak_node(&link.path).and_then(|node| node.upgrade());
+            if parent_node.is_none() || node.is_none() {
+                log!("Failed to find dependency {}", link.path);
+                continue;
+            }
+            let parent_node = parent_node.unwrap();
+            let node = node.unwrap();
+            if Rc::ptr_eq(&parent_node, &node) {
+                log!("Ignored node depending on itself {}", link.path);
+                continue;
+            }
+            let linked = node.borrow().get_parents().iter().any(|parent| {
+                parent.upgrade().is_some_and(|parent| Rc::ptr_eq(&parent, &parent_node))
+            });
+            if linked {
+                continue;
+            }
+            parent_node.borrow_mut().add_child(Rc::downgrade(&node));
+            node.borrow_mut().add_parent(Rc::downgrade(&parent_node));
+        }
+    }
+
 
         fn default(store: Rc<Store>) -> Self {
         Self {
             depth: 0,
+            depths: HashMap::new(),
             nodes_by_depth: Rc::new(RefCell::new(HashMap::new())),
             root: Weak::new(),
             store: store,
@@ -164,13 +409,18 @@ impl GodswoodProto {
     pub fn get_depth(&self) -> usize {
         self.depth
     }
+    pub fn get_node_depth(&self, id: u64) -> Option<usize> {
+        self.depths.get(&id).cloned()
+    }
     pub fn get_root(&self) -> Weak<RefCell<GodsnodeProto>> {
         self.root.clone()
     }
 
     pub fn read_name(&self) -> String {
-        let root = self.root.clone();
-        root.upgrade().unwrap().borrow().name.clone()
+        match self.root.upgrade() {
+            Some(root) => root.borrow().name.clone(),
+            None => String::new(),
+        }
     }
 }
 
@@ -191,14 +441,28 @@ impl Godswood {
         }
     }
 
+    // Rough radius of the wood around its root
+    pub fn radius(&self) -> f32 {
+        self.scales.values().sum::<f32>() * self.base_scale
+    }
+
+    // Rebuild paths, depths and scales after the topology changed
+    pub fn refresh(&mut self) {
+        self.wood.init_nodes();
+        self.wood.rollup_status();
+        self.scales.clear();
+        self.calculate_scales();
+    }
+
     fn calculate_scales(&mut self) {
         log!("Initializing scales for wood");
         let nodes = self.wood.get_nodes_by_depths();
         let depth = self.wood.get_depth();
         let nodes = nodes.borrow();
         for i in 1..depth {
-            let items = nodes.get(&i).unwrap();
             let mut kids_max = 0;
+            let empty = Vec::new();
+            let items = nodes.get(&i).unwrap_or(&empty);
             for item in items.iter() {
                 if let Some(node) = item.upgrade() {
                     let node = node.borrow();
@@ -257,3 +521,63 @@ impl Godswood {
     }
     */
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::span::godswood::error::WoodErrorKind;
+
+    // Both woods share the db node declared by shop first
+    fn woods() -> Godswoods {
+        let mut woods = Godswoods::new();
+        woods.add_wood(&json!({
+            "name": "shop",
+            "children": { "db": { "id": "db", "children": { "disk": {} } } },
+        })).unwrap();
+        woods.add_wood(&json!({
+            "name": "blog",
+            "children": { "api": { "children": { "db": { "id": "db" } } } },
+        })).unwrap();
+        woods
+    }
+
+    fn reject(raw: Value) -> (WoodErrorKind, String) {
+        let err = woods().add_wood(&raw).unwrap_err();
+        (err.kind, err.path)
+    }
+
+    #[test]
+    fn shares_nodes() {
+        let mut woods = woods();
+        let db = woods.store.get_node(".shop.db").unwrap();
+        assert!(Rc::ptr_eq(&woods.store.get_node(".blog.api.db").unwrap(), &db));
+        assert!(woods.store.get_node(".blog.api.db.disk").is_some());
+        assert_eq!(db.borrow().app_meta_map.len(), 2);
+
+        assert!(woods.remove_wood(&"shop".to_string()));
+        assert!(woods.store.get_node(".shop.db").is_none());
+        assert!(Rc::ptr_eq(&woods.store.get_node(".blog.api.db").unwrap(), &db));
+        assert_eq!(db.borrow().app_meta_map.len(), 1);
+        assert!(!woods.remove_wood(&"shop".to_string()));
+    }
+
+    #[test]
+    fn rejects_conflicting_declarations() {
+        let shared = WoodErrorKind::SharedNode("db".to_string());
+        assert_eq!(
+            reject(json!({ "name": "wiki", "children": { "store": { "id": "db" } } })),
+            (shared.clone(), "$.children.store".to_string()));
+        assert_eq!(
+            reject(json!({ "name": "wiki", "children": { "db": { "id": "db", "children": { "cache": {} } } } })),
+            (shared.clone(), "$.children.db.children".to_string()));
+        assert_eq!(
+            reject(json!({ "name": "wiki", "children": { "a": { "id": "cache" }, "b": { "children": { "a": { "id": "cache" } } } } })).0,
+            WoodErrorKind::SharedNode("cache".to_string()));
+
+        // Repeating the subtree is fine
+        let mut woods = woods();
+        woods.add_wood(&json!({ "name": "wiki", "children": { "db": { "id": "db", "children": { "disk": {} } } } })).unwrap();
+        assert!(woods.store.get_node(".wiki.db.disk").is_some());
+    }
+}
@@ -0,0 +1,153 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use crate::span::godswood::alert::AlertSeverity;
+use crate::span::godswood::query::GodsnodeQuery;
+
+// Oldest entries are dropped beyond this
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorklogKind {
+    Status,
+    Alert,
+    Topology,
+    Note,
+}
+
+impl WorklogKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorklogKind::Status => "status",
+            WorklogKind::Alert => "alert",
+            WorklogKind::Topology => "topology",
+            WorklogKind::Note => "note",
+        }
+    }
+}
+
+// Something that happened at `at` ms, on the node or wood at `path` if any
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorklogEntry {
+    pub at: u64,
+    pub kind: WorklogKind,
+    pub severity: AlertSeverity,
+    pub path: Option<String>,
+    pub message: String,
+}
+
+// Entries in the order they were logged, shared between the world and the worklog span
+pub struct WorklogProto {
+    // With their sequence number, which keeps increasing as the oldest entries are dropped
+    entries: VecDeque<(u64, WorklogEntry)>,
+    next: u64,
+}
+
+pub type Worklog = Rc<RefCell<WorklogProto>>;
+
+impl WorklogProto {
+    pub fn new() -> Worklog {
+        Rc::new(RefCell::new(Self {
+            entries: VecDeque::new(),
+            next: 0,
+        }))
+    }
+
+    pub fn push(&mut self, entry: WorklogEntry) {
+        self.entries.push_back((self.next, entry));
+        self.next += 1;
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn add_note(&mut self, at: u64, path: Option<&str>, message: &str) {
+        self.push(WorklogEntry {
+            at,
+            kind: WorklogKind::Note,
+            severity: AlertSeverity::Info,
+            path: path.map(|path| path.to_string()),
+            message: message.to_string(),
+        });
+    }
+
+    // Matching entries with their sequence number, oldest first
+    pub fn get_entries(&self, filter: &WorklogFilter) -> Vec<(u64, &WorklogEntry)> {
+        self.entries.iter().filter(|(_, entry)| filter.matches(entry)).map(|(seq, entry)| (*seq, entry)).collect()
+    }
+}
+
+// Entries at least as severe as `severity`, on nodes matching `path` when set
+#[derive(Clone, Debug)]
+pub struct WorklogFilter {
+    pub severity: AlertSeverity,
+    pub path: Option<GodsnodeQuery>,
+}
+
+impl WorklogFilter {
+    pub fn new() -> Self {
+        Self {
+            severity: AlertSeverity::Info,
+            path: None,
+        }
+    }
+
+    // Unknown severities show everything, empty or bad patterns any path
+    pub fn parse(severity: &str, path: &str) -> Self {
+        Self {
+            severity: AlertSeverity::parse(severity).unwrap_or(AlertSeverity::Info),
+            path: GodsnodeQuery::parse(path.trim()),
+        }
+    }
+
+    pub fn matches(&self, entry: &WorklogEntry) -> bool {
+        if entry.severity < self.severity {
+            return false;
+        }
+        match (&self.path, &entry.path) {
+            (None, _) => true,
+            (Some(query), Some(path)) => query.matches(path),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_entries() {
+        let worklog = WorklogProto::new();
+        let mut worklog = worklog.borrow_mut();
+        for at in 0..MAX_ENTRIES as u64 + 2 {
+            worklog.add_note(at, None, "note");
+        }
+        let entries = worklog.get_entries(&WorklogFilter::new());
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!((entries[0].0, entries[0].1.at), (2, 2));
+        assert_eq!(entries.last().unwrap().0, MAX_ENTRIES as u64 + 1);
+    }
+
+    #[test]
+    fn filters_entries() {
+        let worklog = WorklogProto::new();
+        let mut worklog = worklog.borrow_mut();
+        worklog.add_note(0, Some(".shop.api"), "deployed");
+        worklog.add_note(1, None, "standup");
+        worklog.push(WorklogEntry {
+            at: 2,
+            kind: WorklogKind::Alert,
+            severity: AlertSeverity::Critical,
+            path: Some(".shop.db".to_string()),
+            message: "down".to_string(),
+        });
+        let seqs = |severity: &str, path: &str| -> Vec<u64> {
+            worklog.get_entries(&WorklogFilter::parse(severity, path)).iter().map(|(seq, _)| *seq).collect()
+        };
+        assert_eq!(seqs("", ""), vec![0, 1, 2]);
+        assert_eq!(seqs("critical", ""), vec![2]);
+        assert_eq!(seqs("info", ".shop.*"), vec![0, 2]);
+        assert_eq!(seqs("info", ".shop.api"), vec![0]);
+    }
+}
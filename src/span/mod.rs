@@ -1,2 +1,7 @@
 pub mod cursor;
+pub mod pointer;
+pub mod alerts;
+pub mod picker;
+pub mod worklog;
+pub mod chart;
 pub mod godswood;
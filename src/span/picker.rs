@@ -0,0 +1,236 @@
+use wasm_bindgen::prelude::*;
+use std::any::Any;
+use crate::utils::timestamp_ms;
+use crate::span::pointer::PointerEvent;
+use crate::span::godswood::select::Selection;
+use crate::span::godswood::control::CameraControl;
+
+// Pointer moving at most this many pixels between down and up still clicks
+const CLICK_SLOP: f64 = 4.;
+// Pointer resting this long over a node shows its tooltip
+const HOVER_DELAY_MS: u64 = 300;
+// Tooltip placement relative to the pointer, and its layout
+const TOOLTIP_OFFSET: f64 = 14.;
+const TITLE_HEIGHT: f64 = 20.;
+const ROW_HEIGHT: f64 = 16.;
+const PADDING: f64 = 6.;
+const MAX_WIDTH: f64 = 360.;
+
+// Node details shown while hovering it, `color` marks its status
+pub struct Tooltip {
+    pub title: String,
+    pub color: String,
+    pub rows: Vec<(String, String)>,
+}
+
+// Tooltip of the node at a path, None once it is gone
+pub type TooltipProvider = Box<dyn Fn(&str) -> Option<Tooltip>>;
+
+// Invisible span over the world, clicking it picks the node under the pointer, dragging and the
+// wheel move the camera, and resting the pointer over a node shows its tooltip above the world
+pub struct PickerSpan {
+    pub name: String,
+
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+
+    pub width: f32,
+    pub height: f32,
+    pub order: u8,
+
+    selection: Selection,
+    control: CameraControl,
+    tooltip: TooltipProvider,
+    // Where the button went down, and whether it came up close enough to click
+    down: Option<(f64, f64)>,
+    clicked: bool,
+    // Last pointer position over the span
+    pointer: Option<(f64, f64)>,
+}
+
+impl PickerSpan {
+    pub fn new(
+        selection: Selection,
+        control: CameraControl,
+        tooltip: TooltipProvider,
+        name: &str,
+        width: f32, height: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            x: 0.,
+            y: 0.,
+            w: 0.,
+            h: 0.,
+
+            width,
+            height,
+            order: 1,
+            selection,
+            control,
+            tooltip,
+            down: None,
+            clicked: false,
+            pointer: None,
+        }
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.w && y >= self.y && y <= self.y + self.h
+    }
+
+    // Pointer in normalized device coordinates with the aspect ratio of the span
+    fn to_ndc(&self, x: f64, y: f64) -> Option<(f32, f32, f32)> {
+        if !self.contains(x, y) || self.w <= 0. || self.h <= 0. {
+            return None;
+        }
+        let ndc_x = (x - self.x) / self.w * 2. - 1.;
+        let ndc_y = 1. - (y - self.y) / self.h * 2.;
+        Some((ndc_x as f32, ndc_y as f32, (self.w / self.h) as f32))
+    }
+
+    // Box below right of the pointer, flipped to the other side of it where it would leave the
+    // canvas, and clamped to the canvas otherwise
+    fn draw_tooltip(&self, ctx: &web_sys::CanvasRenderingContext2d, tooltip: &Tooltip, x: f64, y: f64) {
+        let (canvas_w, canvas_h) = ctx.canvas().map_or((self.x + self.w, self.y + self.h), |canvas| {
+            (canvas.width() as f64, canvas.height() as f64)
+        });
+
+        ctx.set_font("12px Arial");
+        let measure = |text: &str| ctx.measure_text(text).map_or(0., |metrics| metrics.width());
+        let key_w = tooltip.rows.iter().map(|(key, _)| measure(key)).fold(0., f64::max);
+        let value_w = tooltip.rows.iter().map(|(_, value)| measure(value)).fold(0., f64::max);
+        let title_w = measure(&tooltip.title);
+        let w = (PADDING * 3. + key_w + value_w).max(PADDING * 2. + title_w).min(MAX_WIDTH).min(canvas_w);
+        let h = (TITLE_HEIGHT + tooltip.rows.len() as f64 * ROW_HEIGHT + PADDING).min(canvas_h);
+
+        let mut left = x + TOOLTIP_OFFSET;
+        if left + w > canvas_w {
+            left = x - TOOLTIP_OFFSET - w;
+        }
+        let mut top = y + TOOLTIP_OFFSET;
+        if top + h > canvas_h {
+            top = y - TOOLTIP_OFFSET - h;
+        }
+        let left = left.max(0.).min(canvas_w - w);
+        let top = top.max(0.).min(canvas_h - h);
+
+        ctx.set_fill_style(&JsValue::from_str("rgba(20, 20, 20, 0.9)"));
+        ctx.fill_rect(left, top, w, h);
+        ctx.set_stroke_style(&JsValue::from_str(&tooltip.color));
+        ctx.stroke_rect(left, top, w, h);
+
+        ctx.set_text_align("left");
+        ctx.set_text_baseline("middle");
+        ctx.set_fill_style(&JsValue::from_str(&tooltip.color));
+        ctx.fill_rect(left + PADDING, top + 6., 4., TITLE_HEIGHT - 10.);
+        ctx.set_font("bold 12px Arial");
+        ctx.set_fill_style(&JsValue::from_str("white"));
+        let _ = ctx.fill_text_with_max_width(&tooltip.title, left + PADDING + 8., top + TITLE_HEIGHT / 2., w - PADDING * 2. - 8.);
+
+        ctx.set_font("12px Arial");
+        let key_w = key_w.min((w - PADDING * 3.) * 0.4);
+        for (i, (key, value)) in tooltip.rows.iter().enumerate() {
+            let middle = top + TITLE_HEIGHT + i as f64 * ROW_HEIGHT + ROW_HEIGHT / 2.;
+            if middle > top + h {
+                break;
+            }
+            ctx.set_fill_style(&JsValue::from_str("gray"));
+            let _ = ctx.fill_text_with_max_width(key, left + PADDING, middle, key_w.max(1.));
+            ctx.set_fill_style(&JsValue::from_str("lightgray"));
+            let value_left = left + PADDING * 2. + key_w;
+            let _ = ctx.fill_text_with_max_width(value, value_left, middle, (left + w - PADDING - value_left).max(1.));
+        }
+    }
+}
+
+impl wand::SpanTrait for PickerSpan {
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn dispatch_event(&mut self, _ev: &mut wand::component::Event) {
+    }
+
+    fn dispath(&mut self, data: Box<dyn Any>) {
+        let event = match data.downcast::<PointerEvent>() {
+            Ok(event) => *event,
+            Err(_) => return,
+        };
+        match event {
+            PointerEvent::Down { x, y } => {
+                self.down = if self.contains(x, y) { Some((x, y)) } else { None };
+            },
+            PointerEvent::Up { x, y } => {
+                self.clicked = self.down.take().is_some_and(|(down_x, down_y)| {
+                    (x - down_x).abs() <= CLICK_SLOP && (y - down_y).abs() <= CLICK_SLOP
+                });
+            },
+            PointerEvent::Click { x, y } => {
+                if !std::mem::replace(&mut self.clicked, false) {
+                    return;
+                }
+                if let Some((ndc_x, ndc_y, aspect)) = self.to_ndc(x, y) {
+                    self.selection.borrow_mut().request_pick(ndc_x, ndc_y, aspect);
+                }
+            },
+            // Only drags started over the world move the camera
+            PointerEvent::Drag { dx, dy, pan, .. } => {
+                if self.down.is_none() || self.w <= 0. || self.h <= 0. {
+                    return;
+                }
+                let (dx, dy) = ((dx / self.w * 2.) as f32, (-dy / self.h * 2.) as f32);
+                let mut control = self.control.borrow_mut();
+                if pan {
+                    control.pan(dx, dy, (self.w / self.h) as f32);
+                } else {
+                    control.orbit(dx, dy);
+                }
+            },
+            PointerEvent::Wheel { x, y, delta } => {
+                if let Some((ndc_x, ndc_y, aspect)) = self.to_ndc(x, y) {
+                    self.control.borrow_mut().zoom(delta as f32, ndc_x, ndc_y, aspect);
+                }
+            },
+            PointerEvent::Move { x, y } => {
+                let pointer = self.to_ndc(x, y);
+                self.pointer = pointer.map(|_| (x, y));
+                self.selection.borrow_mut().set_pointer(pointer);
+            },
+        }
+    }
+
+    fn render_tick(&self, ctx: &web_sys::CanvasRenderingContext2d) {
+        // Hidden while a button is held
+        let (x, y) = match self.pointer {
+            Some(pointer) if self.down.is_none() => pointer,
+            _ => return,
+        };
+        let selection = self.selection.borrow();
+        let path = match selection.get_hovered() {
+            Some((path, since)) if timestamp_ms().saturating_sub(since) >= HOVER_DELAY_MS => path,
+            _ => return,
+        };
+        if let Some(tooltip) = (self.tooltip)(path) {
+            self.draw_tooltip(ctx, &tooltip, x, y);
+        }
+    }
+
+    fn on_resize(&mut self, left: f64, top: f64, right: f64, bottom: f64) -> (f64, f64, bool) {
+        // Stale until the pointer moves again
+        self.pointer = None;
+        self.selection.borrow_mut().set_pointer(None);
+        self.x = left;
+        self.y = top;
+        self.w = self.width as f64 * (right - left);
+        self.h = self.height as f64 * (bottom - top);
+        (0., 0., true)
+    }
+
+    fn get_order(&self) -> u8 {
+        self.order
+    }
+
+}
@@ -0,0 +1,12 @@
+// Pointer input handed to spans through `dispath`, in canvas pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PointerEvent {
+    Move { x: f64, y: f64 },
+    Down { x: f64, y: f64 },
+    Up { x: f64, y: f64 },
+    Click { x: f64, y: f64 },
+    // Moved by `dx` and `dy` with a button held, panning instead of orbiting when `pan`
+    Drag { x: f64, y: f64, dx: f64, dy: f64, pan: bool },
+    // Positive `delta` scrolls down, in pixels
+    Wheel { x: f64, y: f64, delta: f64 },
+}
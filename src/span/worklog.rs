@@ -0,0 +1,239 @@
+use std::cell::Cell;
+use wasm_bindgen::prelude::*;
+use std::any::Any;
+use crate::utils::format_clock;
+use crate::span::pointer::PointerEvent;
+use crate::span::godswood::worklog::{Worklog, WorklogEntry, WorklogFilter};
+
+const HEADER_HEIGHT: f64 = 24.;
+const ROW_HEIGHT: f64 = 18.;
+const PADDING: f64 = 6.;
+
+// Event log with the newest entries at the bottom, following them until scrolled up
+pub struct WorklogSpan {
+    pub name: String,
+
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+
+    pub width: f32,
+    pub height: f32,
+    pub order: u8,
+
+    worklog: Worklog,
+    filter: WorklogFilter,
+    // Rows scrolled up from the newest entry, zero follows new entries
+    scroll: Cell<usize>,
+    // Matching entries at the last render
+    count: Cell<usize>,
+    // Sequence number of the newest matching entry at the last render, to keep the view in place
+    // as entries come in
+    newest: Cell<Option<u64>>,
+}
+
+impl WorklogSpan {
+    pub fn new(
+        worklog: Worklog,
+        name: &str,
+        width: f32, height: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            x: 0.,
+            y: 0.,
+            w: 0.,
+            h: 0.,
+
+            width,
+            height,
+            order: 1,
+            worklog,
+            filter: WorklogFilter::new(),
+            scroll: Cell::new(0),
+            count: Cell::new(0),
+            newest: Cell::new(None),
+        }
+    }
+
+    fn visible_rows(&self) -> usize {
+        ((self.h - HEADER_HEIGHT) / ROW_HEIGHT).floor().max(0.) as usize
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.w && y >= self.y && y <= self.y + self.h
+    }
+
+    // Rows are moved by whole steps, a negative delta scrolls up to older entries
+    fn scroll_by(&self, delta: f64) {
+        let rows = (delta.abs() / ROW_HEIGHT).ceil() as usize;
+        let max = self.count.get().saturating_sub(self.visible_rows());
+        let scroll = if delta < 0. {
+            self.scroll.get() + rows
+        } else {
+            self.scroll.get().saturating_sub(rows)
+        };
+        self.scroll.set(scroll.min(max));
+    }
+
+    // Scrolled up views stay on the same entries while new ones come in below, returns the rows
+    // scrolled up
+    fn follow(&self, entries: &[(u64, &WorklogEntry)]) -> usize {
+        let newest = self.newest.get();
+        if self.scroll.get() > 0 {
+            let added = entries.iter().rev().take_while(|(seq, _)| newest.is_some_and(|newest| *seq > newest)).count();
+            self.scroll.set(self.scroll.get() + added);
+        }
+        self.count.set(entries.len());
+        self.newest.set(entries.last().map(|(seq, _)| *seq).or(newest));
+        let scroll = self.scroll.get().min(entries.len().saturating_sub(self.visible_rows()));
+        self.scroll.set(scroll);
+        scroll
+    }
+
+    fn draw_outline(&self, ctx: &web_sys::CanvasRenderingContext2d) {
+        ctx.set_stroke_style(&JsValue::from_str("white"));
+        ctx.stroke_rect(self.x, self.y, self.w, self.h);
+    }
+
+    fn draw_row(&self, ctx: &web_sys::CanvasRenderingContext2d, entry: &WorklogEntry, top: f64) {
+        let middle = top + ROW_HEIGHT / 2.;
+        let inner = (self.w - 2. * PADDING - 10.).max(0.);
+        let left = self.x + PADDING + 10.;
+        ctx.set_fill_style(&JsValue::from_str(entry.severity.color()));
+        ctx.fill_rect(self.x + PADDING, top + 3., 4., ROW_HEIGHT - 6.);
+
+        // Time, kind, path and message columns
+        ctx.set_fill_style(&JsValue::from_str("gray"));
+        let _ = ctx.fill_text_with_max_width(&format_clock(entry.at), left, middle, inner * 0.1);
+        let _ = ctx.fill_text_with_max_width(entry.kind.as_str(), left + inner * 0.11, middle, inner * 0.08);
+        ctx.set_fill_style(&JsValue::from_str(entry.severity.color()));
+        let _ = ctx.fill_text_with_max_width(
+            entry.path.as_ref().map_or("", |path| path.as_str()), left + inner * 0.2, middle, inner * 0.3
+        );
+        ctx.set_fill_style(&JsValue::from_str("lightgray"));
+        let _ = ctx.fill_text_with_max_width(&entry.message, left + inner * 0.52, middle, inner * 0.48);
+    }
+}
+
+impl wand::SpanTrait for WorklogSpan {
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn dispatch_event(&mut self, _ev: &mut wand::component::Event) {
+    }
+
+    fn dispath(&mut self, data: Box<dyn Any>) {
+        let data = match data.downcast::<WorklogFilter>() {
+            Ok(filter) => {
+                self.filter = *filter;
+                self.scroll.set(0);
+                self.count.set(0);
+                self.newest.set(None);
+                return;
+            },
+            Err(data) => data,
+        };
+        if let Ok(event) = data.downcast::<PointerEvent>() {
+            if let PointerEvent::Wheel { x, y, delta } = *event {
+                if self.contains(x, y) {
+                    self.scroll_by(delta);
+                }
+            }
+        }
+    }
+
+    fn render_tick(&self, ctx: &web_sys::CanvasRenderingContext2d) {
+        self.draw_outline(ctx);
+        let worklog = self.worklog.borrow();
+        let entries = worklog.get_entries(&self.filter);
+
+        let scroll = self.follow(&entries);
+        let count = entries.len();
+        let end = count - scroll;
+        let start = end.saturating_sub(self.visible_rows());
+
+        ctx.set_text_align("left");
+        ctx.set_text_baseline("middle");
+        ctx.set_font("14px Arial");
+        ctx.set_fill_style(&JsValue::from_str("white"));
+        let title = match scroll {
+            0 => format!("Worklog ({})", count),
+            _ => format!("Worklog ({}), {} newer below", count, scroll),
+        };
+        let _ = ctx.fill_text(&title, self.x + PADDING, self.y + HEADER_HEIGHT / 2.);
+
+        ctx.set_font("12px Arial");
+        for (i, (_, entry)) in entries[start..end].iter().enumerate() {
+            self.draw_row(ctx, entry, self.y + HEADER_HEIGHT + i as f64 * ROW_HEIGHT);
+        }
+    }
+
+    fn on_resize(&mut self, left: f64, top: f64, right: f64, bottom: f64) -> (f64, f64, bool) {
+        self.x = left;
+        self.y = top;
+        self.w = self.width as f64 * (right - left);
+        self.h = self.height as f64 * (bottom - top);
+        (0., 0., true)
+    }
+
+    fn get_order(&self) -> u8 {
+        self.order
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::godswood::worklog::WorklogProto;
+
+    // Ten rows high
+    fn span(worklog: &Worklog) -> WorklogSpan {
+        let mut span = WorklogSpan::new(worklog.clone(), "worklog", 1., 1.);
+        span.h = HEADER_HEIGHT + 10. * ROW_HEIGHT;
+        span
+    }
+
+    fn follow(span: &WorklogSpan, worklog: &Worklog) -> usize {
+        span.follow(&worklog.borrow().get_entries(&WorklogFilter::new()))
+    }
+
+    #[test]
+    fn follows_new_entries() {
+        let worklog = WorklogProto::new();
+        let span = span(&worklog);
+        for at in 0..20 {
+            worklog.borrow_mut().add_note(at, None, "note");
+        }
+        assert_eq!(follow(&span, &worklog), 0);
+        worklog.borrow_mut().add_note(20, None, "note");
+        assert_eq!(follow(&span, &worklog), 0);
+
+        span.scroll_by(-3. * ROW_HEIGHT);
+        assert_eq!(follow(&span, &worklog), 3);
+        worklog.borrow_mut().add_note(21, None, "note");
+        assert_eq!(follow(&span, &worklog), 4);
+        span.scroll_by(-100. * ROW_HEIGHT);
+        assert_eq!(follow(&span, &worklog), 12);
+    }
+
+    #[test]
+    fn stays_in_place_when_full() {
+        let worklog = WorklogProto::new();
+        let span = span(&worklog);
+        // As many as the worklog keeps
+        for at in 0..10_000 {
+            worklog.borrow_mut().add_note(at, None, "note");
+        }
+        follow(&span, &worklog);
+        span.scroll_by(-5. * ROW_HEIGHT);
+        assert_eq!(follow(&span, &worklog), 5);
+        for at in 10_000..10_003 {
+            worklog.borrow_mut().add_note(at, None, "note");
+        }
+        assert_eq!(follow(&span, &worklog), 8);
+    }
+}
@@ -2,6 +2,7 @@ use std::time::{SystemTime, UNIX_EPOCH, Duration, Instant};
 use serde_json::Value;
 use serde_json::map::Map;
 use serde_json::value::Index;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 
@@ -21,10 +22,18 @@ pub fn set_panic_hook() {
 #[macro_export]
 macro_rules! log {
     ( $( $t:tt )* ) => {
-        wand::utils::log(&format!( $( $t )* ));
+        $crate::utils::log(&format!( $( $t )* ));
     }
 }
 
+// Browser console, or stderr for native unit tests
+pub fn log(msg: &str) {
+    #[cfg(target_arch = "wasm32")]
+    wand::utils::log(msg);
+    #[cfg(not(target_arch = "wasm32"))]
+    eprintln!("{}", msg);
+}
+
 #[wasm_bindgen]
 extern {
     fn alert(s: &str);
@@ -40,6 +49,8 @@ pub trait JsonParser {
     fn get_str<I: Index>(&self, index: I, default: &str) -> String;
     fn get_u64<I: Index>(&self, index: I, default: u64) -> u64;
     fn get_f64<I: Index>(&self, index: I, default: f64) -> f64;
+    fn get_str_map<I: Index>(&self, index: I) -> HashMap<String, String>;
+    fn get_str_list<I: Index>(&self, index: I) -> Vec<String>;
 }
 #[allow(dead_code)]
 #[inline]
@@ -53,6 +64,29 @@ pub fn now_ms() -> u128 {
     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()
 }
 
+// Milliseconds since epoch from the browser clock, `now_ms` panics on wasm
+#[inline]
+pub fn timestamp_ms() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+// Local wall clock time of a timestamp in ms, like `14:05:09`
+pub fn format_clock(ms: u64) -> String {
+    let date = js_sys::Date::new(&JsValue::from_f64(ms as f64));
+    format!("{:02}:{:02}:{:02}", date.get_hours(), date.get_minutes(), date.get_seconds())
+}
+
+// Short human readable duration, like `45s`, `3m 12s` or `2d 4h`
+pub fn format_duration(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        3600..=86399 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
 #[allow(dead_code)]
 impl JsonParser for Value {
     fn get_bool<I: Index>(&self, index: I, default: bool) -> bool {
@@ -87,6 +121,28 @@ impl JsonParser for Value {
             None => default,
         }
     }
+
+    // Scalar values are kept as their text, nested values are dropped
+    fn get_str_map<I: Index>(&self, index: I) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        if let Some(v) = self[index].as_object() {
+            for (key, value) in v.iter() {
+                match value {
+                    Value::String(text) => { map.insert(key.clone(), text.clone()); },
+                    Value::Number(_) | Value::Bool(_) => { map.insert(key.clone(), value.to_string()); },
+                    _ => {},
+                }
+            }
+        }
+        map
+    }
+
+    fn get_str_list<I: Index>(&self, index: I) -> Vec<String> {
+        match self[index].as_array() {
+            Some(v) => v.iter().filter_map(|item| item.as_str()).map(|item| item.to_string()).collect(),
+            None => Vec::new(),
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -104,6 +160,12 @@ pub fn get_u32_le(v: &[u8]) -> u32 {
     v[0] as u32
 }
 
+#[allow(dead_code)]
+#[inline]
+pub fn get_u64_le(v: &[u8]) -> u64 {
+    ((get_u32_le(&v[4..]) as u64) << 32) | get_u32_le(v) as u64
+}
+
 #[derive(Debug)]
 pub struct CodecError;
 impl fmt::Display for CodecError {
@@ -21,6 +21,27 @@ window.addEventListener("resize", resize);
 window.addEventListener("mousemove", e => {
   let rec = canvas.getBoundingClientRect();
   app.on_mouse_move(e.clientX - rec.left, e.clientY - rec.top);
+  // Left drag orbits, right or shift drag pans
+  if (e.buttons & 3) {
+    app.on_drag(e.clientX - rec.left, e.clientY - rec.top, e.movementX, e.movementY, (e.buttons & 2) !== 0 || e.shiftKey);
+  }
+});
+canvas.addEventListener("contextmenu", e => e.preventDefault());
+window.addEventListener("mousedown", e => {
+  let rec = canvas.getBoundingClientRect();
+  app.on_mouse_down(e.clientX - rec.left, e.clientY - rec.top);
+});
+window.addEventListener("mouseup", e => {
+  let rec = canvas.getBoundingClientRect();
+  app.on_mouse_up(e.clientX - rec.left, e.clientY - rec.top);
+});
+window.addEventListener("click", e => {
+  let rec = canvas.getBoundingClientRect();
+  app.on_click(e.clientX - rec.left, e.clientY - rec.top);
+});
+window.addEventListener("wheel", e => {
+  let rec = canvas.getBoundingClientRect();
+  app.on_wheel(e.clientX - rec.left, e.clientY - rec.top, e.deltaY);
 });
 
 const renderer = () => {